@@ -0,0 +1,151 @@
+//! HTTP detection server, answering `POST /detect` with the detections of the image in the body.
+//! The model is loaded once and shared by every request, see `yolo_rs::server`.
+//!
+//! ```sh
+//! wasmedge --dir .:. http-server.wasm --manifest ./example_inputs/yolov8n.toml --addr 127.0.0.1:8080
+//! curl --data-binary @./example_inputs/busy_street.png http://127.0.0.1:8080/detect
+//! ```
+
+use std::{
+    net::{SocketAddr, TcpListener, TcpStream},
+    time::Duration,
+};
+
+use clap::Parser;
+use yolo_rs::{server::DetectionServer, Yolo, YoloBuilder};
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// path to model file, with encoding
+    #[arg(short, long, required_unless_present = "manifest")]
+    model_path: Option<String>,
+
+    /// classnames path
+    #[arg(short, long, required_unless_present = "manifest")]
+    class_names_path: Option<String>,
+
+    /// path to a model manifest (.toml / .yaml), replaces --model-path and --class-names-path
+    #[arg(long, conflicts_with_all = ["model_path", "class_names_path"])]
+    manifest: Option<String>,
+
+    /// address to listen on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    addr: SocketAddr,
+
+    /// seconds a client has to send its request and read the response, 0 to wait forever
+    #[arg(long, default_value_t = 30)]
+    timeout: u64,
+}
+
+/// Listening socket opened through WasmEdge's socket extension of WASI,
+/// the standard library only accepts connections on an already open socket
+#[cfg(target_os = "wasi")]
+mod wasmedge_socket {
+    use std::{
+        io,
+        net::{SocketAddr, TcpListener},
+        os::wasi::io::FromRawFd,
+    };
+
+    const ADDRESS_FAMILY_INET4: u8 = 1;
+    const ADDRESS_FAMILY_INET6: u8 = 2;
+    const SOCKET_TYPE_STREAM: u8 = 2;
+    const BACKLOG: u32 = 128;
+
+    #[repr(C)]
+    struct WasiAddress {
+        buf: *const u8,
+        size: usize,
+    }
+
+    #[link(wasm_import_module = "wasi_snapshot_preview1")]
+    extern "C" {
+        fn sock_open(addr_family: u8, sock_type: u8, fd: *mut u32) -> u32;
+        fn sock_bind(fd: u32, addr: *mut WasiAddress, port: u32) -> u32;
+        fn sock_listen(fd: u32, backlog: u32) -> u32;
+    }
+
+    fn check(errno: u32) -> io::Result<()> {
+        match errno {
+            0 => Ok(()),
+            errno => Err(io::Error::from_raw_os_error(errno as i32)),
+        }
+    }
+
+    pub fn bind(addr: SocketAddr) -> io::Result<TcpListener> {
+        let (family, octets) = match addr {
+            SocketAddr::V4(addr) => (ADDRESS_FAMILY_INET4, addr.ip().octets().to_vec()),
+            SocketAddr::V6(addr) => (ADDRESS_FAMILY_INET6, addr.ip().octets().to_vec()),
+        };
+        let mut address = WasiAddress {
+            buf: octets.as_ptr(),
+            size: octets.len(),
+        };
+
+        let mut fd = 0;
+        unsafe {
+            check(sock_open(family, SOCKET_TYPE_STREAM, &mut fd))?;
+            check(sock_bind(fd, &mut address, addr.port() as u32))?;
+            check(sock_listen(fd, BACKLOG))?;
+            Ok(TcpListener::from_raw_fd(fd as i32))
+        }
+    }
+}
+
+#[cfg(target_os = "wasi")]
+fn bind(addr: SocketAddr) -> std::io::Result<TcpListener> {
+    wasmedge_socket::bind(addr)
+}
+
+#[cfg(not(target_os = "wasi"))]
+fn bind(addr: SocketAddr) -> std::io::Result<TcpListener> {
+    TcpListener::bind(addr)
+}
+
+/// Requests are served one at a time, without timeouts a stalled client would block every other one.
+/// WASI preview 1 has no socket options, the standard library returns `Unsupported` there
+fn set_timeouts(stream: &TcpStream, timeout: Option<Duration>) -> std::io::Result<()> {
+    stream.set_read_timeout(timeout)?;
+    stream.set_write_timeout(timeout)
+}
+
+pub fn main() {
+    let args = Args::parse();
+
+    // Create YOLO instance, once for every request
+    let yolo: Yolo = match args.manifest {
+        Some(manifest) => YoloBuilder::from_manifest(manifest).unwrap().build(),
+        None => YoloBuilder::new()
+            .classes_file(args.class_names_path.unwrap())
+            .unwrap()
+            .build_from_files([args.model_path.unwrap()]),
+    }
+    .unwrap();
+    let server = DetectionServer::new(yolo);
+
+    let listener = bind(args.addr).unwrap();
+    println!("Listening on http://{}", args.addr);
+
+    let timeout = (args.timeout > 0).then(|| Duration::from_secs(args.timeout));
+    let mut timeouts_supported = true;
+
+    // Requests are answered one at a time, a failed connection does not stop the server
+    for stream in listener.incoming() {
+        let result = stream.map_err(Into::into).and_then(|stream| {
+            if timeouts_supported {
+                if let Err(error) = set_timeouts(&stream, timeout) {
+                    eprintln!(
+                        "Connection timeouts are not supported, serving without: {}",
+                        error
+                    );
+                    timeouts_supported = false;
+                }
+            }
+            server.serve(stream)
+        });
+        if let Err(error) = result {
+            eprintln!("Connection error: {}", error);
+        }
+    }
+}
@@ -2,46 +2,54 @@ use std::fs;
 
 use clap::Parser;
 use image::ImageFormat;
-use yolo_rs::{
-    utils::{self, draw_bounding_boxes_on_mut_image},
-    ConfThresh, IOUThresh, Yolo, YoloBuilder,
-};
+use yolo_rs::{jsonl::JsonlWriter, utils::draw_bounding_boxes, Yolo, YoloBuilder};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
     /// path to model file, with encoding
-    #[arg(short, long)]
-    model_path: String,
+    #[arg(short, long, required_unless_present = "manifest")]
+    model_path: Option<String>,
 
     /// classnames path
-    #[arg(short, long)]
-    class_names_path: String,
+    #[arg(short, long, required_unless_present = "manifest")]
+    class_names_path: Option<String>,
+
+    /// path to a model manifest (.toml / .yaml), replaces --model-path and --class-names-path
+    #[arg(long, conflicts_with_all = ["model_path", "class_names_path"])]
+    manifest: Option<String>,
 
     /// image path
     #[arg(short, long)]
     image_path: String,
-    
+
+    /// write the detections as a JSON line to this file, `-` for stdout
+    #[arg(long)]
+    jsonl: Option<String>,
 }
 
 pub fn main() {
     let args = Args::parse();
-    println!("model_bin_name {}", args.model_path);
-    println!("image_name {}", args.image_path);
+    // Keep stdout for the JSON lines
+    eprintln!("model_bin_name {:?}", args.model_path);
+    eprintln!("image_name {}", args.image_path);
 
     // Create YOLO instance
-    let yolo: Yolo = YoloBuilder::new()
-        .classes_file(args.class_names_path)
-        .unwrap()
-        // .execution_target(wasi_nn::ExecutionTarget::GPU)
-        .build_from_files([args.model_path])
-        .unwrap();
+    let yolo: Yolo = match args.manifest {
+        Some(manifest) => YoloBuilder::from_manifest(manifest).unwrap().build(),
+        None => YoloBuilder::new()
+            .classes_file(args.class_names_path.unwrap())
+            .unwrap()
+            // .execution_target(wasi_nn::ExecutionTarget::GPU)
+            .build_from_files([args.model_path.unwrap()]),
+    }
+    .unwrap();
 
-    let conf_thresh = ConfThresh(0.5);
-    let iou_thresh = IOUThresh(0.5);
+    let conf_thresh = yolo.default_conf_thresh();
+    let iou_thresh = yolo.default_iou_thresh();
 
     // Load in the image
-    let image_bytes = fs::read(args.image_path).unwrap();
+    let image_bytes = fs::read(&args.image_path).unwrap();
     let rgb_image = image::load_from_memory(&image_bytes).unwrap().to_rgb8();
 
     // Run inference
@@ -53,12 +61,17 @@ pub fn main() {
     let vec_result = yolo
         .infer_image(&conf_thresh, &iou_thresh, &rgb_image)
         .unwrap();
-    println!("Detection Results {:?}", vec_result);
-    let output_image = draw_bounding_boxes_on_mut_image(
-        rgb_image,
-        &vec_result,
-        &utils::load_font("./yolo-rs-wasm/assets/ClearSans-Medium.ttf").unwrap(),
-    );
+    match args.jsonl.as_deref() {
+        Some("-") => JsonlWriter::stdout()
+            .write(&args.image_path, 0, &vec_result)
+            .unwrap(),
+        Some(path) => JsonlWriter::create(path)
+            .unwrap()
+            .write(&args.image_path, 0, &vec_result)
+            .unwrap(),
+        None => println!("Detection Results {:?}", vec_result),
+    }
+    let output_image = draw_bounding_boxes(rgb_image, &vec_result);
 
     output_image
         .save_with_format("output.png", ImageFormat::Png)
@@ -1,18 +1,29 @@
+use std::io::Write;
+
 use clap::Parser;
 use log::LevelFilter;
 use simplelog::{ColorChoice, CombinedLogger, Config, TermLogger, TerminalMode};
-use yolo_rs::{utils, ConfThresh, DrawBoundingBoxes, IOUThresh, Yolo, YoloBuilder};
+use yolo_rs::{
+    jsonl::JsonlWriter,
+    tracking::{Tracker, TrackerConfig},
+    utils::Anonymization,
+    ConfThresh, DrawBoundingBoxes, Yolo, YoloBuilder,
+};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
     /// path to model file, with encoding
-    #[arg(short, long)]
-    model_path: String,
+    #[arg(short, long, required_unless_present = "manifest")]
+    model_path: Option<String>,
 
     /// classnames path
-    #[arg(short, long)]
-    class_names_path: String,
+    #[arg(short, long, required_unless_present = "manifest")]
+    class_names_path: Option<String>,
+
+    /// path to a model manifest (.toml / .yaml), replaces --model-path and --class-names-path
+    #[arg(long, conflicts_with_all = ["model_path", "class_names_path"])]
+    manifest: Option<String>,
 
     /// video_path
     #[arg(short, long)]
@@ -21,43 +32,102 @@ struct Args {
     /// video_path
     #[arg(short, long)]
     output_video_path: String,
+
+    /// assign track ids to the detections, linking objects across frames
+    #[arg(long)]
+    track: bool,
+
+    /// pixelate the detections of these comma separated classes instead of drawing boxes, e.g. `person,car`
+    #[arg(long, value_delimiter = ',')]
+    anonymize: Option<Vec<String>>,
+
+    /// write the detections of each frame as a JSON line to this file as soon as they are produced,
+    /// `-` for stdout
+    #[arg(long)]
+    jsonl: Option<String>,
+
+    /// frame rate of the input video, adds the time of each frame in the video to the JSON lines
+    #[arg(long)]
+    fps: Option<f64>,
 }
 
 pub fn main() {
     let args = Args::parse();
-    println!("model_bin_name {}", args.model_path);
-    println!("input_video_path {}", args.input_video_path);
-    println!("output_video_path {}", args.output_video_path);
+    // Keep stdout for the JSON lines
+    eprintln!("model_bin_name {:?}", args.model_path);
+    eprintln!("input_video_path {}", args.input_video_path);
+    eprintln!("output_video_path {}", args.output_video_path);
 
+    let terminal_mode = match args.jsonl.as_deref() {
+        Some("-") => TerminalMode::Stderr,
+        _ => TerminalMode::Mixed,
+    };
     CombinedLogger::init(vec![TermLogger::new(
         LevelFilter::Info,
         Config::default(),
-        TerminalMode::Mixed,
+        terminal_mode,
         ColorChoice::Always,
     )])
     .unwrap();
 
     // Create YOLO instance
-    let yolo: Yolo = YoloBuilder::new()
-        .classes_file(args.class_names_path)
-        .unwrap()
-        .build_from_files([args.model_path])
-        .unwrap();
-
-    let conf_thresh = ConfThresh(0.5);
-    let iou_thresh = IOUThresh(0.5);
-
-    let video_results = yolo
-        .infer_video(
-            args.input_video_path,
-            args.output_video_path,
-            &conf_thresh,
-            &iou_thresh,
-            DrawBoundingBoxes::TrueWithFont(
-                utils::load_font("./yolo-rs-wasm/assets/ClearSans-Medium.ttf").unwrap(),
-            ),
-        )
-        .unwrap();
+    let yolo: Yolo = match args.manifest {
+        Some(manifest) => YoloBuilder::from_manifest(manifest).unwrap().build(),
+        None => YoloBuilder::new()
+            .classes_file(args.class_names_path.unwrap())
+            .unwrap()
+            .build_from_files([args.model_path.unwrap()]),
+    }
+    .unwrap();
+
+    let mut conf_thresh = yolo.default_conf_thresh();
+    let iou_thresh = yolo.default_iou_thresh();
+
+    // The tracker uses low confidence detections to keep existing tracks alive,
+    // only detections above the default threshold start new tracks
+    let mut tracker = args.track.then(|| {
+        let config = TrackerConfig {
+            high_conf_thresh: conf_thresh.0,
+            ..Default::default()
+        };
+        conf_thresh = ConfThresh(config.low_conf_thresh.min(conf_thresh.0));
+        Tracker::new(config)
+    });
+
+    let draw_bounding_boxes = match args.anonymize {
+        Some(classes) => DrawBoundingBoxes::Anonymize {
+            classes,
+            anonymization: Anonymization::default(),
+        },
+        None => DrawBoundingBoxes::Default,
+    };
+
+    // Without --jsonl, the results of every frame are printed at the end
+    let mut jsonl = args.jsonl.as_deref().map(|path| match path {
+        "-" => JsonlWriter::new(Box::new(std::io::stdout()) as Box<dyn Write>),
+        path => JsonlWriter::new(Box::new(std::fs::File::create(path).unwrap()) as Box<dyn Write>),
+    });
+    if let Some(fps) = args.fps {
+        jsonl = jsonl.map(|writer| writer.with_fps(fps));
+    }
+    let mut video_results = Vec::new();
+
+    yolo.infer_video_with(
+        &args.input_video_path,
+        &args.output_video_path,
+        &conf_thresh,
+        &iou_thresh,
+        draw_bounding_boxes,
+        tracker.as_mut(),
+        |frame, results| {
+            match jsonl.as_mut() {
+                Some(writer) => writer.write(&args.input_video_path, frame, results)?,
+                None => video_results.push(results.clone()),
+            }
+            Ok(())
+        },
+    )
+    .unwrap();
 
     for (idx, result) in video_results.iter().enumerate() {
         println!("Inference Results for Frame {}, {:?}", idx, result);
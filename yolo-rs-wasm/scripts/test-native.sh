@@ -0,0 +1,6 @@
+set -e
+
+# Builds and tests the library on the host, without the wasm-only WASI-NN and video plugin pieces.
+# Extra arguments are passed to cargo, e.g. `--features pure-rust`
+cargo clippy --target x86_64-unknown-linux-gnu --no-default-features --all-targets "$@" -- -D warnings
+cargo test --target x86_64-unknown-linux-gnu --no-default-features "$@"
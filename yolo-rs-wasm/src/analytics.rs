@@ -0,0 +1,514 @@
+//! Line crossing and zone counting over the per-frame results of a video
+//!
+//! Lines and zones are defined in image coordinates. Every detection is reduced to an [`Anchor`] point:
+//! - a tracked detection crosses a line when its anchor moves across the line between two frames,
+//!   from the left to the right of the line (looking from `start` to `end` on screen) counts as
+//!   [`Direction::In`], the opposite as [`Direction::Out`]
+//! - a detection occupies a zone when its anchor is inside the zone polygon
+//!
+//! Crossings and zone enter / exit events need the track ids assigned by a
+//! [`Tracker`](crate::tracking::Tracker), occupancy is counted for every detection.
+//! A track not seen for more than [`DEFAULT_TRACK_MAX_AGE`] frames is forgotten,
+//! and exits the zones it was last seen in.
+//!
+//! Events are only returned in the [`FrameReport`] of their frame, [`Analytics`] keeps the running
+//! counts but no history of events, so that its memory stays bounded on a live stream.
+//!
+//! ```rust,ignore
+//! let mut analytics = Analytics::new()
+//!     .with_line("stop line", (0., 400.), (1280., 400.))
+//!     .with_zone("crossing", [(100., 300.), (600., 300.), (600., 500.), (100., 500.)]);
+//!
+//! let frames = yolo.infer_video(input, output, &conf, &iou, DrawBoundingBoxes::False, Some(&mut tracker))?;
+//! let reports = analytics.process_frames(&frames, 30.0);
+//! println!("{} cars in", analytics.line_counts("stop line").unwrap().in_count("car"));
+//! ```
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
+
+use crate::{
+    geometry::{Anchor, Line, Point, Polygon},
+    InferenceResult,
+};
+
+/// Number of frames a track can go unseen before it is forgotten, the default `max_age` of
+/// [`TrackerConfig`](crate::tracking::TrackerConfig)
+pub const DEFAULT_TRACK_MAX_AGE: usize = 30;
+
+/// Direction of a line crossing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    In,
+    Out,
+}
+
+/// Running per-class crossing totals of a line
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineCounts {
+    in_counts: BTreeMap<String, u64>,
+    out_counts: BTreeMap<String, u64>,
+}
+
+impl LineCounts {
+    pub fn in_count(&self, class: &str) -> u64 {
+        self.in_counts.get(class).copied().unwrap_or(0)
+    }
+
+    pub fn out_count(&self, class: &str) -> u64 {
+        self.out_counts.get(class).copied().unwrap_or(0)
+    }
+
+    pub fn total_in(&self) -> u64 {
+        self.in_counts.values().sum()
+    }
+
+    pub fn total_out(&self) -> u64 {
+        self.out_counts.values().sum()
+    }
+
+    /// Crossings in, by class
+    pub fn in_counts(&self) -> &BTreeMap<String, u64> {
+        &self.in_counts
+    }
+
+    /// Crossings out, by class
+    pub fn out_counts(&self) -> &BTreeMap<String, u64> {
+        &self.out_counts
+    }
+
+    fn record(&mut self, class: &str, direction: Direction) {
+        let counts = match direction {
+            Direction::In => &mut self.in_counts,
+            Direction::Out => &mut self.out_counts,
+        };
+        *counts.entry(class.to_string()).or_default() += 1;
+    }
+}
+
+/// Number of detections inside a zone on a single frame, by class
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ZoneOccupancy {
+    pub zone: String,
+    pub counts: BTreeMap<String, usize>,
+}
+
+impl ZoneOccupancy {
+    pub fn count(&self, class: &str) -> usize {
+        self.counts.get(class).copied().unwrap_or(0)
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.values().sum()
+    }
+}
+
+/// Something that happened at a given time of the video
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnalyticsEvent {
+    LineCrossing {
+        frame: usize,
+        timestamp: Duration,
+        line: String,
+        class: String,
+        track_id: u64,
+        direction: Direction,
+    },
+    ZoneEnter {
+        frame: usize,
+        timestamp: Duration,
+        zone: String,
+        class: String,
+        track_id: u64,
+    },
+    ZoneExit {
+        frame: usize,
+        timestamp: Duration,
+        zone: String,
+        class: String,
+        track_id: u64,
+    },
+}
+
+/// Analytics of a single frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameReport {
+    pub frame: usize,
+    pub timestamp: Duration,
+    /// Occupancy of every zone, in the order the zones were added
+    pub zone_occupancy: Vec<ZoneOccupancy>,
+    /// Events of this frame
+    pub events: Vec<AnalyticsEvent>,
+}
+
+#[derive(Debug, Clone)]
+struct CountingLine {
+    name: String,
+    line: Line,
+    counts: LineCounts,
+}
+
+#[derive(Debug, Clone)]
+struct Zone {
+    name: String,
+    polygon: Polygon,
+}
+
+/// Last known state of a track
+#[derive(Debug, Clone)]
+struct TrackState {
+    class: String,
+    position: Point,
+    /// Whether the track is inside each zone
+    in_zones: Vec<bool>,
+    /// Frame the track was last seen on
+    last_frame: usize,
+}
+
+/// Counts line crossings and zone occupancy frame by frame, see the [module documentation](self)
+#[derive(Debug, Clone)]
+pub struct Analytics {
+    lines: Vec<CountingLine>,
+    zones: Vec<Zone>,
+    anchor: Anchor,
+    track_max_age: usize,
+    tracks: HashMap<u64, TrackState>,
+    frame: usize,
+}
+
+impl Default for Analytics {
+    fn default() -> Self {
+        Analytics {
+            lines: Vec::new(),
+            zones: Vec::new(),
+            anchor: Anchor::default(),
+            track_max_age: DEFAULT_TRACK_MAX_AGE,
+            tracks: HashMap::new(),
+            frame: 0,
+        }
+    }
+}
+
+impl Analytics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a counting line from `start` to `end`
+    pub fn with_line<S: Into<String>, P: Into<Point>>(mut self, name: S, start: P, end: P) -> Self {
+        self.lines.push(CountingLine {
+            name: name.into(),
+            line: Line::new(start, end),
+            counts: LineCounts::default(),
+        });
+        self
+    }
+
+    /// Adds a zone from the points of its outline
+    pub fn with_zone<S: Into<String>, P: Into<Point>>(
+        mut self,
+        name: S,
+        points: impl IntoIterator<Item = P>,
+    ) -> Self {
+        self.zones.push(Zone {
+            name: name.into(),
+            polygon: Polygon::new(points),
+        });
+        self
+    }
+
+    /// Point of the bounding boxes checked against lines and zones, defaults to [`Anchor::BottomCenter`]
+    pub fn with_anchor(mut self, anchor: Anchor) -> Self {
+        self.anchor = anchor;
+        self
+    }
+
+    /// Number of frames a track can go unseen before it is forgotten, emitting
+    /// [`AnalyticsEvent::ZoneExit`] for the zones it was still in. Defaults to [`DEFAULT_TRACK_MAX_AGE`],
+    /// it should match the `max_age` of the tracker
+    pub fn with_track_max_age(mut self, frames: usize) -> Self {
+        self.track_max_age = frames;
+        self
+    }
+
+    /// Running crossing totals of the line named `line`
+    pub fn line_counts(&self, line: &str) -> Option<&LineCounts> {
+        self.lines
+            .iter()
+            .find(|counting_line| counting_line.name == line)
+            .map(|counting_line| &counting_line.counts)
+    }
+
+    /// Number of frames processed so far
+    pub fn frame_count(&self) -> usize {
+        self.frame
+    }
+
+    /// Processes the results of the next frame, shown at `timestamp`
+    pub fn update(
+        &mut self,
+        timestamp: Duration,
+        frame_results: &[InferenceResult],
+    ) -> FrameReport {
+        let frame = self.frame;
+        self.frame += 1;
+
+        let mut zone_occupancy = self
+            .zones
+            .iter()
+            .map(|zone| ZoneOccupancy {
+                zone: zone.name.clone(),
+                counts: BTreeMap::new(),
+            })
+            .collect::<Vec<ZoneOccupancy>>();
+        let mut events = Vec::new();
+
+        for result in frame_results {
+            let position = self.anchor.of(result.b_box());
+            let in_zones = self
+                .zones
+                .iter()
+                .map(|zone| zone.polygon.contains(position))
+                .collect::<Vec<bool>>();
+
+            for (occupancy, _) in zone_occupancy
+                .iter_mut()
+                .zip(&in_zones)
+                .filter(|(_, inside)| **inside)
+            {
+                *occupancy
+                    .counts
+                    .entry(result.class().to_string())
+                    .or_default() += 1;
+            }
+
+            let Some(track_id) = result.track_id() else {
+                continue;
+            };
+            let previous = self.tracks.insert(
+                track_id,
+                TrackState {
+                    class: result.class().to_string(),
+                    position,
+                    in_zones: in_zones.clone(),
+                    last_frame: frame,
+                },
+            );
+
+            let class = result.class().to_string();
+            let previous_zones = match &previous {
+                Some(previous) => previous.in_zones.clone(),
+                None => vec![false; self.zones.len()],
+            };
+            for ((zone, was_inside), is_inside) in
+                self.zones.iter().zip(previous_zones).zip(in_zones)
+            {
+                let zone = zone.name.clone();
+                let class = class.clone();
+                match (was_inside, is_inside) {
+                    (false, true) => events.push(AnalyticsEvent::ZoneEnter {
+                        frame,
+                        timestamp,
+                        zone,
+                        class,
+                        track_id,
+                    }),
+                    (true, false) => events.push(AnalyticsEvent::ZoneExit {
+                        frame,
+                        timestamp,
+                        zone,
+                        class,
+                        track_id,
+                    }),
+                    _ => {}
+                }
+            }
+
+            let Some(previous) = previous else {
+                continue;
+            };
+            for counting_line in self.lines.iter_mut() {
+                if !counting_line.line.intersects(previous.position, position) {
+                    continue;
+                }
+                let direction = match counting_line.line.side(position) >= 0. {
+                    true => Direction::In,
+                    false => Direction::Out,
+                };
+                counting_line.counts.record(&class, direction);
+                events.push(AnalyticsEvent::LineCrossing {
+                    frame,
+                    timestamp,
+                    line: counting_line.name.clone(),
+                    class: class.clone(),
+                    track_id,
+                    direction,
+                });
+            }
+        }
+
+        // Tracks lost by the tracker leave the zones they were last seen in
+        let max_age = self.track_max_age;
+        let mut expired = self
+            .tracks
+            .iter()
+            .filter(|(_, track)| frame - track.last_frame > max_age)
+            .map(|(&track_id, _)| track_id)
+            .collect::<Vec<u64>>();
+        expired.sort_unstable();
+        for track_id in expired {
+            let Some(track) = self.tracks.remove(&track_id) else {
+                continue;
+            };
+            for (zone, _) in self
+                .zones
+                .iter()
+                .zip(track.in_zones)
+                .filter(|(_, inside)| *inside)
+            {
+                events.push(AnalyticsEvent::ZoneExit {
+                    frame,
+                    timestamp,
+                    zone: zone.name.clone(),
+                    class: track.class.clone(),
+                    track_id,
+                });
+            }
+        }
+
+        FrameReport {
+            frame,
+            timestamp,
+            zone_occupancy,
+            events,
+        }
+    }
+
+    /// Processes the results of every frame of a video, e.g. as returned by
+    /// [`Yolo::infer_video`](crate::Yolo::infer_video), timestamps are derived from `fps`
+    pub fn process_frames(
+        &mut self,
+        frame_results: &[Vec<InferenceResult>],
+        fps: f32,
+    ) -> Vec<FrameReport> {
+        frame_results
+            .iter()
+            .map(|results| {
+                let timestamp = Duration::from_secs_f32(self.frame as f32 / fps.max(f32::EPSILON));
+                self.update(timestamp, results)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Analytics, AnalyticsEvent, Direction};
+    use crate::InferenceResult;
+    use imageproc::rect::Rect;
+
+    fn detection(x: i32, y: i32, class: &str, track_id: Option<u64>) -> InferenceResult {
+        InferenceResult::new(Rect::at(x, y).of_size(10, 10), class, 0, 0.9).with_track_id(track_id)
+    }
+
+    #[test]
+    fn test_line_crossings() {
+        let mut analytics = Analytics::new().with_line("road", (0., 50.), (200., 50.));
+
+        // a car driving down through the line, a person walking up through it,
+        // and an untracked car that is never counted
+        let frames = (0..10)
+            .map(|step| {
+                vec![
+                    detection(20, step * 10, "car", Some(1)),
+                    detection(100, 90 - step * 10, "person", Some(2)),
+                    detection(150, step * 10, "car", None),
+                ]
+            })
+            .collect::<Vec<_>>();
+        let reports = analytics.process_frames(&frames, 10.0);
+
+        let counts = analytics.line_counts("road").unwrap();
+        assert_eq!((counts.in_count("car"), counts.out_count("car")), (1, 0));
+        assert_eq!(
+            (counts.in_count("person"), counts.out_count("person")),
+            (0, 1)
+        );
+        assert_eq!((counts.total_in(), counts.total_out()), (1, 1));
+        assert!(analytics.line_counts("missing").is_none());
+
+        // the bottom of the car box reaches y = 50 at step 4
+        let crossing = reports[4].events.iter().find_map(|event| match event {
+            AnalyticsEvent::LineCrossing {
+                track_id: 1,
+                direction,
+                frame,
+                ..
+            } => Some((*direction, *frame)),
+            _ => None,
+        });
+        assert_eq!(crossing, Some((Direction::In, 4)));
+        assert!((reports[4].timestamp.as_secs_f32() - 0.4).abs() < 1e-6);
+        let events = reports
+            .iter()
+            .map(|report| report.events.len())
+            .sum::<usize>();
+        assert_eq!(events, 2);
+    }
+
+    #[test]
+    fn test_zone_occupancy() {
+        let mut analytics =
+            Analytics::new().with_zone("lot", [(0., 0.), (100., 0.), (100., 100.), (0., 100.)]);
+
+        let report = analytics.update(
+            Default::default(),
+            &[
+                detection(10, 10, "car", Some(1)),
+                detection(50, 10, "car", None),
+                detection(20, 20, "person", None),
+                detection(300, 300, "car", Some(2)),
+            ],
+        );
+        assert_eq!(report.zone_occupancy[0].count("car"), 2);
+        assert_eq!(report.zone_occupancy[0].total(), 3);
+        assert!(matches!(
+            report.events[..],
+            [AnalyticsEvent::ZoneEnter { track_id: 1, .. }]
+        ));
+
+        // track 1 leaves the zone
+        let report = analytics.update(Default::default(), &[detection(300, 10, "car", Some(1))]);
+        assert_eq!(report.zone_occupancy[0].total(), 0);
+        assert!(matches!(
+            report.events[..],
+            [AnalyticsEvent::ZoneExit { track_id: 1, .. }]
+        ));
+    }
+
+    #[test]
+    fn test_lost_tracks_exit_zones() {
+        let mut analytics = Analytics::new()
+            .with_zone("lot", [(0., 0.), (100., 0.), (100., 100.), (0., 100.)])
+            .with_track_max_age(2);
+
+        let entered = analytics.update(Default::default(), &[detection(10, 10, "car", Some(1))]);
+        assert!(matches!(
+            entered.events[..],
+            [AnalyticsEvent::ZoneEnter { track_id: 1, .. }]
+        ));
+        // the car is not detected anymore, it exits the zone once its track has expired
+        let reports = (0..3)
+            .map(|_| analytics.update(Default::default(), &[]))
+            .collect::<Vec<_>>();
+        assert!(reports[0].events.is_empty());
+        assert!(reports[1].events.is_empty());
+        assert!(matches!(
+            reports[2].events[..],
+            [AnalyticsEvent::ZoneExit { track_id: 1, frame: 3, ref class, .. }] if class == "car"
+        ));
+        assert!(analytics.tracks.is_empty());
+    }
+}
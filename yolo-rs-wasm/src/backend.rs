@@ -0,0 +1,466 @@
+//! Inference backends
+//!
+//! [`Yolo`](crate::Yolo) runs the model through an [`InferenceBackend`],
+//! pre and post processing are the same whichever backend is used.
+//! - `WasiNnBackend` runs the model with WasmEdge's WASI-NN plugin, with the `wasi-nn` feature
+//! - [`ReplayBackend`] serves output tensors recorded earlier, so that the whole pipeline
+//!   can be tested on a normal host without WasmEdge
+//! - [`RecordingBackend`] wraps another backend and writes every output to disk for later replay
+//! - `TractBackend` runs ONNX models in process, with the `pure-rust` feature
+//!
+//! [`DefaultBackend`] is the backend [`YoloBuilder`](crate::YoloBuilder) builds from model files:
+//! `TractBackend` with the `pure-rust` feature, `WasiNnBackend` otherwise.
+//! Without either feature (e.g. in a native build only reusing pre and post processing)
+//! it is a boxed [`InferenceBackend`] passed to [`YoloBuilder::build_with_backend`](crate::YoloBuilder::build_with_backend).
+
+use std::{
+    cell::Cell,
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "wasi-nn")]
+use wasi_nn::Graph;
+
+#[cfg(feature = "pure-rust")]
+mod tract;
+#[cfg(feature = "pure-rust")]
+pub use tract::TractBackend;
+
+/// Backend used by [`Yolo`](crate::Yolo) when none is specified
+#[cfg(feature = "pure-rust")]
+pub type DefaultBackend = TractBackend;
+/// Backend used by [`Yolo`](crate::Yolo) when none is specified
+#[cfg(all(feature = "wasi-nn", not(feature = "pure-rust")))]
+pub type DefaultBackend = WasiNnBackend;
+/// Backend used by [`Yolo`](crate::Yolo) when none is specified
+#[cfg(not(any(feature = "wasi-nn", feature = "pure-rust")))]
+pub type DefaultBackend = Box<dyn InferenceBackend>;
+
+/// Magic bytes at the start of a tensor file written by [`write_tensors`]
+const TENSOR_FILE_MAGIC: &[u8; 4] = b"YRST";
+/// Extension of the tensor files read by [`ReplayBackend::from_dir`]
+pub const TENSOR_FILE_EXTENSION: &str = "tensor";
+
+/// Error emitted by an inference backend
+#[derive(thiserror::Error, Debug)]
+pub enum BackendError {
+    #[cfg(feature = "wasi-nn")]
+    #[error("wasi-nn error")]
+    WasiNn(#[from] wasi_nn::Error),
+
+    #[cfg(feature = "pure-rust")]
+    #[error("tract error")]
+    Tract(#[from] tract_onnx::prelude::TractError),
+
+    #[error("file error")]
+    FileError(#[from] io::Error),
+
+    #[error("tensor of shape {shape:?} cannot hold {len} elements")]
+    ShapeMismatch { shape: Vec<usize>, len: usize },
+
+    #[error("tensor file is malformed")]
+    InvalidTensorFile,
+
+    #[error("replay backend has no recorded outputs")]
+    NoRecordings,
+
+    #[error("backend did not return any output tensor")]
+    MissingOutput,
+}
+
+/// Dense f32 tensor in row major order
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tensor {
+    shape: Vec<usize>,
+    data: Vec<f32>,
+}
+
+impl Tensor {
+    /// Creates a tensor, fails if the number of elements does not match the shape
+    pub fn new(shape: Vec<usize>, data: Vec<f32>) -> Result<Self, BackendError> {
+        if shape.iter().product::<usize>() != data.len() {
+            return Err(BackendError::ShapeMismatch {
+                shape,
+                len: data.len(),
+            });
+        }
+        Ok(Tensor { shape, data })
+    }
+
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    pub fn data(&self) -> &[f32] {
+        &self.data
+    }
+
+    pub fn into_data(self) -> Vec<f32> {
+        self.data
+    }
+}
+
+/// Writes tensors in the little endian format read by [`read_tensors`]:
+/// magic, tensor count, then per tensor its rank, dimensions and data
+pub fn write_tensors<W: Write>(mut writer: W, tensors: &[Tensor]) -> Result<(), BackendError> {
+    writer.write_all(TENSOR_FILE_MAGIC)?;
+    writer.write_all(&(tensors.len() as u32).to_le_bytes())?;
+    for tensor in tensors {
+        writer.write_all(&(tensor.shape.len() as u32).to_le_bytes())?;
+        for dim in &tensor.shape {
+            writer.write_all(&(*dim as u64).to_le_bytes())?;
+        }
+        for value in &tensor.data {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads tensors written by [`write_tensors`]
+pub fn read_tensors<R: Read>(mut reader: R) -> Result<Vec<Tensor>, BackendError> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != TENSOR_FILE_MAGIC {
+        return Err(BackendError::InvalidTensorFile);
+    }
+
+    let mut u32_buf = [0u8; 4];
+    let mut u64_buf = [0u8; 8];
+    reader.read_exact(&mut u32_buf)?;
+    let count = u32::from_le_bytes(u32_buf);
+
+    let mut tensors = Vec::new();
+    for _ in 0..count {
+        reader.read_exact(&mut u32_buf)?;
+        let rank = u32::from_le_bytes(u32_buf);
+        let mut shape = Vec::new();
+        for _ in 0..rank {
+            reader.read_exact(&mut u64_buf)?;
+            let dim = usize::try_from(u64::from_le_bytes(u64_buf))
+                .map_err(|_| BackendError::InvalidTensorFile)?;
+            shape.push(dim);
+        }
+
+        // The header is not trusted: the size must not overflow and the data must be in the file,
+        // which is read as it comes instead of allocating the size up front
+        let byte_len = shape
+            .iter()
+            .try_fold(4usize, |len, dim| len.checked_mul(*dim))
+            .ok_or(BackendError::InvalidTensorFile)?;
+        let mut bytes = Vec::new();
+        reader
+            .by_ref()
+            .take(byte_len as u64)
+            .read_to_end(&mut bytes)?;
+        if bytes.len() != byte_len {
+            return Err(BackendError::InvalidTensorFile);
+        }
+        let data = bytes
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect();
+        tensors.push(Tensor::new(shape, data)?);
+    }
+    Ok(tensors)
+}
+
+/// A model runtime [`Yolo`](crate::Yolo) can run inference with
+pub trait InferenceBackend {
+    /// Sets `input` as the input of the model, computes the inference,
+    /// and returns every output of the model along with its shape
+    fn infer(&self, input: &Tensor) -> Result<Vec<Tensor>, BackendError>;
+}
+
+impl<B: InferenceBackend + ?Sized> InferenceBackend for Box<B> {
+    fn infer(&self, input: &Tensor) -> Result<Vec<Tensor>, BackendError> {
+        (**self).infer(input)
+    }
+}
+
+/// Backend running the model with the WASI-NN plugin of WasmEdge
+#[cfg(feature = "wasi-nn")]
+pub struct WasiNnBackend {
+    graph: Graph,
+    output_shape: Vec<usize>,
+}
+
+#[cfg(feature = "wasi-nn")]
+impl WasiNnBackend {
+    /// WASI-NN does not report the shape of outputs,
+    /// so the shape of the single output of the model has to be known up front
+    pub fn new(graph: Graph, output_shape: Vec<usize>) -> Self {
+        WasiNnBackend {
+            graph,
+            output_shape,
+        }
+    }
+
+    pub fn graph(&self) -> &Graph {
+        &self.graph
+    }
+}
+
+#[cfg(feature = "wasi-nn")]
+impl InferenceBackend for WasiNnBackend {
+    fn infer(&self, input: &Tensor) -> Result<Vec<Tensor>, BackendError> {
+        let mut context = self.graph.init_execution_context()?;
+
+        context.set_input(0, wasi_nn::TensorType::F32, input.shape(), input.data())?;
+
+        let mut output_buffer = vec![0f32; self.output_shape.iter().product()];
+
+        // Execute the inference.
+        context.compute()?;
+        context.get_output(0, &mut output_buffer)?;
+
+        Ok(vec![Tensor::new(self.output_shape.clone(), output_buffer)?])
+    }
+}
+
+/// Backend serving previously recorded outputs instead of running a model.
+/// Each call to [`InferenceBackend::infer`] returns the next recording,
+/// starting over from the first one once all have been served. The input is ignored
+pub struct ReplayBackend {
+    recordings: Vec<Vec<Tensor>>,
+    next: Cell<usize>,
+}
+
+impl ReplayBackend {
+    /// Replays in memory recordings, one `Vec<Tensor>` per call
+    pub fn new(recordings: Vec<Vec<Tensor>>) -> Result<Self, BackendError> {
+        if recordings.is_empty() {
+            return Err(BackendError::NoRecordings);
+        }
+        Ok(ReplayBackend {
+            recordings,
+            next: Cell::new(0),
+        })
+    }
+
+    /// Replays every `.tensor` file in `dir`, in file name order,
+    /// e.g. as written by [`RecordingBackend`]
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Self, BackendError> {
+        let mut paths = fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<PathBuf>, io::Error>>()?;
+        paths.retain(|path| {
+            path.extension()
+                .is_some_and(|ext| ext == TENSOR_FILE_EXTENSION)
+        });
+        paths.sort();
+
+        let recordings = paths
+            .iter()
+            .map(|path| read_tensors(io::BufReader::new(fs::File::open(path)?)))
+            .collect::<Result<Vec<Vec<Tensor>>, BackendError>>()?;
+        Self::new(recordings)
+    }
+}
+
+impl InferenceBackend for ReplayBackend {
+    fn infer(&self, _input: &Tensor) -> Result<Vec<Tensor>, BackendError> {
+        let idx = self.next.get();
+        self.next.set((idx + 1) % self.recordings.len());
+        Ok(self.recordings[idx].clone())
+    }
+}
+
+/// Backend writing the outputs of the wrapped backend to `dir`,
+/// one file per call named `000000.tensor`, `000001.tensor`...
+/// The recordings can be replayed with [`ReplayBackend::from_dir`]
+pub struct RecordingBackend<B> {
+    inner: B,
+    dir: PathBuf,
+    count: Cell<usize>,
+}
+
+impl<B: InferenceBackend> RecordingBackend<B> {
+    pub fn new<P: AsRef<Path>>(inner: B, dir: P) -> Result<Self, BackendError> {
+        fs::create_dir_all(dir.as_ref())?;
+        Ok(RecordingBackend {
+            inner,
+            dir: dir.as_ref().to_path_buf(),
+            count: Cell::new(0),
+        })
+    }
+
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+}
+
+impl<B: InferenceBackend> InferenceBackend for RecordingBackend<B> {
+    fn infer(&self, input: &Tensor) -> Result<Vec<Tensor>, BackendError> {
+        let outputs = self.inner.infer(input)?;
+
+        let count = self.count.get();
+        let path = self.dir.join(format!("{count:06}.{TENSOR_FILE_EXTENSION}"));
+        write_tensors(io::BufWriter::new(fs::File::create(path)?), &outputs)?;
+        self.count.set(count + 1);
+
+        Ok(outputs)
+    }
+}
+
+/// Yolo on 64x64 images detecting one person in the middle of every image, for tests
+#[cfg(test)]
+pub(crate) fn one_person_yolo() -> crate::Yolo<ReplayBackend> {
+    let num_anchors = crate::InputSize::new(64, 64).num_anchors();
+    let mut data = vec![0f32; 5 * num_anchors];
+    for (row, value) in [32., 32., 16., 16., 0.9].iter().enumerate() {
+        data[row * num_anchors] = *value;
+    }
+    let backend = ReplayBackend::new(vec![vec![
+        Tensor::new(vec![1, 5, num_anchors], data).unwrap()
+    ]])
+    .unwrap();
+    crate::YoloBuilder::new()
+        .classes(vec!["person".to_string()])
+        .input_size(crate::InputSize::new(64, 64))
+        .build_with_backend(backend)
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        read_tensors, write_tensors, BackendError, InferenceBackend, RecordingBackend,
+        ReplayBackend, Tensor, TENSOR_FILE_MAGIC,
+    };
+    use crate::{InputSize, Yolo, YoloBuilder};
+    use image::RgbImage;
+    use imageproc::rect::Rect;
+    use std::{fs, path::Path};
+
+    /// Recording of the detections of [`test_replay_golden_inference`]
+    const GOLDEN_RECORDINGS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/recordings");
+
+    /// Output of a model with 2 classes for a 64 x 64 input,
+    /// with the given detections as `[cx, cy, w, h, p_class_0, p_class_1]`
+    fn recorded_output(detections: &[[f32; 6]]) -> Tensor {
+        let num_anchors = InputSize::new(64, 64).num_anchors();
+        let mut data = vec![0f32; 6 * num_anchors];
+        for (anchor, detection) in detections.iter().enumerate() {
+            for (row, value) in detection.iter().enumerate() {
+                data[row * num_anchors + anchor] = *value;
+            }
+        }
+        Tensor::new(vec![1, 6, num_anchors], data).unwrap()
+    }
+
+    fn replay_yolo(detections: &[[f32; 6]]) -> Yolo<ReplayBackend> {
+        let backend = ReplayBackend::new(vec![vec![recorded_output(detections)]]).unwrap();
+        yolo_with_backend(backend)
+    }
+
+    fn yolo_with_backend<B: InferenceBackend>(backend: B) -> Yolo<B> {
+        YoloBuilder::new()
+            .classes(vec!["person".to_string(), "car".to_string()])
+            .input_size(InputSize::new(64, 64))
+            .build_with_backend(backend)
+            .unwrap()
+    }
+
+    /// Checks the results of the detections of [`test_replay_golden_inference`]
+    fn assert_golden_results<B: InferenceBackend>(yolo: &Yolo<B>) {
+        // 128 x 64 image, scaled down by 2 to fit the 64 x 64 input
+        let image = RgbImage::new(128, 64);
+        let results = yolo
+            .infer_image(
+                &yolo.default_conf_thresh(),
+                &yolo.default_iou_thresh(),
+                &image,
+            )
+            .unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].class(), "person");
+        assert_eq!(results[0].confidence(), 0.9);
+        assert_eq!(results[0].b_box(), Rect::at(24, 24).of_size(16, 16));
+        assert_eq!(results[1].class(), "car");
+        assert_eq!(results[1].confidence(), 0.7);
+    }
+
+    #[test]
+    fn test_tensor_file_round_trip() {
+        let tensors = vec![
+            Tensor::new(vec![2, 3], vec![0., 1., 2., 3., 4., 5.]).unwrap(),
+            Tensor::new(vec![1], vec![-1.5]).unwrap(),
+        ];
+        let mut bytes = Vec::new();
+        write_tensors(&mut bytes, &tensors).unwrap();
+
+        assert_eq!(read_tensors(bytes.as_slice()).unwrap(), tensors);
+        assert!(read_tensors(&b"nope"[..]).is_err());
+        assert!(Tensor::new(vec![2, 2], vec![0.; 3]).is_err());
+    }
+
+    #[test]
+    fn test_tensor_file_corrupt_header() {
+        // one tensor of rank 2 with the given dimensions, followed by 8 bytes of data
+        let file = |dims: [u64; 2]| {
+            let mut bytes = TENSOR_FILE_MAGIC.to_vec();
+            bytes.extend_from_slice(&1u32.to_le_bytes());
+            bytes.extend_from_slice(&2u32.to_le_bytes());
+            for dim in dims {
+                bytes.extend_from_slice(&dim.to_le_bytes());
+            }
+            bytes.extend_from_slice(&[0; 8]);
+            bytes
+        };
+
+        assert!(read_tensors(file([1, 2]).as_slice()).is_ok());
+        // the size overflows
+        assert!(matches!(
+            read_tensors(file([u64::MAX, 2]).as_slice()),
+            Err(BackendError::InvalidTensorFile)
+        ));
+        // a terabyte tensor, larger than the file
+        assert!(matches!(
+            read_tensors(file([1 << 20, 1 << 18]).as_slice()),
+            Err(BackendError::InvalidTensorFile)
+        ));
+    }
+
+    #[test]
+    fn test_replay_golden_inference() {
+        let yolo = replay_yolo(&[
+            // person, kept
+            [16., 16., 8., 8., 0.9, 0.],
+            // person, overlaps the first one and is supressed
+            [17., 16., 8., 8., 0.8, 0.],
+            // car at the same location, a different class is not supressed
+            [16., 16., 8., 8., 0.1, 0.7],
+            // below the confidence threshold
+            [40., 40., 8., 8., 0.3, 0.2],
+        ]);
+
+        assert_golden_results(&yolo);
+    }
+
+    #[test]
+    fn test_replay_golden_recording() {
+        let yolo = yolo_with_backend(ReplayBackend::from_dir(GOLDEN_RECORDINGS).unwrap());
+        assert_golden_results(&yolo);
+    }
+
+    #[test]
+    fn test_record_then_replay_from_dir() {
+        let dir = std::env::temp_dir().join(format!("yolo-rs-recordings-{}", std::process::id()));
+        let replay = ReplayBackend::from_dir(GOLDEN_RECORDINGS).unwrap();
+        let yolo = yolo_with_backend(RecordingBackend::new(replay, &dir).unwrap());
+        assert_golden_results(&yolo);
+        assert_golden_results(&yolo);
+
+        // one file per inference, identical to the recording replayed
+        let golden = fs::read(Path::new(GOLDEN_RECORDINGS).join("000000.tensor")).unwrap();
+        assert_eq!(fs::read(dir.join("000000.tensor")).unwrap(), golden);
+        assert_eq!(fs::read(dir.join("000001.tensor")).unwrap(), golden);
+
+        assert_golden_results(&yolo_with_backend(ReplayBackend::from_dir(&dir).unwrap()));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,128 @@
+//! Pure Rust inference backend, enabled with the `pure-rust` feature.
+//! Runs ONNX models in process with [tract](https://github.com/sonos/tract),
+//! so no WASI-NN plugin is needed, in any `wasm32-wasi` runtime or natively.
+
+use std::path::Path;
+
+use tract_onnx::prelude::{
+    tvec, DatumType, Framework, InferenceFact, InferenceModel, InferenceModelExt, TypedModel,
+    TypedRunnableModel,
+};
+
+use super::{BackendError, InferenceBackend, Tensor};
+use crate::InputSize;
+
+/// Backend running ONNX models with tract
+pub struct TractBackend {
+    model: TypedRunnableModel<TypedModel>,
+}
+
+impl TractBackend {
+    /// Loads an ONNX model, optimized for a `[1, 3, height, width]` input
+    pub fn from_file<P: AsRef<Path>>(
+        model_path: P,
+        input_size: &InputSize,
+    ) -> Result<Self, BackendError> {
+        let model = tract_onnx::onnx().model_for_path(model_path)?;
+        Self::from_model(model, input_size)
+    }
+
+    /// Loads an ONNX model from memory, optimized for a `[1, 3, height, width]` input
+    pub fn from_bytes(model_bytes: &[u8], input_size: &InputSize) -> Result<Self, BackendError> {
+        let model = tract_onnx::onnx().model_for_read(&mut &model_bytes[..])?;
+        Self::from_model(model, input_size)
+    }
+
+    fn from_model(model: InferenceModel, input_size: &InputSize) -> Result<Self, BackendError> {
+        let input_fact = InferenceFact::dt_shape(
+            DatumType::F32,
+            tvec!(1, 3, input_size.height as usize, input_size.width as usize),
+        );
+        let model = model.with_input_fact(0, input_fact)?.into_optimized()?;
+        Self::from_typed_model(model)
+    }
+
+    /// Backend running a model built or loaded with tract directly
+    pub(crate) fn from_typed_model(model: TypedModel) -> Result<Self, BackendError> {
+        Ok(TractBackend {
+            model: model.into_runnable()?,
+        })
+    }
+}
+
+impl InferenceBackend for TractBackend {
+    fn infer(&self, input: &Tensor) -> Result<Vec<Tensor>, BackendError> {
+        let tract_input = tract_onnx::prelude::Tensor::from_shape(input.shape(), input.data())?;
+        let outputs = self.model.run(tvec!(tract_input.into()))?;
+
+        outputs
+            .iter()
+            .map(|output| {
+                let view = output.to_array_view::<f32>()?;
+                Tensor::new(view.shape().to_vec(), view.iter().copied().collect())
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TractBackend;
+    use crate::{
+        backend::{InferenceBackend, Tensor},
+        BuildError, InputSize, PostProcessingError, YoloBuilder, YoloRuntimeError,
+    };
+    use image::RgbImage;
+    use tract_onnx::prelude::{DatumType, TypedFact, TypedModel};
+
+    /// Model returning its `[1, 3, height, width]` input as output
+    fn identity_backend(input_size: InputSize) -> TractBackend {
+        let mut model = TypedModel::default();
+        let shape = [1, 3, input_size.height as usize, input_size.width as usize];
+        let input = model
+            .add_source("images", TypedFact::dt_shape(DatumType::F32, shape))
+            .unwrap();
+        model.set_output_outlets(&[input]).unwrap();
+        TractBackend::from_typed_model(model).unwrap()
+    }
+
+    #[test]
+    fn test_tract_input_layout() {
+        let backend = identity_backend(InputSize::new(3, 2));
+        let data = (0..18).map(|value| value as f32).collect::<Vec<f32>>();
+        let input = Tensor::new(vec![1, 3, 2, 3], data).unwrap();
+
+        // the row major data and shape of the input reach the model unchanged
+        let outputs = backend.infer(&input).unwrap();
+        assert_eq!(outputs, vec![input]);
+    }
+
+    #[test]
+    fn test_tract_output_shape_validation() {
+        let yolo = YoloBuilder::new()
+            .classes(vec!["person".to_string()])
+            .input_size(InputSize::new(64, 64))
+            .build_with_backend(identity_backend(InputSize::new(64, 64)))
+            .unwrap();
+
+        let error = yolo
+            .infer_image(
+                &yolo.default_conf_thresh(),
+                &yolo.default_iou_thresh(),
+                &RgbImage::new(64, 64),
+            )
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            YoloRuntimeError::PostProcessingError(PostProcessingError::OutputShape { ref shape, num_classes: 1 })
+                if shape == &[1, 3, 64, 64]
+        ));
+    }
+
+    #[test]
+    fn test_tract_checks_classes_before_loading() {
+        // not a model: without classes the build fails before parsing it
+        let built = YoloBuilder::new().build_from_bytes([b"not an onnx model"]);
+        assert!(matches!(built, Err(BuildError::MissingClasses)));
+    }
+}
@@ -0,0 +1,541 @@
+//! Detection on large sets of images, with progress, an error report and resume
+//!
+//! [`BatchRunner`] runs inference on every image of a directory tree or glob pattern and writes
+//! its outputs to the same relative path in an output directory. Images that cannot be read or processed
+//! are reported and skipped. Outputs are written to a temporary file and renamed once complete,
+//! so after a crash or restart, images whose outputs all exist are skipped instead of processed again.
+//! [`BatchRunner::run_with`] also hands the detections of each image to a callback,
+//! e.g. to filter them or to collect them in a single file.
+//!
+//! ```rust,ignore
+//! let report = BatchRunner::from_glob("/data/cameras/**/*.jpg", "/data/detections")?
+//!     .with_outputs([BatchOutput::AnnotatedImage, BatchOutput::YoloLabels])
+//!     .with_error_report("/data/detections/errors.tsv")
+//!     .run(&yolo, &yolo.default_conf_thresh(), &yolo.default_iou_thresh())?;
+//! println!("{}", report);
+//! ```
+
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use image::{ImageFormat, RgbImage};
+use prgrs::Prgrs;
+use rusttype::Font;
+
+use crate::{
+    backend::InferenceBackend,
+    export::{voc::write_voc_xml, yolo_labels::write_labels, ExportError},
+    jsonl::Detection,
+    utils::{self, Anonymization, DrawStyle},
+    ConfThresh, IOUThresh, InferenceResult, Yolo, YoloRuntimeError,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum BatchError {
+    #[error("file error")]
+    FileError(#[from] io::Error),
+
+    #[error("invalid glob pattern")]
+    Pattern(#[from] glob::PatternError),
+
+    #[error("error reading image")]
+    ImageError(#[from] image::ImageError),
+
+    #[error("inference error")]
+    Inference(#[from] YoloRuntimeError),
+
+    #[error("error writing results")]
+    Export(#[from] ExportError),
+
+    #[error("error writing JSON")]
+    Json(#[from] serde_json::Error),
+
+    #[error("annotated images need a font, see BatchRunner::with_font")]
+    MissingFont,
+}
+
+/// What is written for each image, at its relative path in the output directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchOutput {
+    /// The image with the detections drawn, or anonymized with [`BatchRunner::with_anonymization`],
+    /// in the format of the input image
+    AnnotatedImage,
+    /// The detections as a JSON array of [`Detection`], `.json`
+    Json,
+    /// A YOLO label file, `.txt`
+    YoloLabels,
+    /// A Pascal VOC annotation, `.xml`
+    VocXml,
+}
+
+impl BatchOutput {
+    /// Path of the output for the image at `relative_path` in `output_dir`
+    pub fn path(&self, output_dir: &Path, relative_path: &Path) -> PathBuf {
+        let path = output_dir.join(relative_path);
+        match self {
+            BatchOutput::AnnotatedImage => path,
+            BatchOutput::Json => path.with_extension("json"),
+            BatchOutput::YoloLabels => path.with_extension("txt"),
+            BatchOutput::VocXml => path.with_extension("xml"),
+        }
+    }
+}
+
+/// An image that could not be processed
+#[derive(Debug)]
+pub struct BatchFailure {
+    pub path: PathBuf,
+    pub error: BatchError,
+}
+
+impl BatchFailure {
+    /// The error followed by its sources, see [`utils::error_chain`]
+    pub fn message(&self) -> String {
+        utils::error_chain(&self.error)
+    }
+
+    /// Line of the error report, the image path and the error separated by a tab
+    fn report_line(&self) -> String {
+        format!(
+            "{}\t{}\n",
+            self.path.display(),
+            self.message().replace(['\t', '\n'], " ")
+        )
+    }
+}
+
+/// Outcome of [`BatchRunner::run`]
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    /// Images processed in this run
+    pub processed: usize,
+    /// Images skipped because their outputs already existed
+    pub skipped: usize,
+    pub failures: Vec<BatchFailure>,
+}
+
+impl BatchReport {
+    /// Writes a line per failure, the image path and the error separated by a tab
+    pub fn write_errors<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let report = self
+            .failures
+            .iter()
+            .map(BatchFailure::report_line)
+            .collect::<String>();
+        fs::write(path, report)
+    }
+}
+
+impl fmt::Display for BatchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} images processed, {} skipped, {} failed",
+            self.processed,
+            self.skipped,
+            self.failures.len()
+        )
+    }
+}
+
+/// Batch of images and where their outputs go, see the [module documentation](self)
+pub struct BatchRunner {
+    /// Path of every image, and its path relative to the input directory
+    images: Vec<(PathBuf, PathBuf)>,
+    output_dir: PathBuf,
+    outputs: Vec<BatchOutput>,
+    font: Option<Font<'static>>,
+    style: DrawStyle,
+    /// Classes to anonymize instead of drawing the detections, and how
+    anonymize: Option<(Vec<String>, Anonymization)>,
+    resume: bool,
+    progress: bool,
+    error_report: Option<PathBuf>,
+}
+
+impl BatchRunner {
+    /// Images inside `output_dir` are left out, they are the outputs of a previous run
+    fn new(mut images: Vec<(PathBuf, PathBuf)>, output_dir: PathBuf) -> Self {
+        {
+            let in_output_dir = utils::in_dir(&output_dir);
+            images.retain(|(path, _)| !in_output_dir(path));
+        }
+        BatchRunner {
+            images,
+            output_dir,
+            outputs: vec![BatchOutput::AnnotatedImage],
+            #[cfg(feature = "bundled-font")]
+            font: Some(utils::default_font()),
+            #[cfg(not(feature = "bundled-font"))]
+            font: None,
+            style: DrawStyle::default(),
+            anonymize: None,
+            resume: true,
+            progress: true,
+            error_report: None,
+        }
+    }
+
+    /// Every image in `input_dir` and its sub directories, except those in `output_dir`
+    pub fn from_dir<P, Q>(input_dir: P, output_dir: Q) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let input_dir = input_dir.as_ref();
+        let images = utils::image_files(input_dir)?
+            .into_iter()
+            .map(|path| {
+                let relative_path = path.strip_prefix(input_dir).unwrap_or(&path).to_path_buf();
+                (path, relative_path)
+            })
+            .collect();
+        Ok(Self::new(images, output_dir.as_ref().to_path_buf()))
+    }
+
+    /// Every image matching a glob pattern such as `data/**/*.jpg`. Outputs are placed relative to
+    /// the directory before the first wildcard, `data` in this example
+    pub fn from_glob<Q: AsRef<Path>>(pattern: &str, output_dir: Q) -> Result<Self, BatchError> {
+        let base_dir = glob_base_dir(pattern);
+        let mut images = Vec::new();
+        for entry in glob::glob(pattern)? {
+            let path = entry.map_err(io::Error::from)?;
+            if path.is_file() && ImageFormat::from_path(&path).is_ok() {
+                let relative_path = path.strip_prefix(&base_dir).unwrap_or(&path).to_path_buf();
+                images.push((path, relative_path));
+            }
+        }
+        images.sort();
+        Ok(Self::new(images, output_dir.as_ref().to_path_buf()))
+    }
+
+    /// What to write for each image, an annotated image by default
+    pub fn with_outputs(mut self, outputs: impl IntoIterator<Item = BatchOutput>) -> Self {
+        self.outputs = outputs.into_iter().collect();
+        self
+    }
+
+    /// Font of the labels of annotated images, the bundled font with the `bundled-font` feature
+    pub fn with_font(mut self, font: Font<'static>) -> Self {
+        self.font = Some(font);
+        self
+    }
+
+    pub fn with_style(mut self, style: DrawStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Anonymize the detections of `classes` in annotated images instead of drawing them,
+    /// or every detection if `classes` is empty
+    pub fn with_anonymization(
+        mut self,
+        classes: Vec<String>,
+        anonymization: Anonymization,
+    ) -> Self {
+        self.anonymize = Some((classes, anonymization));
+        self
+    }
+
+    /// Skip images whose outputs already exist, enabled by default
+    pub fn with_resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+
+    /// Show a progress bar on the standard output, enabled by default
+    pub fn with_progress(mut self, progress: bool) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Write the failures to this file as they happen, in the format of [`BatchReport::write_errors`].
+    /// The file is replaced at the start of each run
+    pub fn with_error_report<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.error_report = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Path of every image, and its path relative to the input directory
+    pub fn images(&self) -> &[(PathBuf, PathBuf)] {
+        &self.images
+    }
+
+    /// Whether every output of the image at `relative_path` exists, never for a runner without outputs
+    pub fn is_done(&self, relative_path: &Path) -> bool {
+        !self.outputs.is_empty()
+            && self
+                .outputs
+                .iter()
+                .all(|output| output.path(&self.output_dir, relative_path).is_file())
+    }
+
+    /// Runs inference on every image and writes its outputs. Failures of single images are collected
+    /// in the report, only failing to write the error report is returned as an error
+    pub fn run<B: InferenceBackend>(
+        &self,
+        yolo: &Yolo<B>,
+        conf_thresh: &ConfThresh,
+        iou_thresh: &IOUThresh,
+    ) -> Result<BatchReport, BatchError> {
+        self.run_with(yolo, conf_thresh, iou_thresh, |_, _, _, _| Ok(()))
+    }
+
+    /// Like [`BatchRunner::run`], calling `on_image` with the path, relative path, image and detections
+    /// of each processed image before its outputs are written. Changes to the detections are written,
+    /// an error fails the image
+    pub fn run_with<B, F>(
+        &self,
+        yolo: &Yolo<B>,
+        conf_thresh: &ConfThresh,
+        iou_thresh: &IOUThresh,
+        mut on_image: F,
+    ) -> Result<BatchReport, BatchError>
+    where
+        B: InferenceBackend,
+        F: FnMut(&Path, &Path, &RgbImage, &mut Vec<InferenceResult>) -> Result<(), BatchError>,
+    {
+        if self.outputs.contains(&BatchOutput::AnnotatedImage)
+            && self.font.is_none()
+            && self.anonymize.is_none()
+        {
+            return Err(BatchError::MissingFont);
+        }
+        let mut error_report = match &self.error_report {
+            Some(path) => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                Some(fs::File::create(path)?)
+            }
+            None => None,
+        };
+
+        let mut report = BatchReport::default();
+        let images = self.images.iter();
+        let images: Box<dyn Iterator<Item = &(PathBuf, PathBuf)>> = if self.progress {
+            Box::new(Prgrs::new(images, self.images.len()))
+        } else {
+            Box::new(images)
+        };
+
+        for (path, relative_path) in images {
+            if self.resume && self.is_done(relative_path) {
+                report.skipped += 1;
+                continue;
+            }
+            let processed = self.process(
+                yolo,
+                conf_thresh,
+                iou_thresh,
+                path,
+                relative_path,
+                &mut on_image,
+            );
+            match processed {
+                Ok(()) => report.processed += 1,
+                Err(error) => {
+                    let failure = BatchFailure {
+                        path: path.clone(),
+                        error,
+                    };
+                    if self.progress {
+                        let _ =
+                            prgrs::writeln(&format!("{}: {}", path.display(), failure.message()));
+                    }
+                    // written right away, so that the report is useful while a long batch runs
+                    if let Some(file) = error_report.as_mut() {
+                        file.write_all(failure.report_line().as_bytes())?;
+                    }
+                    report.failures.push(failure);
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    fn process<B, F>(
+        &self,
+        yolo: &Yolo<B>,
+        conf_thresh: &ConfThresh,
+        iou_thresh: &IOUThresh,
+        path: &Path,
+        relative_path: &Path,
+        on_image: &mut F,
+    ) -> Result<(), BatchError>
+    where
+        B: InferenceBackend,
+        F: FnMut(&Path, &Path, &RgbImage, &mut Vec<InferenceResult>) -> Result<(), BatchError>,
+    {
+        let image = image::open(path)?.to_rgb8();
+        let mut results = yolo.infer_image(conf_thresh, iou_thresh, &image)?;
+        on_image(path, relative_path, &image, &mut results)?;
+        let (width, height) = image.dimensions();
+
+        for output in &self.outputs {
+            let output_path = output.path(&self.output_dir, relative_path);
+            write_atomically(&output_path, |tmp_path| {
+                match output {
+                    BatchOutput::AnnotatedImage => {
+                        let format = ImageFormat::from_path(&output_path)?;
+                        let annotated = match &self.anonymize {
+                            Some((classes, anonymization)) => utils::anonymize_mut_image(
+                                image.clone(),
+                                &results,
+                                classes,
+                                *anonymization,
+                            ),
+                            None => utils::draw_bounding_boxes_with_style(
+                                image.clone(),
+                                &results,
+                                self.font.as_ref().ok_or(BatchError::MissingFont)?,
+                                &self.style,
+                            ),
+                        };
+                        annotated.save_with_format(tmp_path, format)?;
+                    }
+                    BatchOutput::Json => {
+                        let detections = results.iter().map(Detection::from).collect::<Vec<_>>();
+                        fs::write(tmp_path, serde_json::to_string_pretty(&detections)?)?;
+                    }
+                    BatchOutput::YoloLabels => write_labels(tmp_path, &results, width, height)?,
+                    BatchOutput::VocXml => write_voc_xml(
+                        tmp_path,
+                        &relative_path.to_string_lossy(),
+                        (width, height),
+                        &results,
+                    )?,
+                }
+                Ok(())
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes `path` through a temporary file in the same directory, renamed once `write` succeeds,
+/// so that an interrupted write never leaves a partial file at `path`
+fn write_atomically<F>(path: &Path, write: F) -> Result<(), BatchError>
+where
+    F: FnOnce(&Path) -> Result<(), BatchError>,
+{
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp_path = path.with_file_name(format!(".{}.partial", file_name));
+    match write(&tmp_path) {
+        Ok(()) => Ok(fs::rename(&tmp_path, path)?),
+        Err(error) => {
+            let _ = fs::remove_file(&tmp_path);
+            Err(error)
+        }
+    }
+}
+
+/// Directory of a glob pattern before its first wildcard
+fn glob_base_dir(pattern: &str) -> PathBuf {
+    Path::new(pattern)
+        .components()
+        .take_while(|component| {
+            !component
+                .as_os_str()
+                .to_string_lossy()
+                .contains(['*', '?', '['])
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{glob_base_dir, BatchOutput, BatchRunner};
+    use crate::backend::one_person_yolo;
+    use image::RgbImage;
+    use std::{fs, path::Path};
+
+    #[test]
+    fn test_glob_base_dir() {
+        assert_eq!(glob_base_dir("data/**/*.jpg"), Path::new("data"));
+        assert_eq!(glob_base_dir("/data/cam[0-9]/*.png"), Path::new("/data"));
+        assert_eq!(glob_base_dir("*.png"), Path::new(""));
+    }
+
+    #[test]
+    fn test_batch_resume() {
+        let dir = std::env::temp_dir().join(format!("yolo-rs-batch-{}", std::process::id()));
+        let (input_dir, output_dir) = (dir.join("input"), dir.join("output"));
+        fs::create_dir_all(input_dir.join("cam1")).unwrap();
+        RgbImage::new(64, 64)
+            .save(input_dir.join("cam1/a.png"))
+            .unwrap();
+        RgbImage::new(64, 64).save(input_dir.join("b.png")).unwrap();
+        fs::write(input_dir.join("corrupt.png"), b"not a png").unwrap();
+
+        let yolo = one_person_yolo();
+
+        let runner = BatchRunner::from_dir(&input_dir, &output_dir)
+            .unwrap()
+            .with_outputs([BatchOutput::YoloLabels, BatchOutput::VocXml])
+            .with_progress(false)
+            .with_error_report(output_dir.join("errors.tsv"));
+        let (conf, iou) = (yolo.default_conf_thresh(), yolo.default_iou_thresh());
+
+        let report = runner.run(&yolo, &conf, &iou).unwrap();
+        assert_eq!((report.processed, report.skipped), (2, 0));
+        assert_eq!(report.failures.len(), 1);
+        assert!(report.failures[0].path.ends_with("corrupt.png"));
+        assert!(fs::read_to_string(output_dir.join("errors.tsv"))
+            .unwrap()
+            .contains("corrupt.png\terror reading image"));
+        assert_eq!(
+            fs::read_to_string(output_dir.join("cam1/a.txt")).unwrap(),
+            "0 0.500000 0.500000 0.250000 0.250000\n"
+        );
+        assert!(output_dir.join("b.xml").is_file());
+
+        // the corrupt image is tried again, and b.png which lost one of its outputs
+        fs::remove_file(output_dir.join("b.xml")).unwrap();
+        let report = runner.run(&yolo, &conf, &iou).unwrap();
+        assert_eq!((report.processed, report.skipped), (1, 1));
+        assert_eq!(report.failures.len(), 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_batch_output_inside_input() {
+        let dir = std::env::temp_dir().join(format!("yolo-rs-batch-nested-{}", std::process::id()));
+        let output_dir = dir.join("detections");
+        fs::create_dir_all(&output_dir).unwrap();
+        RgbImage::new(64, 64).save(dir.join("a.png")).unwrap();
+        // annotated image of a previous run
+        RgbImage::new(64, 64)
+            .save(output_dir.join("a.png"))
+            .unwrap();
+
+        let runner = BatchRunner::from_dir(&dir, &output_dir)
+            .unwrap()
+            .with_outputs([BatchOutput::Json])
+            .with_progress(false);
+        assert_eq!(runner.images(), [(dir.join("a.png"), "a.png".into())]);
+
+        let yolo = one_person_yolo();
+        let (conf, iou) = (yolo.default_conf_thresh(), yolo.default_iou_thresh());
+        let mut seen = Vec::new();
+        let report = runner
+            .run_with(&yolo, &conf, &iou, |_, relative_path, _, results| {
+                seen.push((relative_path.to_path_buf(), results.len()));
+                results.clear();
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(report.processed, 1);
+        assert_eq!(seen, [("a.png".into(), 1)]);
+        assert_eq!(fs::read_to_string(output_dir.join("a.json")).unwrap(), "[]");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
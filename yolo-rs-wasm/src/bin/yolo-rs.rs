@@ -0,0 +1,876 @@
+//! `yolo-rs` command line interface, built with the `cli` feature
+//!
+//! ```sh
+//! cargo build --release --target wasm32-wasi --features cli --bin yolo-rs
+//! wasmedge --dir .:. target/wasm32-wasi/release/yolo-rs.wasm \
+//!     --model-path yolov8n.torchscript --class-names-path class_names \
+//!     detect image example_inputs/busy_street.png --format image,json
+//! ```
+
+#[cfg(not(any(feature = "wasi-nn", feature = "pure-rust")))]
+compile_error!(
+    "the yolo-rs binary needs an inference backend, enable the `wasi-nn` or `pure-rust` feature"
+);
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use image::RgbImage;
+use rusttype::Font;
+#[cfg(feature = "wasi-nn")]
+use yolo_rs::manifest::{ManifestEncoding, ManifestTarget};
+use yolo_rs::{
+    batch::{BatchOutput, BatchReport, BatchRunner},
+    dataset::{Dataset, Split},
+    eval::{load_coco_ground_truth, Evaluator},
+    export::{
+        coco::{image_ids_from_annotations, CocoResultsWriter},
+        crops::{export_crops, CropOptions},
+        label_studio::{labeling_config, LabelStudioTasks},
+        voc::write_voc_xml,
+        yolo_labels::{label_directory, write_labels},
+    },
+    jsonl::{Detection, JsonlWriter},
+    utils::{self, Anonymization, DrawStyle, FontSize},
+    BuildError, ConfThresh, IOUThresh, InferenceResult, InputSize, Yolo, YoloBuilder,
+};
+#[cfg(feature = "video-plugin")]
+use yolo_rs::{
+    tracking::{Tracker, TrackerConfig},
+    DrawBoundingBoxes,
+};
+
+type CliResult<T> = Result<T, Box<dyn Error>>;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    #[command(flatten)]
+    model: ModelArgs,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Args, Debug)]
+struct ModelArgs {
+    /// path to model file, with encoding, repeated for models made of several files
+    #[arg(short, long, global = true)]
+    model_path: Vec<PathBuf>,
+
+    /// classnames path, one class per line
+    #[arg(short, long, global = true)]
+    class_names_path: Option<PathBuf>,
+
+    /// take the class names from the `names` of an ultralytics data.yaml
+    #[arg(long, global = true, conflicts_with = "class_names_path")]
+    data_yaml: Option<PathBuf>,
+
+    /// path to a model manifest (.toml / .yaml), other model options override its settings
+    #[arg(long, global = true)]
+    manifest: Option<PathBuf>,
+
+    /// name of a model preloaded by the host with `wasmedge --nn-preload`, instead of --model-path
+    #[arg(long, global = true)]
+    cache_name: Option<String>,
+
+    /// confidence threshold, defaults to the manifest's or 0.5 (0.001 for eval)
+    #[arg(long, global = true)]
+    conf: Option<f32>,
+
+    /// IoU threshold of non maximum suppression, defaults to the manifest's or 0.5
+    #[arg(long, global = true)]
+    iou: Option<f32>,
+
+    /// model input size as WIDTHxHEIGHT, e.g. 640x640
+    #[arg(long, global = true, value_parser = parse_input_size)]
+    input_size: Option<InputSize>,
+
+    /// execution target of the WASI-NN plugin: cpu, gpu, tpu or auto
+    #[cfg(feature = "wasi-nn")]
+    #[arg(long, global = true)]
+    target: Option<ManifestTarget>,
+
+    /// graph encoding of the WASI-NN plugin: openvino, onnx, tensorflow, pytorch, tflite or autodetect
+    #[cfg(feature = "wasi-nn")]
+    #[arg(long, global = true)]
+    encoding: Option<ManifestEncoding>,
+
+    /// print the time spent in each inference stage when done
+    #[arg(long, global = true)]
+    stats: bool,
+}
+
+impl ModelArgs {
+    /// Builds the model, with `classes` unless the classes are given on the command line or in the manifest
+    fn build(&self, classes: Option<&[String]>) -> Result<Yolo, BuildError> {
+        let mut builder = match &self.manifest {
+            Some(manifest) => YoloBuilder::from_manifest(manifest)?,
+            None => YoloBuilder::new(),
+        };
+        if let Some(path) = &self.class_names_path {
+            builder = builder.classes_file(path)?;
+        } else if let Some(path) = &self.data_yaml {
+            builder = builder.data_yaml(path)?;
+        } else if let (None, Some(classes)) = (&self.manifest, classes) {
+            builder = builder.classes(classes.to_vec());
+        }
+
+        if !self.model_path.is_empty() {
+            builder = builder.model_files(&self.model_path);
+        }
+        if let Some(name) = &self.cache_name {
+            builder = builder.model_cache_name(name.clone());
+        }
+        if let Some(conf) = self.conf {
+            builder = builder.conf_thresh(ConfThresh(conf));
+        }
+        if let Some(iou) = self.iou {
+            builder = builder.iou_thresh(IOUThresh(iou));
+        }
+        if let Some(input_size) = self.input_size {
+            builder = builder.input_size(input_size);
+        }
+        #[cfg(feature = "wasi-nn")]
+        {
+            if let Some(target) = self.target {
+                builder = builder.execution_target(target.into());
+            }
+            if let Some(encoding) = self.encoding {
+                builder = builder.graph_encoding(encoding.into());
+            }
+        }
+        builder.collect_stats(self.stats).build()
+    }
+}
+
+fn parse_input_size(input_size: &str) -> Result<InputSize, String> {
+    let error = || format!("expected WIDTHxHEIGHT, got `{}`", input_size);
+    let (width, height) = input_size.split_once(['x', 'X']).ok_or_else(error)?;
+    Ok(InputSize::new(
+        width.trim().parse().map_err(|_| error())?,
+        height.trim().parse().map_err(|_| error())?,
+    ))
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run detection on an image, a directory of images or a video
+    #[command(subcommand)]
+    Detect(DetectCommand),
+    /// Evaluate the model on a YOLO dataset or COCO annotations
+    Eval(EvalArgs),
+    /// Run detection on a directory of images and export the results as a dataset
+    Export(ExportArgs),
+    /// Print the model classes, input size and thresholds
+    Info,
+}
+
+#[derive(Subcommand, Debug)]
+enum DetectCommand {
+    /// Detect objects in a single image
+    Image {
+        /// image to run detection on
+        image_path: PathBuf,
+        #[command(flatten)]
+        output: OutputArgs,
+        #[command(flatten)]
+        draw: DrawArgs,
+    },
+    /// Detect objects in every image of a directory and its sub directories,
+    /// outputs mirror the directory tree
+    Dir {
+        /// directory of the images
+        images_dir: PathBuf,
+        #[command(flatten)]
+        output: OutputArgs,
+        #[command(flatten)]
+        draw: DrawArgs,
+    },
+    /// Detect objects in a video and write it with the detections drawn, requires the video plugin
+    #[cfg(feature = "video-plugin")]
+    Video {
+        /// video to run detection on
+        input_video_path: PathBuf,
+        /// video written with the detections drawn
+        output_video_path: PathBuf,
+        /// assign track ids to the detections, linking objects across frames
+        #[arg(long)]
+        track: bool,
+        /// write the detections of every frame to this JSON file
+        #[arg(long)]
+        results: Option<PathBuf>,
+        /// write the detections of each frame as a JSON line to this file as soon as they are produced,
+        /// `-` for stdout
+        #[arg(long)]
+        jsonl: Option<PathBuf>,
+        /// frame rate of the input video, adds the time of each frame in the video to the JSON lines
+        #[arg(long)]
+        fps: Option<f64>,
+        /// only keep detections of these comma separated classes, e.g. `person,car`
+        #[arg(long, value_delimiter = ',')]
+        filter_classes: Vec<String>,
+        #[command(flatten)]
+        draw: DrawArgs,
+    },
+}
+
+impl Command {
+    /// JSON lines output of the command, see [`status`]
+    fn jsonl(&self) -> Option<&Path> {
+        match self {
+            Command::Detect(DetectCommand::Image { output, .. })
+            | Command::Detect(DetectCommand::Dir { output, .. }) => output.jsonl.as_deref(),
+            #[cfg(feature = "video-plugin")]
+            Command::Detect(DetectCommand::Video { jsonl, .. }) => jsonl.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// What `detect image` and `detect dir` write for each image
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    /// the image with the detections drawn
+    Image,
+    /// the detections as a JSON array
+    Json,
+    /// a YOLO label file
+    Yolo,
+    /// a Pascal VOC XML file
+    Voc,
+}
+
+#[derive(Args, Debug)]
+struct OutputArgs {
+    /// output directory
+    #[arg(short, long, default_value = "detections")]
+    output_dir: PathBuf,
+
+    /// comma separated output formats
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "image")]
+    format: Vec<OutputFormat>,
+
+    /// only keep detections of these comma separated classes, e.g. `person,car`
+    #[arg(long, value_delimiter = ',')]
+    filter_classes: Vec<String>,
+
+    /// also write the detections of each image as a JSON line to this file, `-` for stdout
+    #[arg(long)]
+    jsonl: Option<PathBuf>,
+}
+
+type Jsonl = JsonlWriter<Box<dyn Write>>;
+
+/// JSON lines writer to a file, or to stdout for `-`
+fn jsonl_writer(path: &Path) -> CliResult<Jsonl> {
+    let writer: Box<dyn Write> = if path == Path::new("-") {
+        Box::new(io::stdout())
+    } else {
+        Box::new(fs::File::create(path)?)
+    };
+    Ok(JsonlWriter::new(writer))
+}
+
+/// Prints a progress line, to stderr when stdout carries JSON lines
+fn status(jsonl: Option<&Path>, line: &str) {
+    if jsonl == Some(Path::new("-")) {
+        eprintln!("{}", line);
+    } else {
+        println!("{}", line);
+    }
+}
+
+/// Keeps the detections of `classes`, all of them if `classes` is empty
+fn filter_classes(results: &mut Vec<InferenceResult>, classes: &[String]) {
+    if !classes.is_empty() {
+        results.retain(|result| classes.iter().any(|class| class == result.class()));
+    }
+}
+
+#[derive(Args, Debug)]
+struct DrawArgs {
+    /// font of the labels, the bundled font by default
+    #[arg(long)]
+    font: Option<PathBuf>,
+
+    /// thickness of the box outlines in pixels
+    #[arg(long, default_value_t = 2)]
+    thickness: u32,
+
+    /// label height in pixels, 2.5% of the image height by default
+    #[arg(long)]
+    font_size: Option<f32>,
+
+    /// draw boxes without labels
+    #[arg(long)]
+    no_labels: bool,
+
+    /// leave the confidence out of the labels
+    #[arg(long)]
+    no_confidence: bool,
+
+    /// pixelate the detections of these comma separated classes instead of drawing boxes
+    #[arg(long, value_delimiter = ',')]
+    anonymize: Option<Vec<String>>,
+}
+
+impl DrawArgs {
+    fn style(&self) -> DrawStyle {
+        let default = DrawStyle::default();
+        DrawStyle {
+            thickness: self.thickness,
+            font_size: self.font_size.map_or(default.font_size, FontSize::Fixed),
+            show_labels: !self.no_labels,
+            show_confidence: !self.no_confidence,
+            ..default
+        }
+    }
+
+    fn load_font(&self) -> CliResult<Font<'static>> {
+        match &self.font {
+            Some(path) => Ok(utils::load_font(path)?),
+            #[cfg(feature = "bundled-font")]
+            None => Ok(utils::default_font()),
+            #[cfg(not(feature = "bundled-font"))]
+            None => Err("--font is required without the bundled-font feature".into()),
+        }
+    }
+
+    /// Draws or anonymizes the detections
+    fn draw(&self, image: RgbImage, results: &[InferenceResult], font: &Font<'static>) -> RgbImage {
+        match &self.anonymize {
+            Some(classes) => {
+                utils::anonymize_mut_image(image, results, classes, Anonymization::default())
+            }
+            None => utils::draw_bounding_boxes_with_style(image, results, font, &self.style()),
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum SplitArg {
+    Train,
+    Val,
+    Test,
+}
+
+impl From<SplitArg> for Split {
+    fn from(split: SplitArg) -> Self {
+        match split {
+            SplitArg::Train => Split::Train,
+            SplitArg::Val => Split::Val,
+            SplitArg::Test => Split::Test,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct EvalArgs {
+    /// ultralytics data.yaml of the dataset, also gives the classes if the model has none
+    #[arg(long, required_unless_present = "coco")]
+    data: Option<PathBuf>,
+
+    /// split of the dataset to evaluate on
+    #[arg(long, value_enum, default_value = "val")]
+    split: SplitArg,
+
+    /// COCO annotations file, e.g. instances_val2017.json, instead of a dataset
+    #[arg(long, requires = "images", conflicts_with = "data")]
+    coco: Option<PathBuf>,
+
+    /// directory of the images of the COCO annotations
+    #[arg(long)]
+    images: Option<PathBuf>,
+
+    /// write the confusion matrix, precision recall and F1 curves as CSV and PNG files to this directory
+    #[arg(long)]
+    plots: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum ExportFormat {
+    /// every detection cropped to `<output>/<class>/`
+    Crops,
+    /// COCO detection results, `<output>/predictions.json`
+    Coco,
+    /// a YOLO dataset with labels, classes.txt and data.yaml
+    Yolo,
+    /// a Pascal VOC XML file per image
+    Voc,
+    /// Label Studio tasks with predictions, and the labelling interface
+    LabelStudio,
+}
+
+#[derive(Args, Debug)]
+struct ExportArgs {
+    #[arg(value_enum)]
+    format: ExportFormat,
+
+    /// directory of the images, searched recursively
+    images_dir: PathBuf,
+
+    /// output directory
+    #[arg(short, long)]
+    output_dir: PathBuf,
+
+    /// COCO annotations giving the image ids of `coco`, images are numbered from 1 otherwise
+    #[arg(long)]
+    annotations: Option<PathBuf>,
+
+    /// margin of the `crops`, as a fraction of the box size
+    #[arg(long, default_value_t = 0.0)]
+    padding: f32,
+
+    /// make the `crops` square
+    #[arg(long)]
+    square: bool,
+
+    /// prefix of the image paths in `label-studio` tasks
+    #[arg(long, default_value = "/data/local-files/?d=")]
+    url_prefix: String,
+}
+
+pub fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(&cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            let mut source = error.source();
+            while let Some(cause) = source {
+                eprintln!("  caused by: {}", cause);
+                source = cause.source();
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: &Cli) -> CliResult<()> {
+    let yolo = match &cli.command {
+        Command::Detect(command) => {
+            let yolo = cli.model.build(None)?;
+            detect(&yolo, command)?;
+            yolo
+        }
+        Command::Eval(args) => eval(&cli.model, args)?,
+        Command::Export(args) => {
+            let yolo = cli.model.build(None)?;
+            export(&yolo, args)?;
+            yolo
+        }
+        Command::Info => {
+            let yolo = cli.model.build(None)?;
+            info(&yolo);
+            yolo
+        }
+    };
+
+    if let Some(stats) = yolo.stats() {
+        status(cli.command.jsonl(), &stats.to_string());
+    }
+    Ok(())
+}
+
+fn detect(yolo: &Yolo, command: &DetectCommand) -> CliResult<()> {
+    match command {
+        DetectCommand::Image {
+            image_path,
+            output,
+            draw,
+        } => {
+            let font = draw.load_font()?;
+            let mut jsonl = output.jsonl.as_deref().map(jsonl_writer).transpose()?;
+            let file_name = Path::new(image_path.file_name().unwrap_or_default());
+            detect_image(
+                yolo,
+                image_path,
+                file_name,
+                output,
+                draw,
+                &font,
+                jsonl.as_mut(),
+            )
+        }
+        DetectCommand::Dir {
+            images_dir,
+            output,
+            draw,
+        } => {
+            let outputs = output.format.iter().map(|format| match format {
+                OutputFormat::Image => BatchOutput::AnnotatedImage,
+                OutputFormat::Json => BatchOutput::Json,
+                OutputFormat::Yolo => BatchOutput::YoloLabels,
+                OutputFormat::Voc => BatchOutput::VocXml,
+            });
+            let runner = BatchRunner::from_dir(images_dir, &output.output_dir)?
+                .with_outputs(outputs)
+                .with_style(draw.style())
+                .with_resume(false)
+                .with_progress(false);
+            let runner = match &draw.anonymize {
+                Some(classes) => {
+                    runner.with_anonymization(classes.clone(), Anonymization::default())
+                }
+                None => runner.with_font(draw.load_font()?),
+            };
+
+            let mut jsonl = output.jsonl.as_deref().map(jsonl_writer).transpose()?;
+            let report = runner.run_with(
+                yolo,
+                &yolo.default_conf_thresh(),
+                &yolo.default_iou_thresh(),
+                |image_path, _, _, results| {
+                    filter_classes(results, &output.filter_classes);
+                    status(
+                        output.jsonl.as_deref(),
+                        &format!("{}: {}", image_path.display(), summary(results)),
+                    );
+                    if let Some(writer) = jsonl.as_mut() {
+                        writer.write(&image_path.to_string_lossy(), 0, results)?;
+                    }
+                    Ok(())
+                },
+            )?;
+            check_failures(batch_failures(&report))
+        }
+        #[cfg(feature = "video-plugin")]
+        DetectCommand::Video {
+            input_video_path,
+            output_video_path,
+            track,
+            results,
+            jsonl: jsonl_path,
+            fps,
+            filter_classes: classes,
+            draw,
+        } => {
+            let mut conf_thresh = yolo.default_conf_thresh();
+            // The tracker uses low confidence detections to keep existing tracks alive,
+            // only detections above the default threshold start new tracks
+            let mut tracker = track.then(|| {
+                let config = TrackerConfig {
+                    high_conf_thresh: conf_thresh.0,
+                    ..Default::default()
+                };
+                conf_thresh = ConfThresh(config.low_conf_thresh.min(conf_thresh.0));
+                Tracker::new(config)
+            });
+            let draw_bounding_boxes = match &draw.anonymize {
+                Some(classes) => DrawBoundingBoxes::Anonymize {
+                    classes: classes.clone(),
+                    anonymization: Anonymization::default(),
+                },
+                None => DrawBoundingBoxes::WithStyle(draw.load_font()?, draw.style()),
+            };
+
+            let source = input_video_path.to_string_lossy();
+            let mut jsonl = jsonl_path
+                .as_deref()
+                .map(jsonl_writer)
+                .transpose()?
+                .map(|writer| match fps {
+                    Some(fps) => writer.with_fps(*fps),
+                    None => writer,
+                });
+            let mut frames = Vec::new();
+            let frame_count = yolo.infer_video_with(
+                input_video_path,
+                output_video_path,
+                &conf_thresh,
+                &yolo.default_iou_thresh(),
+                draw_bounding_boxes,
+                tracker.as_mut(),
+                |frame, frame_results| {
+                    filter_classes(frame_results, classes);
+                    if let Some(writer) = jsonl.as_mut() {
+                        writer.write(&source, frame, frame_results)?;
+                    }
+                    frames.push(detections(frame_results));
+                    Ok(())
+                },
+            )?;
+            status(
+                jsonl_path.as_deref(),
+                &format!(
+                    "{}: {} frames, {} detections",
+                    input_video_path.display(),
+                    frame_count,
+                    frames.iter().map(Vec::len).sum::<usize>()
+                ),
+            );
+            if let Some(results_path) = results {
+                fs::write(results_path, serde_json::to_string(&frames)?)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Runs detection on an image and writes the outputs to `<output_dir>/<relative_path>`
+fn detect_image(
+    yolo: &Yolo,
+    image_path: &Path,
+    relative_path: &Path,
+    output: &OutputArgs,
+    draw: &DrawArgs,
+    font: &Font<'static>,
+    jsonl: Option<&mut Jsonl>,
+) -> CliResult<()> {
+    let image = image::open(image_path)?.to_rgb8();
+    let mut results = yolo.infer_image(
+        &yolo.default_conf_thresh(),
+        &yolo.default_iou_thresh(),
+        &image,
+    )?;
+    filter_classes(&mut results, &output.filter_classes);
+    status(
+        output.jsonl.as_deref(),
+        &format!("{}: {}", image_path.display(), summary(&results)),
+    );
+    if let Some(writer) = jsonl {
+        writer.write(&image_path.to_string_lossy(), 0, &results)?;
+    }
+
+    let output_path = output.output_dir.join(relative_path);
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let (width, height) = image.dimensions();
+    for format in &output.format {
+        match format {
+            OutputFormat::Image => {
+                draw.draw(image.clone(), &results, font)
+                    .save(&output_path)?;
+            }
+            OutputFormat::Json => fs::write(
+                output_path.with_extension("json"),
+                serde_json::to_string_pretty(&detections(&results))?,
+            )?,
+            OutputFormat::Yolo => {
+                write_labels(output_path.with_extension("txt"), &results, width, height)?
+            }
+            OutputFormat::Voc => write_voc_xml(
+                output_path.with_extension("xml"),
+                &relative_path.to_string_lossy(),
+                (width, height),
+                &results,
+            )?,
+        }
+    }
+    Ok(())
+}
+
+/// Prints the images of a batch that failed with their error message, a corrupt image does not stop
+/// the others but the command still fails
+fn check_failures<'a>(failures: impl IntoIterator<Item = (&'a Path, String)>) -> CliResult<()> {
+    let mut failed = 0;
+    for (image_path, message) in failures {
+        eprintln!("{}: {}", image_path.display(), message);
+        failed += 1;
+    }
+    match failed {
+        0 => Ok(()),
+        failed => Err(format!("{} images could not be processed", failed).into()),
+    }
+}
+
+fn batch_failures(report: &BatchReport) -> impl Iterator<Item = (&Path, String)> {
+    report
+        .failures
+        .iter()
+        .map(|failure| (failure.path.as_path(), failure.message()))
+}
+
+/// Count of detections by class, e.g. `2 person, 1 car`
+fn summary(results: &[InferenceResult]) -> String {
+    let mut counts: Vec<(&str, usize)> = Vec::new();
+    for result in results {
+        match counts
+            .iter_mut()
+            .find(|(class, _)| *class == result.class())
+        {
+            Some((_, count)) => *count += 1,
+            None => counts.push((result.class(), 1)),
+        }
+    }
+    if counts.is_empty() {
+        return "no detections".to_string();
+    }
+    counts
+        .iter()
+        .map(|(class, count)| format!("{} {}", count, class))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn detections(results: &[InferenceResult]) -> Vec<Detection> {
+    results.iter().map(Detection::from).collect()
+}
+
+fn eval(model: &ModelArgs, args: &EvalArgs) -> CliResult<Yolo> {
+    let iou_thresh = |yolo: &Yolo| yolo.default_iou_thresh();
+    // a low threshold keeps the whole precision recall curve
+    let conf_thresh = ConfThresh(model.conf.unwrap_or(0.001));
+
+    let (yolo, evaluator) = match (&args.data, &args.coco, &args.images) {
+        (Some(data), _, _) => {
+            let dataset = Dataset::from_yaml(data)?;
+            let yolo = model.build(Some(dataset.classes()))?;
+            let mut evaluator = Evaluator::new(yolo.classes().to_vec());
+            for sample in dataset.iter(args.split.into())? {
+                let (image, ground_truth) = sample?;
+                let results = yolo.infer_image(&conf_thresh, &iou_thresh(&yolo), &image)?;
+                evaluator.add_image(&results, &ground_truth);
+            }
+            (yolo, evaluator)
+        }
+        (None, Some(coco), Some(images_dir)) => {
+            let yolo = model.build(None)?;
+            let mut evaluator = Evaluator::new(yolo.classes().to_vec());
+            let mut ground_truth = load_coco_ground_truth(coco, yolo.classes())?
+                .into_iter()
+                .collect::<Vec<_>>();
+            ground_truth.sort_by(|a, b| a.0.cmp(&b.0));
+            for (file_name, objects) in ground_truth {
+                let results =
+                    yolo.infer_file(images_dir.join(file_name), &conf_thresh, &iou_thresh(&yolo))?;
+                evaluator.add_image(&results, &objects);
+            }
+            (yolo, evaluator)
+        }
+        _ => return Err("eval needs --data, or --coco and --images".into()),
+    };
+
+    println!("{}", evaluator.evaluate());
+    if let Some(plots_dir) = &args.plots {
+        #[cfg(feature = "bundled-font")]
+        let font = Some(utils::default_font());
+        #[cfg(not(feature = "bundled-font"))]
+        let font = None;
+        evaluator.save_plots(plots_dir, font.as_ref())?;
+    }
+    Ok(yolo)
+}
+
+fn export(yolo: &Yolo, args: &ExportArgs) -> CliResult<()> {
+    let (conf_thresh, iou_thresh) = (yolo.default_conf_thresh(), yolo.default_iou_thresh());
+    if args.format == ExportFormat::Yolo {
+        let report = label_directory(
+            yolo,
+            &args.images_dir,
+            &args.output_dir,
+            &conf_thresh,
+            &iou_thresh,
+        )?;
+        println!("{} label files written", report.label_paths.len());
+        return check_failures(
+            report
+                .failures
+                .iter()
+                .map(|(image_path, error)| (image_path.as_path(), utils::error_chain(error))),
+        );
+    }
+
+    let image_ids = match &args.annotations {
+        Some(annotations) => Some(image_ids_from_annotations(annotations)?),
+        None => None,
+    };
+    let mut coco = CocoResultsWriter::new(yolo.classes());
+    let mut label_studio = LabelStudioTasks::new("yolo-rs");
+    let crop_options = CropOptions {
+        padding: args.padding,
+        square: args.square,
+        ..Default::default()
+    };
+    fs::create_dir_all(&args.output_dir)?;
+
+    // VOC files are written by the runner, the other formats by the callback
+    let outputs = match args.format {
+        ExportFormat::Voc => vec![BatchOutput::VocXml],
+        _ => Vec::new(),
+    };
+    let runner = BatchRunner::from_dir(&args.images_dir, &args.output_dir)?
+        .with_outputs(outputs)
+        .with_resume(false)
+        .with_progress(false);
+    let default_image_ids = runner
+        .images()
+        .iter()
+        .enumerate()
+        .map(|(idx, (image_path, _))| (image_path.clone(), idx as u64 + 1))
+        .collect::<HashMap<PathBuf, u64>>();
+
+    let report = runner.run_with(
+        yolo,
+        &conf_thresh,
+        &iou_thresh,
+        |image_path, relative_path, image, results| {
+            let file_name = relative_path.to_string_lossy();
+            match args.format {
+                ExportFormat::Crops => {
+                    let stem = relative_path
+                        .with_extension("")
+                        .to_string_lossy()
+                        .replace(['/', '\\'], "_");
+                    export_crops(image, results, &crop_options, &args.output_dir, &stem)?;
+                }
+                ExportFormat::Coco => {
+                    let image_id = match &image_ids {
+                        Some(image_ids) => match image_ids.get(file_name.as_ref()) {
+                            Some(image_id) => *image_id,
+                            None => {
+                                eprintln!("{}: not in the annotations, skipped", file_name);
+                                return Ok(());
+                            }
+                        },
+                        None => default_image_ids[image_path],
+                    };
+                    coco.add(image_id, results)?;
+                }
+                ExportFormat::Voc => {}
+                ExportFormat::LabelStudio => label_studio.add(
+                    format!("{}{}", args.url_prefix, file_name),
+                    image.dimensions(),
+                    results,
+                ),
+                ExportFormat::Yolo => unreachable!("exported with label_directory"),
+            }
+            println!("{}: {}", image_path.display(), summary(results));
+            Ok(())
+        },
+    )?;
+
+    match args.format {
+        ExportFormat::Coco => coco.write(args.output_dir.join("predictions.json"))?,
+        ExportFormat::LabelStudio => {
+            label_studio.write(args.output_dir.join("tasks.json"))?;
+            fs::write(
+                args.output_dir.join("labeling_config.xml"),
+                labeling_config(yolo.classes()),
+            )?;
+        }
+        _ => {}
+    }
+    check_failures(batch_failures(&report))
+}
+
+fn info(yolo: &Yolo) {
+    let input_size = yolo.input_size();
+    println!("backend: {}", std::any::type_name_of_val(yolo.backend()));
+    println!("input size: {}x{}", input_size.width, input_size.height);
+    println!("confidence threshold: {}", yolo.default_conf_thresh().0);
+    println!("IoU threshold: {}", yolo.default_iou_thresh().0);
+    println!("classes ({}):", yolo.classes().len());
+    for (class_id, class) in yolo.classes().iter().enumerate() {
+        println!("  {:>4} {}", class_id, class);
+    }
+}
@@ -0,0 +1,353 @@
+//! Datasets in the ultralytics YOLO layout
+//!
+//! A `data.yaml` file declares the class names and the images of each split,
+//! as directories, lists of directories or text files listing image paths:
+//!
+//! ```yaml
+//! path: .  # dataset root, relative to this file
+//! train: images/train
+//! val: images/val
+//! test: [images/test, images/extra]
+//! names:
+//!   0: person
+//!   1: car
+//! ```
+//!
+//! The label file of an image is found by replacing the last `images` directory of its path with `labels`,
+//! and its extension with `.txt`: `images/val/0001.jpg` is labelled by `labels/val/0001.txt`.
+//! Images without a label file have no objects.
+//!
+//! ```rust,ignore
+//! let dataset = Dataset::from_yaml("coco128/data.yaml")?;
+//! let yolo = YoloBuilder::new().data_yaml("coco128/data.yaml")?.build_from_files([model_path])?;
+//! let mut evaluator = Evaluator::new(dataset.classes().to_vec());
+//! for sample in dataset.iter(Split::Val)? {
+//!     let (image, ground_truth) = sample?;
+//!     evaluator.add_image(&yolo.infer_image(&ConfThresh(0.001), &iou_thresh, &image)?, &ground_truth);
+//! }
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    ffi::OsStr,
+    fmt, fs,
+    path::{Component, Path, PathBuf},
+    vec,
+};
+
+use image::RgbImage;
+use serde::Deserialize;
+
+use crate::{
+    eval::{load_yolo_labels, EvalError, GroundTruth},
+    utils,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum DatasetError {
+    #[error("error reading dataset file")]
+    FileError(#[from] std::io::Error),
+
+    #[error("error parsing data.yaml")]
+    Yaml(#[from] serde_yaml::Error),
+
+    #[error("error reading image")]
+    ImageError(#[from] image::ImageError),
+
+    #[error("error reading labels")]
+    Labels(#[from] EvalError),
+
+    #[error("data.yaml does not declare a {0} split")]
+    MissingSplit(Split),
+}
+
+/// Split of a dataset, as declared in `data.yaml`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Split {
+    Train,
+    Val,
+    Test,
+}
+
+impl fmt::Display for Split {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Split::Train => write!(f, "train"),
+            Split::Val => write!(f, "val"),
+            Split::Test => write!(f, "test"),
+        }
+    }
+}
+
+/// Class names as a list, or a map from class id to name
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Names {
+    List(Vec<String>),
+    Map(BTreeMap<usize, String>),
+}
+
+/// A split as one path or a list of paths
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SplitPaths {
+    One(PathBuf),
+    Many(Vec<PathBuf>),
+}
+
+impl SplitPaths {
+    fn into_vec(self) -> Vec<PathBuf> {
+        match self {
+            SplitPaths::One(path) => vec![path],
+            SplitPaths::Many(paths) => paths,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct DataYaml {
+    path: Option<PathBuf>,
+    train: Option<SplitPaths>,
+    val: Option<SplitPaths>,
+    test: Option<SplitPaths>,
+    names: Names,
+}
+
+/// Dataset declared by a `data.yaml` file, see the [module documentation](self)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dataset {
+    root: PathBuf,
+    classes: Vec<String>,
+    train: Vec<PathBuf>,
+    val: Vec<PathBuf>,
+    test: Vec<PathBuf>,
+}
+
+impl Dataset {
+    /// Reads a `data.yaml` file. Its `path` is resolved against the directory of the file,
+    /// the splits against `path`
+    pub fn from_yaml<P: AsRef<Path>>(yaml_path: P) -> Result<Self, DatasetError> {
+        let yaml_path = yaml_path.as_ref();
+        let data: DataYaml = serde_yaml::from_str(&fs::read_to_string(yaml_path)?)?;
+
+        let yaml_dir = yaml_path.parent().unwrap_or(Path::new(""));
+        let root = match data.path {
+            Some(path) => yaml_dir.join(path),
+            None => yaml_dir.to_path_buf(),
+        };
+        let resolve = |paths: Option<SplitPaths>| {
+            paths
+                .map(SplitPaths::into_vec)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|path| root.join(path))
+                .collect::<Vec<PathBuf>>()
+        };
+
+        // Class ids missing from a map are named after their id
+        let classes = match data.names {
+            Names::List(names) => names,
+            Names::Map(names) => {
+                let len = names.keys().next_back().map_or(0, |max| max + 1);
+                (0..len)
+                    .map(|id| names.get(&id).cloned().unwrap_or_else(|| id.to_string()))
+                    .collect()
+            }
+        };
+
+        Ok(Dataset {
+            train: resolve(data.train),
+            val: resolve(data.val),
+            test: resolve(data.test),
+            root,
+            classes,
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Class names, by class id
+    pub fn classes(&self) -> &[String] {
+        &self.classes
+    }
+
+    /// Directories and image list files of a split, empty if the split is not declared
+    pub fn split_paths(&self, split: Split) -> &[PathBuf] {
+        match split {
+            Split::Train => &self.train,
+            Split::Val => &self.val,
+            Split::Test => &self.test,
+        }
+    }
+
+    /// Paths of the images of a split, and of their label files, which may not exist
+    pub fn samples(&self, split: Split) -> Result<Vec<(PathBuf, PathBuf)>, DatasetError> {
+        let paths = self.split_paths(split);
+        if paths.is_empty() {
+            return Err(DatasetError::MissingSplit(split));
+        }
+
+        let mut images = Vec::new();
+        for path in paths {
+            if path.is_dir() {
+                images.extend(utils::image_files(path)?);
+            } else {
+                // text file with an image path per line, relative to the file
+                let list_dir = path.parent().unwrap_or(Path::new(""));
+                images.extend(
+                    fs::read_to_string(path)?
+                        .lines()
+                        .map(str::trim)
+                        .filter(|line| !line.is_empty())
+                        .map(|line| list_dir.join(line)),
+                );
+            }
+        }
+        Ok(images
+            .into_iter()
+            .map(|image| {
+                let label = label_path(&image);
+                (image, label)
+            })
+            .collect())
+    }
+
+    /// Images of a split with their ground truth, in the order of [`Dataset::samples`]
+    pub fn iter(&self, split: Split) -> Result<DatasetIter, DatasetError> {
+        Ok(DatasetIter {
+            classes: self.classes.clone(),
+            samples: self.samples(split)?.into_iter(),
+        })
+    }
+
+    /// Reads an image and its ground truth, empty if the label file does not exist
+    pub fn load(
+        &self,
+        image_path: &Path,
+        label_path: &Path,
+    ) -> Result<(RgbImage, Vec<GroundTruth>), DatasetError> {
+        load_sample(&self.classes, image_path, label_path)
+    }
+}
+
+fn load_sample(
+    classes: &[String],
+    image_path: &Path,
+    label_path: &Path,
+) -> Result<(RgbImage, Vec<GroundTruth>), DatasetError> {
+    let image = image::open(image_path)?.to_rgb8();
+    let ground_truth = if label_path.is_file() {
+        load_yolo_labels(label_path, image.width(), image.height(), classes)?
+    } else {
+        Vec::new()
+    };
+    Ok((image, ground_truth))
+}
+
+/// Label file of an image, the last `images` directory of its path replaced with `labels`
+/// and its extension with `.txt`
+pub fn label_path(image_path: &Path) -> PathBuf {
+    let components = image_path.components().collect::<Vec<Component>>();
+    let images_idx = components
+        .iter()
+        .rposition(|component| component.as_os_str() == OsStr::new("images"));
+
+    let mut label_path = components
+        .iter()
+        .enumerate()
+        .map(|(idx, component)| {
+            if Some(idx) == images_idx {
+                OsStr::new("labels")
+            } else {
+                component.as_os_str()
+            }
+        })
+        .collect::<PathBuf>();
+    label_path.set_extension("txt");
+    label_path
+}
+
+/// Iterator over the images of a split and their ground truth, see [`Dataset::iter`]
+pub struct DatasetIter {
+    classes: Vec<String>,
+    samples: vec::IntoIter<(PathBuf, PathBuf)>,
+}
+
+impl Iterator for DatasetIter {
+    type Item = Result<(RgbImage, Vec<GroundTruth>), DatasetError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (image_path, label_path) = self.samples.next()?;
+        Some(load_sample(&self.classes, &image_path, &label_path))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.samples.size_hint()
+    }
+}
+
+impl ExactSizeIterator for DatasetIter {}
+
+#[cfg(test)]
+mod tests {
+    use super::{label_path, Dataset, DatasetError, Split};
+    use image::RgbImage;
+    use imageproc::rect::Rect;
+    use std::{fs, path::Path};
+
+    #[test]
+    fn test_label_path() {
+        assert_eq!(
+            label_path(Path::new("data/images/val/images/0001.jpg")),
+            Path::new("data/images/val/labels/0001.txt")
+        );
+        assert_eq!(
+            label_path(Path::new("/data/images/0001.png")),
+            Path::new("/data/labels/0001.txt")
+        );
+    }
+
+    #[test]
+    fn test_dataset() {
+        let dir = std::env::temp_dir().join(format!("yolo-rs-dataset-{}", std::process::id()));
+        fs::create_dir_all(dir.join("coco/images/val")).unwrap();
+        fs::create_dir_all(dir.join("coco/labels/val")).unwrap();
+        fs::write(
+            dir.join("data.yaml"),
+            "path: coco\ntrain: train.txt\nval: images/val\nnames:\n  0: person\n  2: car\n",
+        )
+        .unwrap();
+        RgbImage::new(100, 50)
+            .save(dir.join("coco/images/val/a.png"))
+            .unwrap();
+        RgbImage::new(10, 10)
+            .save(dir.join("coco/images/val/b.png"))
+            .unwrap();
+        fs::write(dir.join("coco/labels/val/a.txt"), "2 0.5 0.5 0.2 0.4\n").unwrap();
+        fs::write(dir.join("coco/train.txt"), "./images/val/b.png\n").unwrap();
+
+        let dataset = Dataset::from_yaml(dir.join("data.yaml")).unwrap();
+        assert_eq!(dataset.classes(), ["person", "1", "car"]);
+
+        let samples = dataset
+            .iter(Split::Val)
+            .unwrap()
+            .collect::<Result<Vec<_>, DatasetError>>()
+            .unwrap();
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].1[0].class, "car");
+        assert_eq!(samples[0].1[0].b_box, Rect::at(40, 15).of_size(20, 20));
+        assert!(samples[1].1.is_empty());
+
+        let train = dataset.samples(Split::Train).unwrap();
+        assert!(train[0].0.ends_with("images/val/b.png"));
+        assert!(matches!(
+            dataset.iter(Split::Test),
+            Err(DatasetError::MissingSplit(Split::Test))
+        ));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
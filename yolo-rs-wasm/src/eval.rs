@@ -0,0 +1,724 @@
+//! Evaluation of a detection model against ground truth labels
+//!
+//! Predictions are matched to the ground truth of the same class at the IoU thresholds
+//! 0.5, 0.55, ..., 0.95, from the most to the least confident. [`Evaluator::evaluate`] then computes
+//! the average precision of each class (101 point interpolated, as in the COCO evaluation),
+//! mAP@0.5, mAP@0.5:0.95, and the precision and recall at the confidence maximizing the F1 of each class.
+//!
+//! ```rust,ignore
+//! let mut evaluator = Evaluator::new(yolo.classes().to_vec());
+//! for (image_path, label_path) in images {
+//!     let image = image::open(&image_path)?.to_rgb8();
+//!     let ground_truth = load_yolo_labels(label_path, image.width(), image.height(), yolo.classes())?;
+//!     evaluator.add_image(&yolo.infer_image(&ConfThresh(0.001), &iou_thresh, &image)?, &ground_truth);
+//! }
+//! println!("{}", evaluator.evaluate());
+//! ```
+//!
+//! Use a very low confidence threshold, such as 0.001, so that the precision recall curves are complete.
+//!
+//! The evaluator also accumulates a [`ConfusionMatrix`], and [`Evaluator::save_plots`] writes it along with
+//! the [precision recall and F1 curves](curves) as CSV files and PNG [charts]. The report includes the confidence
+//! maximizing the F1 of each class, to set [`ConfThresh`](crate::ConfThresh) from data.
+
+pub mod charts;
+pub mod confusion;
+pub mod curves;
+
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use imageproc::rect::Rect;
+use ndarray::Array2;
+use rusttype::Font;
+use serde::Deserialize;
+
+use crate::{
+    process::{bboxes_to_ndarray, rect_to_xyxy, vectorized_iou},
+    ConfThresh, InferenceResult,
+};
+use confusion::ConfusionMatrix;
+use curves::{CurvePoint, F1Curve, PrCurve};
+
+/// IoU thresholds at which predictions are matched, 0.5 to 0.95 by steps of 0.05
+pub const IOU_THRESHOLDS: [f32; 10] = [0.5, 0.55, 0.6, 0.65, 0.7, 0.75, 0.8, 0.85, 0.9, 0.95];
+
+#[derive(thiserror::Error, Debug)]
+pub enum EvalError {
+    #[error("error reading ground truth or writing evaluation file")]
+    FileError(#[from] std::io::Error),
+
+    #[error("error saving chart")]
+    ImageError(#[from] image::ImageError),
+
+    #[error("error parsing COCO annotations")]
+    Json(#[from] serde_json::Error),
+
+    #[error("invalid label on line {line} of {path}")]
+    InvalidLabel { path: PathBuf, line: usize },
+
+    #[error("class {0} is not in the class list")]
+    UnknownClass(String),
+}
+
+/// A labelled object
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroundTruth {
+    /// Bounding box in pixel coordinates of the image
+    pub b_box: Rect,
+    /// Index of the class in the class list
+    pub class_id: usize,
+    pub class: String,
+}
+
+/// Reads a label file in the YOLO training format, with one `class_id cx cy w h` line per object,
+/// coordinates normalized by the size of the image
+pub fn load_yolo_labels<P: AsRef<Path>>(
+    path: P,
+    image_width: u32,
+    image_height: u32,
+    classes: &[String],
+) -> Result<Vec<GroundTruth>, EvalError> {
+    let path = path.as_ref();
+    let contents = fs::read_to_string(path)?;
+    let invalid = |line| EvalError::InvalidLabel {
+        path: path.to_path_buf(),
+        line,
+    };
+
+    let mut ground_truth = Vec::new();
+    for (idx, line) in contents.lines().enumerate() {
+        let fields = line.split_whitespace().collect::<Vec<&str>>();
+        if fields.is_empty() {
+            continue;
+        }
+        if fields.len() < 5 {
+            return Err(invalid(idx + 1));
+        }
+        let class_id = fields[0].parse::<usize>().map_err(|_| invalid(idx + 1))?;
+        let mut coords = [0f32; 4];
+        for (coord, field) in coords.iter_mut().zip(&fields[1..5]) {
+            *coord = field.parse::<f32>().map_err(|_| invalid(idx + 1))?;
+        }
+        let class = classes
+            .get(class_id)
+            .ok_or_else(|| EvalError::UnknownClass(class_id.to_string()))?;
+
+        let [cx, cy, w, h] = coords;
+        let (width, height) = (w * image_width as f32, h * image_height as f32);
+        ground_truth.push(GroundTruth {
+            b_box: rect_from_xywh(
+                cx * image_width as f32 - width / 2.,
+                cy * image_height as f32 - height / 2.,
+                width,
+                height,
+            ),
+            class_id,
+            class: class.clone(),
+        });
+    }
+    Ok(ground_truth)
+}
+
+fn rect_from_xywh(x: f32, y: f32, width: f32, height: f32) -> Rect {
+    Rect::at(x.round() as i32, y.round() as i32).of_size(
+        (width.round() as u32).max(1),
+        (height.round() as u32).max(1),
+    )
+}
+
+#[derive(Deserialize)]
+struct CocoFile {
+    images: Vec<CocoImage>,
+    annotations: Vec<CocoAnnotation>,
+    categories: Vec<CocoCategory>,
+}
+
+#[derive(Deserialize)]
+struct CocoImage {
+    id: u64,
+    file_name: String,
+}
+
+#[derive(Deserialize)]
+struct CocoAnnotation {
+    image_id: u64,
+    category_id: u64,
+    bbox: [f32; 4],
+    #[serde(default)]
+    iscrowd: u8,
+}
+
+#[derive(Deserialize)]
+struct CocoCategory {
+    id: u64,
+    name: String,
+}
+
+/// Reads a COCO annotations file, such as `instances_val2017.json`, into the ground truth of each image,
+/// by file name. Categories are matched to `classes` by name, crowd annotations are skipped
+pub fn load_coco_ground_truth<P: AsRef<Path>>(
+    path: P,
+    classes: &[String],
+) -> Result<HashMap<String, Vec<GroundTruth>>, EvalError> {
+    let coco: CocoFile = serde_json::from_slice(&fs::read(path)?)?;
+
+    let category_classes = coco
+        .categories
+        .into_iter()
+        .map(|category| {
+            let class_id = classes
+                .iter()
+                .position(|class| *class == category.name)
+                .ok_or_else(|| EvalError::UnknownClass(category.name.clone()))?;
+            Ok((category.id, (class_id, category.name)))
+        })
+        .collect::<Result<HashMap<u64, (usize, String)>, EvalError>>()?;
+    let image_names = coco
+        .images
+        .into_iter()
+        .map(|image| (image.id, image.file_name))
+        .collect::<HashMap<u64, String>>();
+
+    let mut ground_truth = image_names
+        .values()
+        .map(|file_name| (file_name.clone(), Vec::new()))
+        .collect::<HashMap<String, Vec<GroundTruth>>>();
+    for annotation in coco.annotations {
+        if annotation.iscrowd != 0 {
+            continue;
+        }
+        let (Some(file_name), Some((class_id, class))) = (
+            image_names.get(&annotation.image_id),
+            category_classes.get(&annotation.category_id),
+        ) else {
+            continue;
+        };
+        let [x, y, width, height] = annotation.bbox;
+        if let Some(objects) = ground_truth.get_mut(file_name) {
+            objects.push(GroundTruth {
+                b_box: rect_from_xywh(x, y, width, height),
+                class_id: *class_id,
+                class: class.clone(),
+            });
+        }
+    }
+    Ok(ground_truth)
+}
+
+/// Predictions and ground truth count of a class, accumulated over every image
+#[derive(Debug, Clone, Default)]
+struct ClassRecords {
+    num_ground_truth: usize,
+    /// Confidence of each prediction, and whether it is a true positive at each IoU threshold
+    predictions: Vec<(f32, [bool; 10])>,
+}
+
+/// Metrics of a single class
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassMetrics {
+    pub class: String,
+    pub num_ground_truth: usize,
+    pub num_predictions: usize,
+    /// Average precision at each of the [`IOU_THRESHOLDS`]
+    pub ap: [f32; 10],
+    /// Precision at IoU 0.5 at the confidence of [`best_f1`](Self::best_f1), rather than over every
+    /// prediction down to the low evaluation threshold. 0 without any true positive
+    pub precision: f32,
+    /// Recall at IoU 0.5 at the confidence of [`best_f1`](Self::best_f1), 0 without any true positive
+    pub recall: f32,
+    /// Point of the precision recall curve with the highest F1, see [`PrCurve::best_f1`]
+    pub best_f1: Option<CurvePoint>,
+}
+
+impl ClassMetrics {
+    /// Average precision at IoU 0.5
+    pub fn ap50(&self) -> f32 {
+        self.ap[0]
+    }
+
+    /// Average precision, averaged over IoU 0.5 to 0.95
+    pub fn ap50_95(&self) -> f32 {
+        self.ap.iter().sum::<f32>() / self.ap.len() as f32
+    }
+
+    /// Confidence threshold maximizing the F1 of the class
+    pub fn best_conf_thresh(&self) -> Option<ConfThresh> {
+        self.best_f1.map(|point| ConfThresh(point.confidence))
+    }
+}
+
+/// Result of [`Evaluator::evaluate`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalReport {
+    pub num_images: usize,
+    /// Metrics of every class, in class order
+    pub classes: Vec<ClassMetrics>,
+}
+
+impl EvalReport {
+    /// Classes with at least one ground truth object, the others are left out of the means
+    fn labelled_classes(&self) -> impl Iterator<Item = &ClassMetrics> {
+        self.classes
+            .iter()
+            .filter(|metrics| metrics.num_ground_truth > 0)
+    }
+
+    fn mean(&self, metric: impl Fn(&ClassMetrics) -> f32) -> f32 {
+        let (sum, count) = self
+            .labelled_classes()
+            .fold((0., 0), |(sum, count), metrics| {
+                (sum + metric(metrics), count + 1)
+            });
+        if count == 0 {
+            return 0.;
+        }
+        sum / count as f32
+    }
+
+    pub fn map50(&self) -> f32 {
+        self.mean(ClassMetrics::ap50)
+    }
+
+    pub fn map50_95(&self) -> f32 {
+        self.mean(ClassMetrics::ap50_95)
+    }
+
+    /// Mean precision over the labelled classes, each at the confidence maximizing its F1
+    pub fn precision(&self) -> f32 {
+        self.mean(|metrics| metrics.precision)
+    }
+
+    /// Mean recall over the labelled classes, each at the confidence maximizing its F1
+    pub fn recall(&self) -> f32 {
+        self.mean(|metrics| metrics.recall)
+    }
+
+    pub fn class(&self, class: &str) -> Option<&ClassMetrics> {
+        self.classes.iter().find(|metrics| metrics.class == class)
+    }
+}
+
+impl fmt::Display for EvalReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<20}{:>10}{:>10}{:>10}{:>10}{:>10}{:>10}{:>10}{:>10}",
+            "class", "labels", "preds", "P@F1", "R@F1", "mAP50", "mAP50-95", "F1", "F1 conf"
+        )?;
+        let labels: usize = self.labelled_classes().map(|m| m.num_ground_truth).sum();
+        let predictions: usize = self.classes.iter().map(|m| m.num_predictions).sum();
+        writeln!(
+            f,
+            "{:<20}{:>10}{:>10}{:>10.3}{:>10.3}{:>10.3}{:>10.3}{:>10}{:>10}",
+            "all",
+            labels,
+            predictions,
+            self.precision(),
+            self.recall(),
+            self.map50(),
+            self.map50_95(),
+            "",
+            ""
+        )?;
+        for metrics in self.labelled_classes() {
+            let (f1, f1_conf) = match metrics.best_f1 {
+                Some(point) => (
+                    format!("{:.3}", point.f1),
+                    format!("{:.3}", point.confidence),
+                ),
+                None => ("-".to_string(), "-".to_string()),
+            };
+            writeln!(
+                f,
+                "{:<20}{:>10}{:>10}{:>10.3}{:>10.3}{:>10.3}{:>10.3}{:>10}{:>10}",
+                metrics.class,
+                metrics.num_ground_truth,
+                metrics.num_predictions,
+                metrics.precision,
+                metrics.recall,
+                metrics.ap50(),
+                metrics.ap50_95(),
+                f1,
+                f1_conf
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Accumulates the predictions and ground truth of many images, see the [module documentation](self)
+#[derive(Debug, Clone)]
+pub struct Evaluator {
+    classes: Vec<String>,
+    records: Vec<ClassRecords>,
+    confusion_matrix: ConfusionMatrix,
+    num_images: usize,
+}
+
+impl Evaluator {
+    /// Evaluator for a model predicting `classes`, usually [`Yolo::classes`](crate::Yolo::classes)
+    pub fn new(classes: Vec<String>) -> Self {
+        Evaluator {
+            records: vec![ClassRecords::default(); classes.len()],
+            confusion_matrix: ConfusionMatrix::new(classes.clone()),
+            classes,
+            num_images: 0,
+        }
+    }
+
+    /// Thresholds of the confusion matrix, by default confidence 0.25 and IoU 0.45. Clears the matrix
+    pub fn with_confusion_thresholds(mut self, conf_thresh: f32, iou_thresh: f32) -> Self {
+        self.confusion_matrix = ConfusionMatrix::new(self.classes.clone())
+            .with_conf_thresh(conf_thresh)
+            .with_iou_thresh(iou_thresh);
+        self
+    }
+
+    pub fn classes(&self) -> &[String] {
+        &self.classes
+    }
+
+    pub fn confusion_matrix(&self) -> &ConfusionMatrix {
+        &self.confusion_matrix
+    }
+
+    /// Matches the predictions of an image to its ground truth, by class id
+    pub fn add_image(&mut self, predictions: &[InferenceResult], ground_truth: &[GroundTruth]) {
+        self.num_images += 1;
+        self.confusion_matrix.add_image(predictions, ground_truth);
+        for object in ground_truth {
+            if let Some(records) = self.records.get_mut(object.class_id) {
+                records.num_ground_truth += 1;
+            }
+        }
+
+        let matches = match_predictions(predictions, ground_truth);
+        for (prediction, matched) in predictions.iter().zip(matches) {
+            if let Some(records) = self.records.get_mut(prediction.class_id()) {
+                records
+                    .predictions
+                    .push((prediction.confidence(), matched.map(|gt| gt.is_some())));
+            }
+        }
+    }
+
+    pub fn evaluate(&self) -> EvalReport {
+        let classes = self
+            .classes
+            .iter()
+            .zip(&self.records)
+            .zip(self.pr_curves())
+            .map(|((class, records), pr_curve)| {
+                let mut predictions = records.predictions.clone();
+                predictions.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+                let mut ap = [0.; 10];
+                for (threshold_idx, ap) in ap.iter_mut().enumerate() {
+                    let true_positives = predictions
+                        .iter()
+                        .map(|(_, tp)| tp[threshold_idx])
+                        .collect::<Vec<bool>>();
+                    *ap = average_precision(&true_positives, records.num_ground_truth);
+                }
+
+                let best_f1 = pr_curve.best_f1();
+                ClassMetrics {
+                    class: class.clone(),
+                    num_ground_truth: records.num_ground_truth,
+                    num_predictions: predictions.len(),
+                    ap,
+                    precision: best_f1.map_or(0., |point| point.precision),
+                    recall: best_f1.map_or(0., |point| point.recall),
+                    best_f1,
+                }
+            })
+            .collect();
+
+        EvalReport {
+            num_images: self.num_images,
+            classes,
+        }
+    }
+
+    /// Precision recall curve of every class at IoU 0.5, in class order
+    pub fn pr_curves(&self) -> Vec<PrCurve> {
+        self.classes
+            .iter()
+            .zip(&self.records)
+            .map(|(class, records)| {
+                let mut predictions = records.predictions.clone();
+                predictions.sort_by(|a, b| b.0.total_cmp(&a.0));
+                let true_positives = predictions
+                    .iter()
+                    .map(|(_, tp)| tp[0])
+                    .collect::<Vec<bool>>();
+
+                let curve = precision_recall(&true_positives, records.num_ground_truth);
+                let points = predictions
+                    .iter()
+                    .zip(curve)
+                    .enumerate()
+                    // a threshold keeps every prediction of the same confidence,
+                    // only the last one of each run is a point of the curve
+                    .filter(|(idx, ((confidence, _), _))| {
+                        predictions
+                            .get(idx + 1)
+                            .is_none_or(|(next, _)| next != confidence)
+                    })
+                    .map(|(_, ((confidence, _), (precision, recall)))| {
+                        CurvePoint::new(*confidence, precision, recall)
+                    })
+                    .collect();
+                PrCurve {
+                    class: class.clone(),
+                    num_ground_truth: records.num_ground_truth,
+                    points,
+                }
+            })
+            .collect()
+    }
+
+    pub fn f1_curve(&self) -> F1Curve {
+        F1Curve::from_pr_curves(&self.pr_curves())
+    }
+
+    /// Writes `confusion_matrix`, `pr_curve` and `f1_curve` as CSV and PNG files to `dir`.
+    /// Chart text is only drawn with a `font`
+    pub fn save_plots<P: AsRef<Path>>(&self, dir: P, font: Option<&Font>) -> Result<(), EvalError> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let pr_curves = self.pr_curves();
+        let f1_curve = F1Curve::from_pr_curves(&pr_curves);
+
+        self.confusion_matrix
+            .write_csv(dir.join("confusion_matrix.csv"))?;
+        charts::render_confusion_matrix(&self.confusion_matrix, font)
+            .save_with_format(dir.join("confusion_matrix.png"), image::ImageFormat::Png)?;
+        curves::write_pr_curves_csv(&pr_curves, dir.join("pr_curve.csv"))?;
+        charts::render_pr_curves(&pr_curves, font)
+            .save_with_format(dir.join("pr_curve.png"), image::ImageFormat::Png)?;
+        f1_curve.write_csv(dir.join("f1_curve.csv"))?;
+        charts::render_f1_curve(&f1_curve, font)
+            .save_with_format(dir.join("f1_curve.png"), image::ImageFormat::Png)?;
+        Ok(())
+    }
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f32 {
+    if denominator == 0 {
+        return 0.;
+    }
+    numerator as f32 / denominator as f32
+}
+
+/// IoU of every prediction, by row, with every ground truth object, by column
+pub(crate) fn iou_matrix(
+    predictions: &[InferenceResult],
+    ground_truth: &[GroundTruth],
+) -> Option<Array2<f64>> {
+    if predictions.is_empty() || ground_truth.is_empty() {
+        return None;
+    }
+    let to_ndarray = |rects: Vec<Rect>| {
+        bboxes_to_ndarray(
+            rects
+                .into_iter()
+                .map(|rect| rect_to_xyxy(rect).map(f64::from))
+                .collect(),
+        )
+    };
+    // Only fails on arrays of incompatible shapes, which are built just above
+    vectorized_iou(
+        to_ndarray(predictions.iter().map(InferenceResult::b_box).collect()),
+        to_ndarray(ground_truth.iter().map(|object| object.b_box).collect()),
+    )
+    .ok()
+}
+
+/// For each prediction and IoU threshold, the index of the ground truth object it matches, if any.
+/// Predictions are matched from the most to the least confident, each to the unmatched object
+/// of the same class with the highest IoU
+pub(crate) fn match_predictions(
+    predictions: &[InferenceResult],
+    ground_truth: &[GroundTruth],
+) -> Vec<[Option<usize>; 10]> {
+    let mut matches = vec![[None; 10]; predictions.len()];
+    if predictions.is_empty() || ground_truth.is_empty() {
+        return matches;
+    }
+
+    let Some(iou_matrix) = iou_matrix(predictions, ground_truth) else {
+        return matches;
+    };
+
+    let mut order = (0..predictions.len()).collect::<Vec<usize>>();
+    order.sort_by(|&a, &b| {
+        predictions[b]
+            .confidence()
+            .total_cmp(&predictions[a].confidence())
+    });
+
+    for (threshold_idx, &threshold) in IOU_THRESHOLDS.iter().enumerate() {
+        let mut matched = vec![false; ground_truth.len()];
+        for &pred_idx in &order {
+            let mut best: Option<(usize, f64)> = None;
+            for (gt_idx, object) in ground_truth.iter().enumerate() {
+                if matched[gt_idx] || object.class_id != predictions[pred_idx].class_id() {
+                    continue;
+                }
+                let iou = iou_matrix[[pred_idx, gt_idx]];
+                if iou >= threshold as f64 && best.is_none_or(|(_, best_iou)| iou > best_iou) {
+                    best = Some((gt_idx, iou));
+                }
+            }
+            if let Some((gt_idx, _)) = best {
+                matched[gt_idx] = true;
+                matches[pred_idx][threshold_idx] = Some(gt_idx);
+            }
+        }
+    }
+    matches
+}
+
+/// Cumulative precision and recall after each prediction, sorted by descending confidence
+pub(crate) fn precision_recall(
+    true_positives: &[bool],
+    num_ground_truth: usize,
+) -> Vec<(f32, f32)> {
+    let mut tp = 0;
+    true_positives
+        .iter()
+        .enumerate()
+        .map(|(idx, &is_tp)| {
+            tp += is_tp as usize;
+            (ratio(tp, idx + 1), ratio(tp, num_ground_truth))
+        })
+        .collect()
+}
+
+/// 101 point interpolated average precision of predictions sorted by descending confidence
+fn average_precision(true_positives: &[bool], num_ground_truth: usize) -> f32 {
+    if num_ground_truth == 0 {
+        return 0.;
+    }
+    let mut curve = precision_recall(true_positives, num_ground_truth);
+    // Precision envelope, the best precision at this recall or any higher one
+    for idx in (0..curve.len().saturating_sub(1)).rev() {
+        curve[idx].0 = curve[idx].0.max(curve[idx + 1].0);
+    }
+
+    let sum: f32 = (0..=100)
+        .map(|step| {
+            let recall = step as f32 / 100.;
+            curve
+                .iter()
+                .find(|(_, r)| *r >= recall)
+                .map_or(0., |(precision, _)| *precision)
+        })
+        .sum();
+    sum / 101.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{average_precision, load_yolo_labels, EvalError, Evaluator, GroundTruth};
+    use crate::{ConfThresh, InferenceResult};
+    use imageproc::rect::Rect;
+
+    fn prediction(x: i32, class_id: usize, confidence: f32) -> InferenceResult {
+        InferenceResult::new(Rect::at(x, 0).of_size(10, 10), "", class_id, confidence)
+    }
+
+    fn ground_truth(x: i32, class_id: usize) -> GroundTruth {
+        GroundTruth {
+            b_box: Rect::at(x, 0).of_size(10, 10),
+            class_id,
+            class: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_average_precision() {
+        assert_eq!(average_precision(&[true, true], 2), 1.);
+        assert_eq!(average_precision(&[], 2), 0.);
+        assert_eq!(average_precision(&[false, false], 0), 0.);
+        // half the objects found, with perfect precision
+        assert!((average_precision(&[true], 2) - 51. / 101.).abs() < 1e-6);
+        // the envelope takes the precision of 2/3 at full recall for every recall
+        assert!((average_precision(&[false, true, true], 2) - 2. / 3.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_evaluator() {
+        let classes = vec!["person".to_string(), "car".to_string()];
+        let mut evaluator = Evaluator::new(classes);
+
+        evaluator.add_image(
+            &[
+                // exact match
+                prediction(0, 0, 0.9),
+                // duplicate of the same person, a false positive
+                prediction(0, 0, 0.8),
+                // shifted by 2 pixels, IoU 8/12
+                prediction(52, 1, 0.7),
+            ],
+            &[ground_truth(0, 0), ground_truth(50, 1)],
+        );
+        // a missed car
+        evaluator.add_image(&[], &[ground_truth(0, 1)]);
+
+        let report = evaluator.evaluate();
+        assert_eq!(report.num_images, 2);
+
+        let person = report.class("person").unwrap();
+        assert_eq!((person.num_ground_truth, person.num_predictions), (1, 2));
+        // at the best F1 confidence of 0.9, above the duplicate
+        assert_eq!((person.precision, person.recall), (1., 1.));
+        assert_eq!(person.ap50_95(), 1.);
+
+        let car = report.class("car").unwrap();
+        assert_eq!((car.precision, car.recall), (1., 0.5));
+        assert!((car.ap50() - 51. / 101.).abs() < 1e-6);
+        // matched up to IoU 0.65
+        assert_eq!(car.ap.iter().filter(|ap| **ap > 0.).count(), 4);
+
+        assert!((report.map50() - (1. + 51. / 101.) / 2.).abs() < 1e-6);
+        assert!(report.to_string().starts_with("class"));
+
+        // the duplicate lowers the F1 of persons below 0.9
+        assert_eq!(person.best_conf_thresh(), Some(ConfThresh(0.9)));
+        assert_eq!(evaluator.confusion_matrix().get(Some(0), None), 1);
+        assert_eq!(evaluator.confusion_matrix().get(None, Some(1)), 1);
+    }
+
+    #[test]
+    fn test_load_yolo_labels() {
+        let dir = std::env::temp_dir().join(format!("yolo-rs-eval-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let classes = vec!["person".to_string(), "car".to_string()];
+
+        let path = dir.join("labels.txt");
+        std::fs::write(&path, "1 0.5 0.5 0.2 0.4\n\n0 0.1 0.1 0.2 0.2 0.9\n").unwrap();
+        let labels = load_yolo_labels(&path, 200, 100, &classes).unwrap();
+        assert_eq!(labels[0].b_box, Rect::at(80, 30).of_size(40, 40));
+        assert_eq!(labels[0].class, "car");
+        assert_eq!(labels[1].b_box, Rect::at(0, 0).of_size(40, 20));
+
+        std::fs::write(&path, "1 0.5 0.5\n").unwrap();
+        assert!(matches!(
+            load_yolo_labels(&path, 200, 100, &classes),
+            Err(EvalError::InvalidLabel { line: 1, .. })
+        ));
+        std::fs::write(&path, "2 0.5 0.5 0.2 0.2\n").unwrap();
+        assert!(matches!(
+            load_yolo_labels(&path, 200, 100, &classes),
+            Err(EvalError::UnknownClass(_))
+        ));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,334 @@
+//! PNG charts of the evaluation curves and confusion matrix
+//!
+//! Axis ticks, legends, class names and counts are only drawn when a font is given,
+//! e.g. [`default_font`](crate::utils::default_font) with the `bundled-font` feature.
+
+use image::{imageops, Rgb, RgbImage};
+use imageproc::{
+    drawing::{draw_filled_rect_mut, draw_line_segment_mut, draw_text_mut, text_size},
+    rect::Rect,
+};
+use rusttype::{Font, Scale};
+
+use crate::{
+    eval::{
+        confusion::ConfusionMatrix,
+        curves::{F1Curve, PrCurve},
+    },
+    heatmap::colormap,
+    utils::DEFAULT_PALETTE,
+};
+
+const PLOT_SIZE: (u32, u32) = (560, 420);
+const MARGIN: u32 = 56;
+const LEGEND_WIDTH: u32 = 180;
+const TEXT_SIZE: f32 = 14.;
+const WHITE: Rgb<u8> = Rgb([255, 255, 255]);
+const BLACK: Rgb<u8> = Rgb([0, 0, 0]);
+const GRID: Rgb<u8> = Rgb([220, 220, 220]);
+
+/// A named line of a chart, with its points and width in pixels
+struct Series<'a> {
+    name: &'a str,
+    points: Vec<(f32, f32)>,
+    color: Rgb<u8>,
+    width: u32,
+}
+
+/// Line chart of `series` on [0, 1] x [0, 1], with a legend on the right
+fn line_chart(series: &[Series], x_label: &str, y_label: &str, font: Option<&Font>) -> RgbImage {
+    let legend_width = if font.is_some() { LEGEND_WIDTH } else { 0 };
+    let (plot_width, plot_height) = PLOT_SIZE;
+    let mut chart = RgbImage::from_pixel(
+        MARGIN + plot_width + MARGIN / 2 + legend_width,
+        MARGIN / 2 + plot_height + MARGIN,
+        WHITE,
+    );
+    let (left, bottom) = (MARGIN as f32, (MARGIN / 2 + plot_height) as f32);
+    let to_pixel = |(x, y): (f32, f32)| {
+        (
+            left + x.clamp(0., 1.) * plot_width as f32,
+            bottom - y.clamp(0., 1.) * plot_height as f32,
+        )
+    };
+
+    for tick in 0..=5 {
+        let value = tick as f32 / 5.;
+        draw_line_segment_mut(
+            &mut chart,
+            to_pixel((value, 0.)),
+            to_pixel((value, 1.)),
+            GRID,
+        );
+        draw_line_segment_mut(
+            &mut chart,
+            to_pixel((0., value)),
+            to_pixel((1., value)),
+            GRID,
+        );
+        if let Some(font) = font {
+            let label = format!("{:.1}", value);
+            let (label_width, label_height) = text_size(Scale::uniform(TEXT_SIZE), font, &label);
+            let (x, y) = to_pixel((value, 0.));
+            draw_text(
+                &mut chart,
+                font,
+                &label,
+                x as i32 - label_width / 2,
+                y as i32 + 4,
+            );
+            let (x, y) = to_pixel((0., value));
+            draw_text(
+                &mut chart,
+                font,
+                &label,
+                x as i32 - label_width - 6,
+                y as i32 - label_height / 2,
+            );
+        }
+    }
+    draw_line_segment_mut(&mut chart, to_pixel((0., 0.)), to_pixel((1., 0.)), BLACK);
+    draw_line_segment_mut(&mut chart, to_pixel((0., 0.)), to_pixel((0., 1.)), BLACK);
+
+    for line in series {
+        for segment in line.points.windows(2) {
+            let (start, end) = (to_pixel(segment[0]), to_pixel(segment[1]));
+            for offset in 0..line.width {
+                let offset = offset as f32 - (line.width - 1) as f32 / 2.;
+                draw_line_segment_mut(
+                    &mut chart,
+                    (start.0, start.1 + offset),
+                    (end.0, end.1 + offset),
+                    line.color,
+                );
+            }
+        }
+    }
+
+    if let Some(font) = font {
+        let (x_label_width, _) = text_size(Scale::uniform(TEXT_SIZE), font, x_label);
+        draw_text(
+            &mut chart,
+            font,
+            x_label,
+            (left + plot_width as f32 / 2.) as i32 - x_label_width / 2,
+            bottom as i32 + 24,
+        );
+        draw_text(&mut chart, font, y_label, 4, 4);
+
+        // legend entries that do not fit in the height of the chart are left out
+        let legend_left = (MARGIN + plot_width + MARGIN / 2) as i32;
+        let row_height = TEXT_SIZE as i32 + 4;
+        for (idx, line) in series.iter().enumerate() {
+            let y = (MARGIN / 2) as i32 + idx as i32 * row_height;
+            if y + row_height > bottom as i32 {
+                break;
+            }
+            draw_filled_rect_mut(
+                &mut chart,
+                Rect::at(legend_left, y + row_height / 2 - 2).of_size(16, 4),
+                line.color,
+            );
+            draw_text(&mut chart, font, line.name, legend_left + 22, y);
+        }
+    }
+    chart
+}
+
+fn draw_text(image: &mut RgbImage, font: &Font, text: &str, x: i32, y: i32) {
+    draw_text_mut(image, BLACK, x, y, Scale::uniform(TEXT_SIZE), font, text);
+}
+
+/// Precision against recall of the classes with ground truth
+pub fn render_pr_curves(curves: &[PrCurve], font: Option<&Font>) -> RgbImage {
+    let series = curves
+        .iter()
+        .enumerate()
+        .filter(|(_, curve)| curve.num_ground_truth > 0)
+        .map(|(class_id, curve)| Series {
+            name: &curve.class,
+            points: curve
+                .points
+                .iter()
+                .map(|point| (point.recall, point.precision))
+                .collect(),
+            color: DEFAULT_PALETTE[class_id % DEFAULT_PALETTE.len()],
+            width: 1,
+        })
+        .collect::<Vec<Series>>();
+    line_chart(&series, "Recall", "Precision", font)
+}
+
+/// F1 of each class against the confidence threshold, with their mean in black
+pub fn render_f1_curve(curve: &F1Curve, font: Option<&Font>) -> RgbImage {
+    let mut series = curve
+        .classes
+        .iter()
+        .enumerate()
+        .map(|(idx, (class, f1))| Series {
+            name: class,
+            points: curve
+                .confidences
+                .iter()
+                .copied()
+                .zip(f1.iter().copied())
+                .collect(),
+            color: DEFAULT_PALETTE[idx % DEFAULT_PALETTE.len()],
+            width: 1,
+        })
+        .collect::<Vec<Series>>();
+    // drawn last, over the classes
+    series.push(Series {
+        name: "all",
+        points: curve
+            .confidences
+            .iter()
+            .copied()
+            .zip(curve.mean())
+            .collect(),
+        color: BLACK,
+        width: 3,
+    });
+    line_chart(&series, "Confidence", "F1", font)
+}
+
+/// Confusion matrix with each column, a true class, normalized by its number of objects,
+/// colored with [`colormap`]
+pub fn render_confusion_matrix(matrix: &ConfusionMatrix, font: Option<&Font>) -> RgbImage {
+    let size = matrix.size();
+    let cell = (PLOT_SIZE.1 / size as u32).clamp(6, 40);
+    let names = matrix
+        .classes()
+        .iter()
+        .map(String::as_str)
+        .chain(["background"])
+        .collect::<Vec<&str>>();
+
+    let label_margin = match font {
+        Some(font) => names
+            .iter()
+            .map(|name| text_size(Scale::uniform(TEXT_SIZE), font, name).0 as u32 + 8)
+            .max()
+            .unwrap_or(0),
+        None => 0,
+    };
+    let offset = label_margin + MARGIN / 4;
+    let mut chart = RgbImage::from_pixel(
+        offset + size as u32 * cell + MARGIN / 4,
+        offset + size as u32 * cell + MARGIN / 4,
+        WHITE,
+    );
+
+    let column_totals = (0..size)
+        .map(|col| {
+            (0..size)
+                .map(|row| matrix.get(index(row, size), index(col, size)))
+                .sum::<usize>()
+        })
+        .collect::<Vec<usize>>();
+
+    for row in 0..size {
+        for (col, &total) in column_totals.iter().enumerate() {
+            let count = matrix.get(index(row, size), index(col, size));
+            let value = if total > 0 {
+                count as f32 / total as f32
+            } else {
+                0.
+            };
+            let (x, y) = (offset + col as u32 * cell, offset + row as u32 * cell);
+            draw_filled_rect_mut(
+                &mut chart,
+                Rect::at(x as i32, y as i32).of_size(cell, cell),
+                colormap(value),
+            );
+
+            if let Some(font) = font {
+                let label = count.to_string();
+                let (label_width, label_height) =
+                    text_size(Scale::uniform(TEXT_SIZE), font, &label);
+                if count > 0 && label_width as u32 + 4 <= cell {
+                    // dark text on the bright end of the colormap
+                    let color = if (0.4..0.9).contains(&value) {
+                        BLACK
+                    } else {
+                        WHITE
+                    };
+                    draw_text_mut(
+                        &mut chart,
+                        color,
+                        (x + cell / 2) as i32 - label_width / 2,
+                        (y + cell / 2) as i32 - label_height / 2,
+                        Scale::uniform(TEXT_SIZE),
+                        font,
+                        &label,
+                    );
+                }
+            }
+        }
+    }
+
+    if let Some(font) = font {
+        for (idx, name) in names.iter().enumerate() {
+            let (name_width, name_height) = text_size(Scale::uniform(TEXT_SIZE), font, name);
+            let center = (offset + idx as u32 * cell + cell / 2) as i32;
+            // predicted classes on the left
+            draw_text(
+                &mut chart,
+                font,
+                name,
+                offset as i32 - name_width - 4,
+                center - name_height / 2,
+            );
+
+            // true classes on the top, written bottom to top
+            let mut label = RgbImage::from_pixel(
+                name_width.max(1) as u32,
+                (name_height + 2).max(1) as u32,
+                WHITE,
+            );
+            draw_text(&mut label, font, name, 0, 0);
+            let label = imageops::rotate270(&label);
+            imageops::overlay(
+                &mut chart,
+                &label,
+                (center - label.width() as i32 / 2) as i64,
+                (offset - label.height() - 4) as i64,
+            );
+        }
+    }
+    chart
+}
+
+/// Class id of a row or column of the matrix, `None` for the background
+fn index(idx: usize, size: usize) -> Option<usize> {
+    (idx + 1 < size).then_some(idx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_confusion_matrix, render_f1_curve};
+    use crate::eval::{
+        confusion::ConfusionMatrix,
+        curves::{CurvePoint, F1Curve, PrCurve},
+    };
+    use image::Rgb;
+
+    #[test]
+    fn test_render_charts() {
+        let curve = PrCurve {
+            class: "cat".to_string(),
+            num_ground_truth: 1,
+            points: vec![CurvePoint::new(0.5, 1., 1.)],
+        };
+        let chart = render_f1_curve(&F1Curve::from_pr_curves(&[curve]), None);
+        assert_eq!(chart.dimensions(), (644, 504));
+        // the mean F1 is 1 up to the confidence 0.5, at the top of the plot
+        assert_eq!(chart.get_pixel(56 + 100, 28), &Rgb([0, 0, 0]));
+
+        let matrix = ConfusionMatrix::new(vec!["cat".to_string()]);
+        let chart = render_confusion_matrix(&matrix, None);
+        // two cells of 40 pixels, all black without any count
+        assert_eq!(chart.dimensions(), (108, 108));
+        assert_eq!(chart.get_pixel(50, 50), &Rgb([0, 0, 0]));
+    }
+}
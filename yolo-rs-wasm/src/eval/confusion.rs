@@ -0,0 +1,204 @@
+//! Confusion matrix of the predicted and true classes
+//!
+//! Predictions above a confidence threshold are matched to ground truth objects of any class,
+//! by decreasing IoU. Unmatched predictions count as background false positives,
+//! unmatched ground truth objects as misses, in the background row and column.
+
+use std::{fs, io, path::Path};
+
+use crate::{
+    eval::{iou_matrix, GroundTruth},
+    InferenceResult,
+};
+
+/// Matrix of predicted classes, by row, against true classes, by column,
+/// with an extra background row and column, see the [module documentation](self)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfusionMatrix {
+    classes: Vec<String>,
+    conf_thresh: f32,
+    iou_thresh: f32,
+    counts: Vec<usize>,
+}
+
+impl ConfusionMatrix {
+    /// Matrix of `classes`, with the ultralytics thresholds, confidence 0.25 and IoU 0.45
+    pub fn new(classes: Vec<String>) -> Self {
+        let size = classes.len() + 1;
+        ConfusionMatrix {
+            classes,
+            conf_thresh: 0.25,
+            iou_thresh: 0.45,
+            counts: vec![0; size * size],
+        }
+    }
+
+    /// Predictions below this confidence are ignored
+    pub fn with_conf_thresh(mut self, conf_thresh: f32) -> Self {
+        self.conf_thresh = conf_thresh;
+        self
+    }
+
+    /// Minimum IoU of a prediction and a ground truth object to match them
+    pub fn with_iou_thresh(mut self, iou_thresh: f32) -> Self {
+        self.iou_thresh = iou_thresh;
+        self
+    }
+
+    pub fn classes(&self) -> &[String] {
+        &self.classes
+    }
+
+    /// Number of rows and columns, the number of classes plus the background
+    pub fn size(&self) -> usize {
+        self.classes.len() + 1
+    }
+
+    /// Count of objects of class `actual` predicted as class `predicted`, `None` is the background:
+    /// `get(Some(c), None)` counts the false positives of class `c`, `get(None, Some(c))` its misses
+    pub fn get(&self, predicted: Option<usize>, actual: Option<usize>) -> usize {
+        let background = self.classes.len();
+        let row = predicted.unwrap_or(background).min(background);
+        let col = actual.unwrap_or(background).min(background);
+        self.counts[row * self.size() + col]
+    }
+
+    fn increment(&mut self, predicted: Option<usize>, actual: Option<usize>) {
+        let background = self.classes.len();
+        let row = predicted.unwrap_or(background);
+        let col = actual.unwrap_or(background);
+        let size = self.size();
+        self.counts[row * size + col] += 1;
+    }
+
+    /// Adds the predictions and ground truth of an image.
+    /// Predictions and objects with a class id outside the class list are ignored
+    pub fn add_image(&mut self, predictions: &[InferenceResult], ground_truth: &[GroundTruth]) {
+        let num_classes = self.classes.len();
+        let predictions = predictions
+            .iter()
+            .filter(|result| {
+                result.confidence() >= self.conf_thresh && result.class_id() < num_classes
+            })
+            .cloned()
+            .collect::<Vec<InferenceResult>>();
+        let ground_truth = ground_truth
+            .iter()
+            .filter(|object| object.class_id < num_classes)
+            .cloned()
+            .collect::<Vec<GroundTruth>>();
+
+        let mut pred_matched = vec![false; predictions.len()];
+        let mut gt_matched = vec![false; ground_truth.len()];
+        if let Some(ious) = iou_matrix(&predictions, &ground_truth) {
+            let mut candidates = ious
+                .indexed_iter()
+                .filter(|(_, &iou)| iou >= self.iou_thresh as f64)
+                .map(|((pred_idx, gt_idx), &iou)| (pred_idx, gt_idx, iou))
+                .collect::<Vec<(usize, usize, f64)>>();
+            candidates.sort_by(|a, b| b.2.total_cmp(&a.2));
+
+            for (pred_idx, gt_idx, _) in candidates {
+                if pred_matched[pred_idx] || gt_matched[gt_idx] {
+                    continue;
+                }
+                pred_matched[pred_idx] = true;
+                gt_matched[gt_idx] = true;
+                self.increment(
+                    Some(predictions[pred_idx].class_id()),
+                    Some(ground_truth[gt_idx].class_id),
+                );
+            }
+        }
+
+        for (prediction, _) in predictions.iter().zip(pred_matched).filter(|(_, m)| !m) {
+            self.increment(Some(prediction.class_id()), None);
+        }
+        for (object, _) in ground_truth.iter().zip(gt_matched).filter(|(_, m)| !m) {
+            self.increment(None, Some(object.class_id));
+        }
+    }
+
+    /// Matrix as CSV, with a header row of the true classes and a first column of the predicted ones
+    pub fn to_csv(&self) -> String {
+        let names = self
+            .classes
+            .iter()
+            .map(|class| csv_field(class))
+            .chain(["background".to_string()])
+            .collect::<Vec<String>>();
+
+        let mut csv = format!("predicted\\true,{}\n", names.join(","));
+        for (row, name) in names.iter().enumerate() {
+            let counts = self.counts[row * self.size()..(row + 1) * self.size()]
+                .iter()
+                .map(usize::to_string)
+                .collect::<Vec<String>>();
+            csv.push_str(&format!("{},{}\n", name, counts.join(",")));
+        }
+        csv
+    }
+
+    pub fn write_csv<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_csv())
+    }
+}
+
+/// Quotes a CSV field containing a separator or a quote
+pub(crate) fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConfusionMatrix;
+    use crate::{eval::GroundTruth, InferenceResult};
+    use imageproc::rect::Rect;
+
+    fn prediction(x: i32, class_id: usize, confidence: f32) -> InferenceResult {
+        InferenceResult::new(Rect::at(x, 0).of_size(10, 10), "", class_id, confidence)
+    }
+
+    fn ground_truth(x: i32, class_id: usize) -> GroundTruth {
+        GroundTruth {
+            b_box: Rect::at(x, 0).of_size(10, 10),
+            class_id,
+            class: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_confusion_matrix() {
+        let mut matrix = ConfusionMatrix::new(vec!["cat".to_string(), "dog, large".to_string()]);
+        matrix.add_image(
+            &[
+                // correct
+                prediction(0, 0, 0.9),
+                // a dog predicted as a cat
+                prediction(20, 0, 0.8),
+                // nothing there
+                prediction(40, 1, 0.7),
+                // below the confidence threshold
+                prediction(60, 1, 0.1),
+            ],
+            &[ground_truth(0, 0), ground_truth(21, 1), ground_truth(60, 1)],
+        );
+
+        assert_eq!(matrix.get(Some(0), Some(0)), 1);
+        assert_eq!(matrix.get(Some(0), Some(1)), 1);
+        assert_eq!(matrix.get(Some(1), None), 1);
+        assert_eq!(matrix.get(None, Some(1)), 1);
+        assert_eq!(matrix.get(Some(1), Some(1)), 0);
+        assert_eq!(
+            matrix.to_csv(),
+            "predicted\\true,cat,\"dog, large\",background\n\
+             cat,1,1,0\n\
+             \"dog, large\",0,0,1\n\
+             background,0,1,0\n"
+        );
+    }
+}
@@ -0,0 +1,201 @@
+//! Precision recall and F1 confidence curves, at IoU 0.5
+//!
+//! The confidence maximizing F1 is a good [`ConfThresh`] for a model on a dataset:
+//! per class with [`PrCurve::best_f1`], or for every class at once with [`F1Curve::best`].
+
+use std::{fs, io, path::Path};
+
+use crate::{eval::confusion::csv_field, ConfThresh};
+
+/// Number of confidences, evenly spaced from 0 to 1, at which the [`F1Curve`] is evaluated
+pub const F1_CURVE_POINTS: usize = 101;
+
+/// Precision, recall and F1 of the predictions at or above a confidence
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CurvePoint {
+    pub confidence: f32,
+    pub precision: f32,
+    pub recall: f32,
+    pub f1: f32,
+}
+
+impl CurvePoint {
+    pub(crate) fn new(confidence: f32, precision: f32, recall: f32) -> Self {
+        let f1 = if precision + recall > 0. {
+            2. * precision * recall / (precision + recall)
+        } else {
+            0.
+        };
+        CurvePoint {
+            confidence,
+            precision,
+            recall,
+            f1,
+        }
+    }
+}
+
+/// Precision recall curve of a class, with a point per prediction from the most to the least confident
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrCurve {
+    pub class: String,
+    pub num_ground_truth: usize,
+    pub points: Vec<CurvePoint>,
+}
+
+impl PrCurve {
+    /// Point with the highest F1, the most confident one on ties,
+    /// `None` without any true positive
+    pub fn best_f1(&self) -> Option<CurvePoint> {
+        self.points.iter().filter(|point| point.f1 > 0.).fold(
+            None,
+            |best: Option<CurvePoint>, point| match best {
+                Some(best) if best.f1 >= point.f1 => Some(best),
+                _ => Some(*point),
+            },
+        )
+    }
+
+    /// Confidence threshold maximizing the F1 of the class
+    pub fn best_conf_thresh(&self) -> Option<ConfThresh> {
+        self.best_f1().map(|point| ConfThresh(point.confidence))
+    }
+}
+
+/// Writes the curves of every class as CSV, with a `class,confidence,precision,recall,f1` row per point
+pub fn write_pr_curves_csv<P: AsRef<Path>>(curves: &[PrCurve], path: P) -> io::Result<()> {
+    let mut csv = "class,confidence,precision,recall,f1\n".to_string();
+    for curve in curves {
+        for point in &curve.points {
+            csv.push_str(&format!(
+                "{},{},{},{},{}\n",
+                csv_field(&curve.class),
+                point.confidence,
+                point.precision,
+                point.recall,
+                point.f1
+            ));
+        }
+    }
+    fs::write(path, csv)
+}
+
+/// F1 of every class against the confidence threshold
+#[derive(Debug, Clone, PartialEq)]
+pub struct F1Curve {
+    /// [`F1_CURVE_POINTS`] confidences from 0 to 1
+    pub confidences: Vec<f32>,
+    /// Class names and F1 at each confidence, for the classes with ground truth
+    pub classes: Vec<(String, Vec<f32>)>,
+}
+
+impl F1Curve {
+    /// F1 curve of the classes with ground truth, sampled from their precision recall curves
+    pub fn from_pr_curves(curves: &[PrCurve]) -> Self {
+        let confidences = (0..F1_CURVE_POINTS)
+            .map(|idx| idx as f32 / (F1_CURVE_POINTS - 1) as f32)
+            .collect::<Vec<f32>>();
+        let classes = curves
+            .iter()
+            .filter(|curve| curve.num_ground_truth > 0)
+            .map(|curve| {
+                let f1 = confidences
+                    .iter()
+                    .map(|&confidence| {
+                        // the points are sorted by decreasing confidence,
+                        // the last one above the threshold counts every prediction kept
+                        curve
+                            .points
+                            .iter()
+                            .take_while(|point| point.confidence >= confidence)
+                            .last()
+                            .map_or(0., |point| point.f1)
+                    })
+                    .collect();
+                (curve.class.clone(), f1)
+            })
+            .collect();
+        F1Curve {
+            confidences,
+            classes,
+        }
+    }
+
+    /// Mean F1 over the classes at each confidence
+    pub fn mean(&self) -> Vec<f32> {
+        let mut mean = vec![0.; self.confidences.len()];
+        for (_, f1) in &self.classes {
+            for (mean, f1) in mean.iter_mut().zip(f1) {
+                *mean += f1 / self.classes.len() as f32;
+            }
+        }
+        mean
+    }
+
+    /// Confidence and value of the highest mean F1, the highest confidence on ties,
+    /// `None` without any true positive
+    pub fn best(&self) -> Option<(ConfThresh, f32)> {
+        self.confidences
+            .iter()
+            .zip(self.mean())
+            .filter(|(_, f1)| *f1 > 0.)
+            .fold(
+                None,
+                |best: Option<(ConfThresh, f32)>, (&confidence, f1)| match best {
+                    Some(best) if best.1 > f1 => Some(best),
+                    _ => Some((ConfThresh(confidence), f1)),
+                },
+            )
+    }
+
+    /// Writes the curve as CSV, with a column per class and one for their mean
+    pub fn write_csv<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let header = self
+            .classes
+            .iter()
+            .map(|(class, _)| csv_field(class))
+            .chain(["all".to_string()])
+            .collect::<Vec<String>>();
+        let mut csv = format!("confidence,{}\n", header.join(","));
+        let mean = self.mean();
+        for (idx, confidence) in self.confidences.iter().enumerate() {
+            let values = self
+                .classes
+                .iter()
+                .map(|(_, f1)| f1[idx].to_string())
+                .chain([mean[idx].to_string()])
+                .collect::<Vec<String>>();
+            csv.push_str(&format!("{},{}\n", confidence, values.join(",")));
+        }
+        fs::write(path, csv)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CurvePoint, F1Curve, PrCurve};
+    use crate::ConfThresh;
+
+    #[test]
+    fn test_best_f1() {
+        let curve = PrCurve {
+            class: "cat".to_string(),
+            num_ground_truth: 2,
+            points: vec![
+                CurvePoint::new(0.9, 1., 0.5),
+                CurvePoint::new(0.6, 1., 1.),
+                CurvePoint::new(0.3, 2. / 3., 1.),
+            ],
+        };
+        assert_eq!(curve.best_conf_thresh(), Some(ConfThresh(0.6)));
+        assert_eq!(curve.points[0].f1, 2. / 3.);
+
+        let f1 = F1Curve::from_pr_curves(&[curve]);
+        assert_eq!(f1.confidences.len(), 101);
+        assert!((f1.classes[0].1[0] - 0.8).abs() < 1e-6);
+        assert_eq!(f1.classes[0].1[50], 1.);
+        assert_eq!(f1.classes[0].1[95], 0.);
+        // the highest confidence with F1 1
+        assert_eq!(f1.best(), Some((ConfThresh(0.6), 1.)));
+    }
+}
@@ -0,0 +1,50 @@
+//! Exporting inference results to files, e.g. to build datasets from the output of a model
+//!
+//! - [`crops`] cuts each detection out of its image, e.g. for a second stage classifier
+//! - [`coco`] writes the COCO detection results format, for pycocotools based evaluation
+//! - [`yolo_labels`] writes label files in the YOLO training format, to pseudo-label a dataset
+//! - [`voc`] and [`label_studio`] write pre-labels for review in CVAT and Label Studio
+
+pub mod coco;
+pub mod crops;
+pub mod label_studio;
+pub mod voc;
+pub mod yolo_labels;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ExportError {
+    #[error("error writing export file")]
+    FileError(#[from] std::io::Error),
+
+    #[error("error encoding image")]
+    ImageError(#[from] image::ImageError),
+
+    #[error("error reading or writing JSON")]
+    Json(#[from] serde_json::Error),
+
+    #[error("error writing YAML")]
+    Yaml(#[from] serde_yaml::Error),
+
+    #[error("class {0} has no category id")]
+    UnknownClass(String),
+
+    #[error("{0} is already the label file of another image")]
+    LabelCollision(std::path::PathBuf),
+
+    #[error("error running inference")]
+    Inference(#[from] crate::YoloRuntimeError),
+}
+
+/// Replaces the characters that are not safe in a file name, such as path separators.
+/// Names made only of dots, such as `..`, would point outside the directory and are replaced too
+pub(crate) fn sanitize_file_name(name: &str) -> String {
+    if name.chars().all(|c| c == '.') {
+        return "_".repeat(name.len().max(1));
+    }
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect()
+}
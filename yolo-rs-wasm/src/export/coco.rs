@@ -0,0 +1,188 @@
+//! Detection results in the COCO format, as read by pycocotools' `COCO.loadRes`
+//!
+//! ```rust,ignore
+//! let image_ids = image_ids_from_annotations("instances_val2017.json")?;
+//! let mut writer = CocoResultsWriter::coco80(yolo.classes());
+//! for (file_name, image_id) in &image_ids {
+//!     let results = yolo.infer_file(images_dir.join(file_name), &conf_thresh, &iou_thresh)?;
+//!     writer.add(*image_id, &results)?;
+//! }
+//! writer.write("detections.json")?;
+//! ```
+
+use std::{collections::HashMap, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use super::ExportError;
+use crate::InferenceResult;
+
+/// COCO category ids of the 80 classes of the models trained on COCO, in class order.
+/// The original dataset numbers its categories from 1 to 90, with gaps
+pub const COCO80_CATEGORY_IDS: [u64; 80] = [
+    1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 27, 28,
+    31, 32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 46, 47, 48, 49, 50, 51, 52, 53, 54, 55,
+    56, 57, 58, 59, 60, 61, 62, 63, 64, 65, 67, 70, 72, 73, 74, 75, 76, 77, 78, 79, 80, 81, 82, 84,
+    85, 86, 87, 88, 89, 90,
+];
+
+/// A single detection of the COCO results format
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CocoDetection {
+    pub image_id: u64,
+    pub category_id: u64,
+    /// [x, y, width, height] of the box, from its top left corner
+    pub bbox: [f32; 4],
+    pub score: f32,
+}
+
+/// Collects the detections of many images and writes them as a COCO results JSON array
+#[derive(Debug, Clone, Default)]
+pub struct CocoResultsWriter {
+    category_ids: HashMap<String, u64>,
+    detections: Vec<CocoDetection>,
+}
+
+impl CocoResultsWriter {
+    /// Category ids numbered from 1 in the order of `classes`
+    pub fn new(classes: &[String]) -> Self {
+        Self::with_category_ids(
+            classes
+                .iter()
+                .enumerate()
+                .map(|(idx, class)| (class.clone(), idx as u64 + 1)),
+        )
+    }
+
+    /// Category ids of the original COCO dataset, for models trained on its 80 classes.
+    /// Classes beyond the 80th are numbered after the last COCO category
+    pub fn coco80(classes: &[String]) -> Self {
+        Self::with_category_ids(classes.iter().enumerate().map(|(idx, class)| {
+            let category_id = COCO80_CATEGORY_IDS
+                .get(idx)
+                .copied()
+                .unwrap_or(idx as u64 + 11);
+            (class.clone(), category_id)
+        }))
+    }
+
+    /// Explicit category id of each class name
+    pub fn with_category_ids<S: Into<String>>(
+        category_ids: impl IntoIterator<Item = (S, u64)>,
+    ) -> Self {
+        CocoResultsWriter {
+            category_ids: category_ids
+                .into_iter()
+                .map(|(class, id)| (class.into(), id))
+                .collect(),
+            detections: Vec::new(),
+        }
+    }
+
+    pub fn category_id(&self, class: &str) -> Option<u64> {
+        self.category_ids.get(class).copied()
+    }
+
+    /// Adds the detections of the image `image_id`,
+    /// fails without adding any of them if a class has no category id
+    pub fn add(&mut self, image_id: u64, results: &[InferenceResult]) -> Result<(), ExportError> {
+        let detections = results
+            .iter()
+            .map(|result| {
+                let category_id = self
+                    .category_id(result.class())
+                    .ok_or_else(|| ExportError::UnknownClass(result.class().to_string()))?;
+                let b_box = result.b_box();
+                Ok(CocoDetection {
+                    image_id,
+                    category_id,
+                    bbox: [
+                        b_box.left() as f32,
+                        b_box.top() as f32,
+                        b_box.width() as f32,
+                        b_box.height() as f32,
+                    ],
+                    score: result.confidence(),
+                })
+            })
+            .collect::<Result<Vec<CocoDetection>, ExportError>>()?;
+        self.detections.extend(detections);
+        Ok(())
+    }
+
+    pub fn detections(&self) -> &[CocoDetection] {
+        &self.detections
+    }
+
+    pub fn to_json(&self) -> Result<String, ExportError> {
+        Ok(serde_json::to_string(&self.detections)?)
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), ExportError> {
+        fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct CocoAnnotations {
+    images: Vec<CocoImage>,
+}
+
+#[derive(Deserialize)]
+struct CocoImage {
+    id: u64,
+    file_name: String,
+}
+
+/// Image id of each file name of a COCO annotations file, such as `instances_val2017.json`,
+/// so that results can be evaluated against its ground truth
+pub fn image_ids_from_annotations<P: AsRef<Path>>(
+    path: P,
+) -> Result<HashMap<String, u64>, ExportError> {
+    let annotations: CocoAnnotations = serde_json::from_slice(&fs::read(path)?)?;
+    Ok(annotations
+        .images
+        .into_iter()
+        .map(|image| (image.file_name, image.id))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CocoResultsWriter;
+    use crate::{export::ExportError, InferenceResult};
+    use imageproc::rect::Rect;
+
+    fn detection(class: &str, confidence: f32) -> InferenceResult {
+        InferenceResult::new(Rect::at(10, 20).of_size(30, 40), class, 0, confidence)
+    }
+
+    #[test]
+    fn test_coco_results() {
+        let classes = ["person", "bicycle", "car"].map(String::from).to_vec();
+        let mut writer = CocoResultsWriter::new(&classes);
+        writer
+            .add(42, &[detection("car", 0.5), detection("person", 0.25)])
+            .unwrap();
+        assert_eq!(
+            writer.to_json().unwrap(),
+            r#"[{"image_id":42,"category_id":3,"bbox":[10.0,20.0,30.0,40.0],"score":0.5},{"image_id":42,"category_id":1,"bbox":[10.0,20.0,30.0,40.0],"score":0.25}]"#
+        );
+
+        assert!(matches!(
+            writer.add(43, &[detection("person", 0.5), detection("dog", 0.5)]),
+            Err(ExportError::UnknownClass(class)) if class == "dog"
+        ));
+        assert_eq!(writer.detections().len(), 2);
+    }
+
+    #[test]
+    fn test_coco80_category_ids() {
+        let classes = (0..81).map(|idx| idx.to_string()).collect::<Vec<String>>();
+        let writer = CocoResultsWriter::coco80(&classes);
+        assert_eq!(writer.category_id("11"), Some(13));
+        assert_eq!(writer.category_id("79"), Some(90));
+        assert_eq!(writer.category_id("80"), Some(91));
+    }
+}
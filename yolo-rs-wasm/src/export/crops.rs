@@ -0,0 +1,244 @@
+//! Crops of every detection, saved in one directory per class
+//!
+//! ```rust,ignore
+//! let results = yolo.infer_image(&conf_thresh, &iou_thresh, &image)?;
+//! let crops = export_crops(&image, &results, &CropOptions::default(), "./crops", "street_0001")?;
+//! // ./crops/person/street_0001_0000.png, ./crops/car/street_0001_0001.png, ...
+//! ```
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use image::{imageops, RgbImage};
+use imageproc::rect::Rect;
+
+use super::{sanitize_file_name, ExportError};
+use crate::InferenceResult;
+
+/// How detections are cropped
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CropOptions {
+    /// Margin added on each side, as a fraction of the box width and height
+    pub padding: f32,
+    /// Grow the shorter side of the crop to make it square
+    pub square: bool,
+    /// Resize the crop to (width, height)
+    pub resize: Option<(u32, u32)>,
+}
+
+impl Default for CropOptions {
+    fn default() -> Self {
+        CropOptions {
+            padding: 0.0,
+            square: false,
+            resize: None,
+        }
+    }
+}
+
+/// Image of a single detection
+#[derive(Debug, Clone)]
+pub struct Crop {
+    pub image: RgbImage,
+    /// Region of the source image that was cropped, after padding and clamping to the image
+    pub source_rect: Rect,
+    /// Index of the detection in the results
+    pub index: usize,
+    pub class: String,
+    pub class_id: usize,
+    pub confidence: f32,
+}
+
+impl Crop {
+    /// Path of the crop within `output_dir`: `<class>/<stem>_<index>.png`
+    pub fn path<P: AsRef<Path>>(&self, output_dir: P, stem: &str) -> PathBuf {
+        output_dir
+            .as_ref()
+            .join(sanitize_file_name(&self.class))
+            .join(format!(
+                "{}_{:04}.png",
+                sanitize_file_name(stem),
+                self.index
+            ))
+    }
+}
+
+/// Region to crop for `b_box`, `None` if it is outside the image
+fn crop_rect(
+    b_box: Rect,
+    image_width: u32,
+    image_height: u32,
+    options: &CropOptions,
+) -> Option<Rect> {
+    let padding = options.padding.max(0.0);
+    let (mut width, mut height) = (
+        b_box.width() as f32 * (1.0 + 2.0 * padding),
+        b_box.height() as f32 * (1.0 + 2.0 * padding),
+    );
+    if options.square {
+        width = width.max(height);
+        height = width;
+    }
+    let center_x = b_box.left() as f32 + b_box.width() as f32 / 2.0;
+    let center_y = b_box.top() as f32 + b_box.height() as f32 / 2.0;
+
+    let left = (center_x - width / 2.0).round().max(0.0) as u32;
+    let top = (center_y - height / 2.0).round().max(0.0) as u32;
+    let right = ((center_x + width / 2.0).round().max(0.0) as u32).min(image_width);
+    let bottom = ((center_y + height / 2.0).round().max(0.0) as u32).min(image_height);
+    if left >= right || top >= bottom {
+        return None;
+    }
+    Some(Rect::at(left as i32, top as i32).of_size(right - left, bottom - top))
+}
+
+/// Crops every detection out of `image`, detections entirely outside the image are skipped
+pub fn crop_detections(
+    image: &RgbImage,
+    results: &[InferenceResult],
+    options: &CropOptions,
+) -> Vec<Crop> {
+    results
+        .iter()
+        .enumerate()
+        .filter_map(|(index, result)| {
+            let rect = crop_rect(result.b_box(), image.width(), image.height(), options)?;
+            let mut crop = imageops::crop_imm(
+                image,
+                rect.left() as u32,
+                rect.top() as u32,
+                rect.width(),
+                rect.height(),
+            )
+            .to_image();
+            if let Some((width, height)) = options.resize {
+                crop = imageops::resize(
+                    &crop,
+                    width.max(1),
+                    height.max(1),
+                    imageops::FilterType::Triangle,
+                );
+            }
+            Some(Crop {
+                image: crop,
+                source_rect: rect,
+                index,
+                class: result.class().to_string(),
+                class_id: result.class_id(),
+                confidence: result.confidence(),
+            })
+        })
+        .collect()
+}
+
+/// Saves crops as PNG files to `<output_dir>/<class>/<stem>_<index>.png`,
+/// `stem` identifies the source image, e.g. its file name or `frame_000042`.
+/// Returns the path of every file written
+pub fn save_crops<P: AsRef<Path>>(
+    crops: &[Crop],
+    output_dir: P,
+    stem: &str,
+) -> Result<Vec<PathBuf>, ExportError> {
+    let mut paths = Vec::with_capacity(crops.len());
+    for crop in crops {
+        let path = crop.path(output_dir.as_ref(), stem);
+        if let Some(class_dir) = path.parent() {
+            fs::create_dir_all(class_dir)?;
+        }
+        crop.image
+            .save_with_format(&path, image::ImageFormat::Png)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Crops every detection out of `image` and saves the crops with [`save_crops`], returns the crops
+pub fn export_crops<P: AsRef<Path>>(
+    image: &RgbImage,
+    results: &[InferenceResult],
+    options: &CropOptions,
+    output_dir: P,
+    stem: &str,
+) -> Result<Vec<Crop>, ExportError> {
+    let crops = crop_detections(image, results, options);
+    save_crops(&crops, output_dir, stem)?;
+    Ok(crops)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crop_detections, export_crops, CropOptions};
+    use crate::InferenceResult;
+    use image::{Rgb, RgbImage};
+    use imageproc::rect::Rect;
+
+    fn detection(rect: Rect, class: &str) -> InferenceResult {
+        InferenceResult::new(rect, class, 0, 0.9)
+    }
+
+    #[test]
+    fn test_crop_detections() {
+        let image = RgbImage::from_fn(100, 100, |x, y| Rgb([x as u8, y as u8, 0]));
+        let results = [
+            detection(Rect::at(10, 20).of_size(20, 10), "car"),
+            // partially outside the image
+            detection(Rect::at(90, 90).of_size(20, 20), "person"),
+            // entirely outside the image
+            detection(Rect::at(200, 200).of_size(20, 20), "person"),
+        ];
+
+        let crops = crop_detections(&image, &results, &CropOptions::default());
+        assert_eq!(crops.len(), 2);
+        assert_eq!(crops[0].image.dimensions(), (20, 10));
+        assert_eq!(crops[0].image.get_pixel(0, 0), &Rgb([10, 20, 0]));
+        assert_eq!(crops[1].source_rect, Rect::at(90, 90).of_size(10, 10));
+
+        let options = CropOptions {
+            padding: 0.25,
+            square: true,
+            resize: None,
+        };
+        let crops = crop_detections(&image, &results[..1], &options);
+        // 30 x 15 with padding, squared around the center (20, 25)
+        assert_eq!(crops[0].source_rect, Rect::at(5, 10).of_size(30, 30));
+
+        let options = CropOptions {
+            resize: Some((8, 8)),
+            ..options
+        };
+        let crops = crop_detections(&image, &results[..1], &options);
+        assert_eq!(crops[0].image.dimensions(), (8, 8));
+    }
+
+    #[test]
+    fn test_export_crops() {
+        let output_dir = std::env::temp_dir().join(format!("yolo-rs-crops-{}", std::process::id()));
+        let image = RgbImage::new(100, 100);
+        let results = [
+            detection(Rect::at(0, 0).of_size(10, 10), "traffic light"),
+            detection(Rect::at(20, 20).of_size(10, 10), "car"),
+            // from a class file, must not escape the output directory
+            detection(Rect::at(40, 40).of_size(10, 10), ".."),
+        ];
+
+        let crops = export_crops(
+            &image,
+            &results,
+            &CropOptions::default(),
+            &output_dir,
+            "frame_000001",
+        )
+        .unwrap();
+        assert_eq!(crops.len(), 3);
+        assert!(output_dir
+            .join("traffic_light/frame_000001_0000.png")
+            .is_file());
+        assert!(output_dir.join("car/frame_000001_0001.png").is_file());
+        assert!(output_dir.join("__/frame_000001_0002.png").is_file());
+        assert!(!std::env::temp_dir().join("frame_000001_0002.png").exists());
+
+        std::fs::remove_dir_all(output_dir).unwrap();
+    }
+}
@@ -0,0 +1,227 @@
+//! Label Studio pre-annotations: tasks with predictions, to review the output of a model in Label Studio
+//!
+//! ```rust,ignore
+//! let mut tasks = LabelStudioTasks::new("yolov8n");
+//! tasks.add("/data/local-files/?d=images/street_0001.jpg", image.dimensions(), &results);
+//! tasks.write("tasks.json")?;
+//! // Labelling interface matching the tasks
+//! std::fs::write("config.xml", labeling_config(yolo.classes()))?;
+//! ```
+
+use std::{fs, path::Path};
+
+use serde::Serialize;
+
+use super::{voc::escape_xml, ExportError};
+use crate::InferenceResult;
+
+/// Name of the image field of the tasks and of the labelling interface
+const IMAGE_NAME: &str = "image";
+/// Name of the rectangle labels control of the labelling interface
+const LABEL_NAME: &str = "label";
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LabelStudioTask {
+    pub data: TaskData,
+    pub predictions: Vec<Prediction>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TaskData {
+    /// URL of the image, as served to Label Studio
+    pub image: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Prediction {
+    pub model_version: String,
+    /// Mean confidence of the detections
+    pub score: f32,
+    pub result: Vec<RegionResult>,
+}
+
+/// A single box
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RegionResult {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub result_type: String,
+    pub from_name: String,
+    pub to_name: String,
+    pub original_width: u32,
+    pub original_height: u32,
+    pub image_rotation: u32,
+    pub value: RectangleValue,
+    pub score: f32,
+}
+
+/// Box position in percent of the image size
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RectangleValue {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub rotation: f32,
+    pub rectanglelabels: Vec<String>,
+}
+
+/// Collects the tasks of many images and writes them as a Label Studio import JSON file
+#[derive(Debug, Clone, Default)]
+pub struct LabelStudioTasks {
+    model_version: String,
+    tasks: Vec<LabelStudioTask>,
+}
+
+impl LabelStudioTasks {
+    /// `model_version` is shown in Label Studio next to the predictions
+    pub fn new<S: Into<String>>(model_version: S) -> Self {
+        LabelStudioTasks {
+            model_version: model_version.into(),
+            tasks: Vec::new(),
+        }
+    }
+
+    /// Adds a task for the image at `image_url` of size (width, height).
+    /// Boxes are clamped to the image, those entirely outside it are skipped
+    pub fn add<S: Into<String>>(
+        &mut self,
+        image_url: S,
+        image_size: (u32, u32),
+        results: &[InferenceResult],
+    ) {
+        let (width, height) = image_size;
+        let percent_x = |x: f32| (x * 100.0 / width.max(1) as f32).clamp(0.0, 100.0);
+        let percent_y = |y: f32| (y * 100.0 / height.max(1) as f32).clamp(0.0, 100.0);
+
+        let regions = results
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, result)| {
+                let b_box = result.b_box();
+                let (x, y) = (
+                    percent_x(b_box.left() as f32),
+                    percent_y(b_box.top() as f32),
+                );
+                let region_width = percent_x((b_box.left() + b_box.width() as i32) as f32) - x;
+                let region_height = percent_y((b_box.top() + b_box.height() as i32) as f32) - y;
+                if region_width <= 0.0 || region_height <= 0.0 {
+                    return None;
+                }
+                Some(RegionResult {
+                    id: idx.to_string(),
+                    result_type: "rectanglelabels".to_string(),
+                    from_name: LABEL_NAME.to_string(),
+                    to_name: IMAGE_NAME.to_string(),
+                    original_width: width,
+                    original_height: height,
+                    image_rotation: 0,
+                    value: RectangleValue {
+                        x,
+                        y,
+                        width: region_width,
+                        height: region_height,
+                        rotation: 0.0,
+                        rectanglelabels: vec![result.class().to_string()],
+                    },
+                    score: result.confidence(),
+                })
+            })
+            .collect::<Vec<RegionResult>>();
+        let score = match regions.len() {
+            0 => 0.0,
+            len => regions.iter().map(|region| region.score).sum::<f32>() / len as f32,
+        };
+
+        self.tasks.push(LabelStudioTask {
+            data: TaskData {
+                image: image_url.into(),
+            },
+            predictions: vec![Prediction {
+                model_version: self.model_version.clone(),
+                score,
+                result: regions,
+            }],
+        });
+    }
+
+    pub fn tasks(&self) -> &[LabelStudioTask] {
+        &self.tasks
+    }
+
+    pub fn to_json(&self) -> Result<String, ExportError> {
+        Ok(serde_json::to_string_pretty(&self.tasks)?)
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), ExportError> {
+        fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+}
+
+/// Labelling interface with one rectangle label per class, matching the exported tasks
+pub fn labeling_config(classes: &[String]) -> String {
+    let mut config = format!(
+        "<View>\n  <Image name=\"{IMAGE_NAME}\" value=\"${IMAGE_NAME}\"/>\n  <RectangleLabels name=\"{LABEL_NAME}\" toName=\"{IMAGE_NAME}\">\n"
+    );
+    for class in classes {
+        config.push_str(&format!("    <Label value=\"{}\"/>\n", escape_xml(class)));
+    }
+    config.push_str("  </RectangleLabels>\n</View>\n");
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{labeling_config, LabelStudioTasks};
+    use crate::InferenceResult;
+    use imageproc::rect::Rect;
+
+    #[test]
+    fn test_label_studio_tasks() {
+        let result = |rect: Rect, class: &str, confidence: f32| {
+            InferenceResult::new(rect, class, 0, confidence)
+        };
+        let mut tasks = LabelStudioTasks::new("yolov8n");
+        tasks.add(
+            "/data/street.jpg",
+            (200, 100),
+            &[
+                result(Rect::at(20, 10).of_size(50, 50), "person", 0.5),
+                result(Rect::at(150, 50).of_size(100, 100), "car", 1.0),
+                // entirely outside the image
+                result(Rect::at(-60, 10).of_size(50, 50), "car", 0.9),
+                result(Rect::at(20, 100).of_size(50, 50), "car", 0.9),
+            ],
+        );
+        tasks.add("/data/empty.jpg", (200, 100), &[]);
+
+        let task = &tasks.tasks()[0];
+        assert_eq!(task.data.image, "/data/street.jpg");
+        assert_eq!(task.predictions[0].result.len(), 2);
+        assert_eq!(task.predictions[0].score, 0.75);
+        let value = &task.predictions[0].result[0].value;
+        assert_eq!(
+            (value.x, value.y, value.width, value.height),
+            (10.0, 10.0, 25.0, 50.0)
+        );
+        // clamped to the image
+        let value = &task.predictions[0].result[1].value;
+        assert_eq!(
+            (value.x, value.y, value.width, value.height),
+            (75.0, 50.0, 25.0, 50.0)
+        );
+
+        let json = tasks.to_json().unwrap();
+        assert!(json.contains("\"type\": \"rectanglelabels\""));
+        assert!(json.contains("\"rectanglelabels\": [\n"));
+        assert!(tasks.tasks()[1].predictions[0].result.is_empty());
+    }
+
+    #[test]
+    fn test_labeling_config() {
+        let config = labeling_config(&["person".to_string(), "R&D".to_string()]);
+        assert!(config.contains("<RectangleLabels name=\"label\" toName=\"image\">"));
+        assert!(config.contains("<Label value=\"person\"/>\n    <Label value=\"R&amp;D\"/>"));
+    }
+}
@@ -0,0 +1,126 @@
+//! Pascal VOC XML annotations, one file per image, as imported by CVAT and most labelling tools
+//!
+//! ```rust,ignore
+//! let results = yolo.infer_image(&conf_thresh, &iou_thresh, &image)?;
+//! write_voc_xml("./annotations/street_0001.xml", "street_0001.jpg", image.dimensions(), &results)?;
+//! ```
+
+use std::{fmt::Write as _, fs, path::Path};
+
+use super::ExportError;
+use crate::InferenceResult;
+
+/// Escapes the characters with a meaning in XML
+pub(crate) fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// VOC annotation of the image `filename` of size (width, height).
+/// Boxes are clamped to the image and marked truncated if they were not entirely inside it,
+/// coordinates are 1-based and inclusive as in the original dataset
+pub fn voc_xml(filename: &str, image_size: (u32, u32), results: &[InferenceResult]) -> String {
+    let (width, height) = image_size;
+    let mut xml = String::new();
+
+    // Writing to a String cannot fail
+    let _ = writeln!(xml, "<annotation>");
+    let _ = writeln!(xml, "  <filename>{}</filename>", escape_xml(filename));
+    let _ = writeln!(xml, "  <source>");
+    let _ = writeln!(xml, "    <database>Unknown</database>");
+    let _ = writeln!(xml, "  </source>");
+    let _ = writeln!(xml, "  <size>");
+    let _ = writeln!(xml, "    <width>{width}</width>");
+    let _ = writeln!(xml, "    <height>{height}</height>");
+    let _ = writeln!(xml, "    <depth>3</depth>");
+    let _ = writeln!(xml, "  </size>");
+    let _ = writeln!(xml, "  <segmented>0</segmented>");
+
+    for result in results {
+        let b_box = result.b_box();
+        let (left, top) = (b_box.left() as i64, b_box.top() as i64);
+        let (right, bottom) = (left + b_box.width() as i64, top + b_box.height() as i64);
+        let xmin = left.clamp(0, width as i64);
+        let ymin = top.clamp(0, height as i64);
+        let xmax = right.clamp(0, width as i64);
+        let ymax = bottom.clamp(0, height as i64);
+        if xmax <= xmin || ymax <= ymin {
+            continue;
+        }
+        let truncated = (xmin, ymin, xmax, ymax) != (left, top, right, bottom);
+
+        let _ = writeln!(xml, "  <object>");
+        let _ = writeln!(xml, "    <name>{}</name>", escape_xml(result.class()));
+        let _ = writeln!(xml, "    <pose>Unspecified</pose>");
+        let _ = writeln!(xml, "    <truncated>{}</truncated>", truncated as u8);
+        let _ = writeln!(xml, "    <difficult>0</difficult>");
+        let _ = writeln!(xml, "    <bndbox>");
+        let _ = writeln!(xml, "      <xmin>{}</xmin>", xmin + 1);
+        let _ = writeln!(xml, "      <ymin>{}</ymin>", ymin + 1);
+        let _ = writeln!(xml, "      <xmax>{xmax}</xmax>");
+        let _ = writeln!(xml, "      <ymax>{ymax}</ymax>");
+        let _ = writeln!(xml, "    </bndbox>");
+        let _ = writeln!(xml, "  </object>");
+    }
+    let _ = writeln!(xml, "</annotation>");
+    xml
+}
+
+/// Writes the VOC annotation of an image, see [`voc_xml`]
+pub fn write_voc_xml<P: AsRef<Path>>(
+    path: P,
+    filename: &str,
+    image_size: (u32, u32),
+    results: &[InferenceResult],
+) -> Result<(), ExportError> {
+    if let Some(parent) = path.as_ref().parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, voc_xml(filename, image_size, results))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::voc_xml;
+    use crate::InferenceResult;
+    use imageproc::rect::Rect;
+
+    #[test]
+    fn test_voc_xml() {
+        let result = |rect: Rect, class: &str| InferenceResult::new(rect, class, 0, 0.9);
+        let xml = voc_xml(
+            "a&b.jpg",
+            (100, 50),
+            &[
+                result(Rect::at(0, 0).of_size(10, 20), "person"),
+                result(Rect::at(90, 40).of_size(20, 20), "car"),
+            ],
+        );
+
+        assert!(xml.contains("<filename>a&amp;b.jpg</filename>"));
+        assert!(xml.contains("<width>100</width>\n    <height>50</height>"));
+        assert!(xml.contains(
+            "<name>person</name>\n    <pose>Unspecified</pose>\n    <truncated>0</truncated>"
+        ));
+        assert!(xml.contains(
+            "<xmin>1</xmin>\n      <ymin>1</ymin>\n      <xmax>10</xmax>\n      <ymax>20</ymax>"
+        ));
+        assert!(xml.contains(
+            "<name>car</name>\n    <pose>Unspecified</pose>\n    <truncated>1</truncated>"
+        ));
+        assert!(xml.contains(
+            "<xmin>91</xmin>\n      <ymin>41</ymin>\n      <xmax>100</xmax>\n      <ymax>50</ymax>"
+        ));
+    }
+}
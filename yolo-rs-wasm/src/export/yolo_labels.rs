@@ -0,0 +1,312 @@
+//! Label files in the YOLO training format, to pseudo-label a new dataset with an existing model
+//!
+//! Each image gets a `.txt` file with one `class_id cx cy w h` line per detection,
+//! coordinates normalized by the image size. `classes.txt` and an ultralytics `data.yaml`
+//! list the class names.
+//!
+//! [`label_directory`] copies the images to `<dataset>/images` next to `<dataset>/labels`,
+//! so that the dataset loads with [`Dataset::from_yaml`](crate::dataset::Dataset::from_yaml)
+//! and ultralytics, which find the label file of an image by replacing `images` with `labels`.
+//!
+//! ```rust,ignore
+//! // ./dataset/{images,labels}/** for every image in ./photos, plus classes.txt and data.yaml
+//! let report = label_directory(&yolo, "./photos", "./dataset", &conf_thresh, &iou_thresh)?;
+//! for (path, error) in &report.failures {
+//!     eprintln!("{}: {}", path.display(), error);
+//! }
+//! ```
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use super::ExportError;
+use crate::{backend::InferenceBackend, utils, ConfThresh, IOUThresh, InferenceResult, Yolo};
+
+/// Label lines of the detections of an image of `image_width` x `image_height`,
+/// boxes are clamped to the image
+pub fn format_labels(results: &[InferenceResult], image_width: u32, image_height: u32) -> String {
+    let (width, height) = (image_width.max(1) as f32, image_height.max(1) as f32);
+    let mut labels = String::new();
+
+    for result in results {
+        let b_box = result.b_box();
+        let left = (b_box.left() as f32).clamp(0.0, width);
+        let top = (b_box.top() as f32).clamp(0.0, height);
+        let right = (b_box.left() as f32 + b_box.width() as f32).clamp(0.0, width);
+        let bottom = (b_box.top() as f32 + b_box.height() as f32).clamp(0.0, height);
+        if right <= left || bottom <= top {
+            continue;
+        }
+        // Writing to a String cannot fail
+        let _ = writeln!(
+            labels,
+            "{} {:.6} {:.6} {:.6} {:.6}",
+            result.class_id(),
+            (left + right) / 2.0 / width,
+            (top + bottom) / 2.0 / height,
+            (right - left) / width,
+            (bottom - top) / height,
+        );
+    }
+    labels
+}
+
+/// Writes the label file of an image, an image without detections gets an empty file
+pub fn write_labels<P: AsRef<Path>>(
+    path: P,
+    results: &[InferenceResult],
+    image_width: u32,
+    image_height: u32,
+) -> Result<(), ExportError> {
+    if let Some(parent) = path.as_ref().parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, format_labels(results, image_width, image_height))?;
+    Ok(())
+}
+
+/// Writes `classes.txt`, one class name per line
+pub fn write_classes_txt<P: AsRef<Path>>(
+    dataset_dir: P,
+    classes: &[String],
+) -> Result<(), ExportError> {
+    let mut contents = classes.join("\n");
+    contents.push('\n');
+    fs::write(dataset_dir.as_ref().join("classes.txt"), contents)?;
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct DataYaml<'a> {
+    path: &'a str,
+    train: &'a str,
+    val: &'a str,
+    nc: usize,
+    names: BTreeMap<usize, &'a str>,
+}
+
+/// Writes an ultralytics `data.yaml` in `dataset_dir`, using the images in `<dataset_dir>/images`
+/// for both training and validation. Paths are relative to the file, so the dataset can be moved
+pub fn write_data_yaml<P: AsRef<Path>>(
+    dataset_dir: P,
+    classes: &[String],
+) -> Result<(), ExportError> {
+    let data = DataYaml {
+        path: ".",
+        train: "images",
+        val: "images",
+        nc: classes.len(),
+        names: classes.iter().map(String::as_str).enumerate().collect(),
+    };
+    fs::write(
+        dataset_dir.as_ref().join("data.yaml"),
+        serde_yaml::to_string(&data)?,
+    )?;
+    Ok(())
+}
+
+/// Outcome of [`label_directory`]
+#[derive(Debug, Default)]
+pub struct LabelReport {
+    /// Label files written, one per labelled image
+    pub label_paths: Vec<PathBuf>,
+    /// Images that could not be read, labelled or copied, they are left out of the dataset
+    pub failures: Vec<(PathBuf, ExportError)>,
+}
+
+/// Runs inference on every image in `images_dir` and its sub directories, copies each to
+/// `<dataset_dir>/images/<relative path>` and writes its labels to `<dataset_dir>/labels/<relative path>.txt`,
+/// plus `classes.txt` and `data.yaml`. Images already in `<dataset_dir>/images` are not copied,
+/// and copies of a previous run are left out when `<dataset_dir>` is inside `images_dir`.
+///
+/// An image that fails is recorded in the report and the others are still labelled, as is an image
+/// whose label file was already written for another one, e.g. `a.png` after `a.jpg`.
+/// Only errors listing `images_dir` or writing the dataset files are returned
+pub fn label_directory<B, P, Q>(
+    yolo: &Yolo<B>,
+    images_dir: P,
+    dataset_dir: Q,
+    conf_thresh: &ConfThresh,
+    iou_thresh: &IOUThresh,
+) -> Result<LabelReport, ExportError>
+where
+    B: InferenceBackend,
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let (images_dir, dataset_dir) = (images_dir.as_ref(), dataset_dir.as_ref());
+    let (dataset_images_dir, labels_dir) = (dataset_dir.join("images"), dataset_dir.join("labels"));
+    let copy_images = match (images_dir.canonicalize(), dataset_images_dir.canonicalize()) {
+        (Ok(images_dir), Ok(dataset_images_dir)) => images_dir != dataset_images_dir,
+        _ => true,
+    };
+    let in_dataset_images_dir = utils::in_dir(&dataset_images_dir);
+    let mut images = utils::image_files(images_dir)?;
+    if copy_images {
+        images.retain(|path| !in_dataset_images_dir(path));
+    }
+    let mut report = LabelReport::default();
+    let mut written = HashSet::new();
+
+    for image_path in images {
+        let relative_path = image_path.strip_prefix(images_dir).unwrap_or(&image_path);
+        let label_path = labels_dir.join(relative_path).with_extension("txt");
+        if written.contains(&label_path) {
+            report
+                .failures
+                .push((image_path, ExportError::LabelCollision(label_path)));
+            continue;
+        }
+        let labelled = label_image(yolo, &image_path, &label_path, conf_thresh, iou_thresh)
+            .and_then(|_| {
+                if copy_images {
+                    let copy_path = dataset_images_dir.join(relative_path);
+                    if let Some(parent) = copy_path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::copy(&image_path, copy_path)?;
+                }
+                Ok(())
+            });
+        match labelled {
+            Ok(()) => {
+                written.insert(label_path.clone());
+                report.label_paths.push(label_path);
+            }
+            Err(error) => report.failures.push((image_path, error)),
+        }
+    }
+
+    fs::create_dir_all(dataset_dir)?;
+    write_classes_txt(dataset_dir, yolo.classes())?;
+    write_data_yaml(dataset_dir, yolo.classes())?;
+    Ok(report)
+}
+
+fn label_image<B: InferenceBackend>(
+    yolo: &Yolo<B>,
+    image_path: &Path,
+    label_path: &Path,
+    conf_thresh: &ConfThresh,
+    iou_thresh: &IOUThresh,
+) -> Result<(), ExportError> {
+    let image = image::open(image_path)?.to_rgb8();
+    let results = yolo.infer_image(conf_thresh, iou_thresh, &image)?;
+    write_labels(label_path, &results, image.width(), image.height())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_labels, label_directory, write_classes_txt, write_data_yaml};
+    use crate::{
+        backend::one_person_yolo,
+        dataset::{Dataset, Split},
+        export::ExportError,
+        InferenceResult,
+    };
+    use image::RgbImage;
+    use imageproc::rect::Rect;
+    use std::fs;
+
+    #[test]
+    fn test_format_labels() {
+        let result = |rect: Rect, class_id: usize| InferenceResult::new(rect, "", class_id, 0.9);
+        let results = [
+            result(Rect::at(0, 0).of_size(50, 100), 2),
+            // partially outside the image
+            result(Rect::at(150, -20).of_size(100, 40), 0),
+            // outside the image
+            result(Rect::at(300, 300).of_size(10, 10), 1),
+        ];
+
+        assert_eq!(
+            format_labels(&results, 200, 100),
+            "2 0.125000 0.500000 0.250000 1.000000\n0 0.875000 0.100000 0.250000 0.200000\n"
+        );
+        assert_eq!(format_labels(&[], 200, 100), "");
+    }
+
+    #[test]
+    fn test_dataset_files() {
+        let dataset_dir =
+            std::env::temp_dir().join(format!("yolo-rs-data-yaml-{}", std::process::id()));
+        std::fs::create_dir_all(&dataset_dir).unwrap();
+        let classes = ["person", "car"].map(String::from);
+
+        write_classes_txt(&dataset_dir, &classes).unwrap();
+        write_data_yaml(&dataset_dir, &classes).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(dataset_dir.join("classes.txt")).unwrap(),
+            "person\ncar\n"
+        );
+        let data_yaml = std::fs::read_to_string(dataset_dir.join("data.yaml")).unwrap();
+        assert!(data_yaml.starts_with("path: .\ntrain: images\nval: images\n"));
+        assert!(data_yaml.contains("nc: 2\nnames:\n  0: person\n  1: car\n"));
+
+        std::fs::remove_dir_all(dataset_dir).unwrap();
+    }
+
+    #[test]
+    fn test_label_directory_loads_as_dataset() {
+        let dir = std::env::temp_dir().join(format!("yolo-rs-label-dir-{}", std::process::id()));
+        let (photos_dir, dataset_dir) = (dir.join("photos"), dir.join("dataset"));
+        fs::create_dir_all(photos_dir.join("cam1")).unwrap();
+        RgbImage::new(64, 64)
+            .save(photos_dir.join("cam1/a.png"))
+            .unwrap();
+        fs::write(photos_dir.join("corrupt.png"), b"not a png").unwrap();
+
+        let yolo = one_person_yolo();
+        let (conf, iou) = (yolo.default_conf_thresh(), yolo.default_iou_thresh());
+        let report = label_directory(&yolo, &photos_dir, &dataset_dir, &conf, &iou).unwrap();
+        assert_eq!(report.label_paths, [dataset_dir.join("labels/cam1/a.txt")]);
+        assert_eq!(report.failures.len(), 1);
+        assert!(report.failures[0].0.ends_with("corrupt.png"));
+
+        let dataset = Dataset::from_yaml(dataset_dir.join("data.yaml")).unwrap();
+        assert_eq!(dataset.classes(), ["person"]);
+        let samples = dataset.samples(Split::Val).unwrap();
+        assert_eq!(samples.len(), 1);
+        assert!(samples[0].0.starts_with(dataset_dir.join("images")));
+        assert!(samples[0].1.is_file());
+        let (image, ground_truth) = dataset.iter(Split::Train).unwrap().next().unwrap().unwrap();
+        assert_eq!(image.dimensions(), (64, 64));
+        assert_eq!(ground_truth.len(), 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_label_directory_in_place() {
+        // the dataset is written inside the directory being labelled, as a second run would see it
+        let dir = std::env::temp_dir().join(format!("yolo-rs-label-rerun-{}", std::process::id()));
+        let dataset_dir = dir.join("dataset");
+        fs::create_dir_all(&dir).unwrap();
+        RgbImage::new(64, 64).save(dir.join("a.jpg")).unwrap();
+        RgbImage::new(64, 64).save(dir.join("a.png")).unwrap();
+
+        let yolo = one_person_yolo();
+        let (conf, iou) = (yolo.default_conf_thresh(), yolo.default_iou_thresh());
+        for _ in 0..2 {
+            let report = label_directory(&yolo, &dir, &dataset_dir, &conf, &iou).unwrap();
+            assert_eq!(report.label_paths, [dataset_dir.join("labels/a.txt")]);
+            // a.png would overwrite the labels of a.jpg
+            assert_eq!(report.failures.len(), 1);
+            assert!(report.failures[0].0.ends_with("a.png"));
+            assert!(matches!(
+                report.failures[0].1,
+                ExportError::LabelCollision(_)
+            ));
+        }
+        assert!(!dataset_dir.join("images/dataset").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
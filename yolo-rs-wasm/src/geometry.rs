@@ -0,0 +1,185 @@
+//! Points, line segments and polygons in image coordinates (x to the right, y down)
+
+use imageproc::rect::Rect;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Point {
+    pub fn new(x: f32, y: f32) -> Self {
+        Point { x, y }
+    }
+}
+
+impl From<(f32, f32)> for Point {
+    fn from((x, y): (f32, f32)) -> Self {
+        Point { x, y }
+    }
+}
+
+/// Reference point of a bounding box
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Anchor {
+    Center,
+    /// Middle of the bottom edge, where people and vehicles touch the ground
+    #[default]
+    BottomCenter,
+}
+
+impl Anchor {
+    pub fn of(&self, b_box: Rect) -> Point {
+        let x = b_box.left() as f32 + b_box.width() as f32 / 2.;
+        match self {
+            Anchor::Center => Point::new(x, b_box.top() as f32 + b_box.height() as f32 / 2.),
+            Anchor::BottomCenter => Point::new(x, (b_box.top() + b_box.height() as i32) as f32),
+        }
+    }
+}
+
+/// Line segment from `start` to `end`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Line {
+    pub start: Point,
+    pub end: Point,
+}
+
+impl Line {
+    pub fn new<P: Into<Point>>(start: P, end: P) -> Self {
+        Line {
+            start: start.into(),
+            end: end.into(),
+        }
+    }
+
+    /// Positive when `point` is on the right of the line, looking from `start` to `end`
+    /// on screen, negative on the left, and 0 on the line
+    pub fn side(&self, point: Point) -> f32 {
+        (self.end.x - self.start.x) * (point.y - self.start.y)
+            - (self.end.y - self.start.y) * (point.x - self.start.x)
+    }
+
+    /// Whether the segment from `from` to `to` crosses this segment.
+    /// Points on the line count as being on its right, so that a path touching
+    /// the line and moving on crosses it once
+    pub fn intersects(&self, from: Point, to: Point) -> bool {
+        let path = Line::new(from, to);
+        let (d1, d2) = (self.side(from), self.side(to));
+        let (d3, d4) = (path.side(self.start), path.side(self.end));
+        (d1 < 0.) != (d2 < 0.) && d3 * d4 <= 0.
+    }
+}
+
+/// Simple polygon, the last point connects back to the first
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polygon {
+    points: Vec<Point>,
+}
+
+impl Polygon {
+    pub fn new<P: Into<Point>>(points: impl IntoIterator<Item = P>) -> Self {
+        Polygon {
+            points: points.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    pub fn from_rect(rect: Rect) -> Self {
+        let (left, top) = (rect.left() as f32, rect.top() as f32);
+        let (right, bottom) = (left + rect.width() as f32, top + rect.height() as f32);
+        Polygon::new([(left, top), (right, top), (right, bottom), (left, bottom)])
+    }
+
+    pub fn points(&self) -> &[Point] {
+        &self.points
+    }
+
+    /// Even-odd rule point in polygon test
+    pub fn contains(&self, point: Point) -> bool {
+        let mut inside = false;
+        let mut prev = match self.points.last() {
+            Some(prev) => *prev,
+            None => return false,
+        };
+        for &current in &self.points {
+            if (current.y > point.y) != (prev.y > point.y) {
+                let x_cross =
+                    current.x + (point.y - current.y) * (prev.x - current.x) / (prev.y - current.y);
+                if point.x < x_cross {
+                    inside = !inside;
+                }
+            }
+            prev = current;
+        }
+        inside
+    }
+
+    /// Area enclosed by the polygon, with the shoelace formula
+    pub fn area(&self) -> f32 {
+        let n = self.points.len();
+        let twice_area: f32 = (0..n)
+            .map(|i| {
+                let (a, b) = (self.points[i], self.points[(i + 1) % n]);
+                a.x * b.y - b.x * a.y
+            })
+            .sum();
+        twice_area.abs() / 2.
+    }
+
+    /// Smallest rectangle containing the polygon, `None` for an empty polygon
+    pub fn bounding_rect(&self) -> Option<Rect> {
+        let first = self.points.first()?;
+        let (mut min, mut max) = (*first, *first);
+        for point in &self.points {
+            min = Point::new(min.x.min(point.x), min.y.min(point.y));
+            max = Point::new(max.x.max(point.x), max.y.max(point.y));
+        }
+        Some(
+            Rect::at(min.x.floor() as i32, min.y.floor() as i32).of_size(
+                ((max.x.ceil() - min.x.floor()) as u32).max(1),
+                ((max.y.ceil() - min.y.floor()) as u32).max(1),
+            ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Line, Point, Polygon};
+
+    #[test]
+    fn test_polygon_contains() {
+        // L shaped polygon
+        let polygon = Polygon::new([
+            (0., 0.),
+            (10., 0.),
+            (10., 5.),
+            (5., 5.),
+            (5., 10.),
+            (0., 10.),
+        ]);
+        assert!(polygon.contains(Point::new(2., 2.)));
+        assert!(polygon.contains(Point::new(2., 8.)));
+        assert!(!polygon.contains(Point::new(8., 8.)));
+        assert!(!polygon.contains(Point::new(-1., 2.)));
+        assert_eq!(polygon.area(), 75.);
+        assert!(!Polygon::new(Vec::<Point>::new()).contains(Point::new(0., 0.)));
+    }
+
+    #[test]
+    fn test_line_crossing() {
+        let line = Line::new((0., 10.), (100., 10.));
+        assert!(line.side(Point::new(50., 20.)) > 0.);
+        assert!(line.side(Point::new(50., 0.)) < 0.);
+
+        assert!(line.intersects(Point::new(50., 0.), Point::new(50., 20.)));
+        // passes beyond the end of the line
+        assert!(!line.intersects(Point::new(150., 0.), Point::new(150., 20.)));
+        // stays on one side
+        assert!(!line.intersects(Point::new(50., 0.), Point::new(60., 5.)));
+        // touches the line, then moves on
+        assert!(line.intersects(Point::new(50., 0.), Point::new(50., 10.)));
+        assert!(!line.intersects(Point::new(50., 10.), Point::new(50., 20.)));
+    }
+}
@@ -0,0 +1,285 @@
+//! Density of detections accumulated over many images or video frames
+//!
+//! ```rust,ignore
+//! let mut heatmap = Heatmap::new(frame.width(), frame.height())
+//!     .with_cell_size(8)
+//!     .with_classes(["person"]);
+//! heatmap.accumulate_frames(&yolo.infer_video(input, output, &conf, &iou, DrawBoundingBoxes::False, None)?);
+//! heatmap.save_png("foot_traffic.png")?;
+//! heatmap.render_blended(&frame, 0.6).save("foot_traffic_overlay.png")?;
+//! ```
+
+use std::path::Path;
+
+use image::{Rgb, RgbImage};
+
+use crate::{geometry::Anchor, InferenceResult};
+
+/// Colors of [`colormap`], evenly spaced from 0 to 1
+const COLORMAP_STOPS: [[f32; 3]; 6] = [
+    [0., 0., 0.],
+    [0., 0., 255.],
+    [0., 255., 255.],
+    [0., 255., 0.],
+    [255., 255., 0.],
+    [255., 0., 0.],
+];
+
+/// Maps `t` in [0, 1] to a color, from black through blue, cyan, green and yellow to red
+pub fn colormap(t: f32) -> Rgb<u8> {
+    let scaled = t.clamp(0., 1.) * (COLORMAP_STOPS.len() - 1) as f32;
+    let idx = (scaled.floor() as usize).min(COLORMAP_STOPS.len() - 2);
+    let frac = scaled - idx as f32;
+    let (low, high) = (COLORMAP_STOPS[idx], COLORMAP_STOPS[idx + 1]);
+    Rgb([0, 1, 2].map(|c| (low[c] + (high[c] - low[c]) * frac).round() as u8))
+}
+
+/// What a detection adds to the heatmap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeatmapMode {
+    /// One count in the cell of an anchor point of the box
+    Points(Anchor),
+    /// One count in every cell covered by the box
+    Coverage,
+}
+
+impl Default for HeatmapMode {
+    fn default() -> Self {
+        HeatmapMode::Points(Anchor::BottomCenter)
+    }
+}
+
+/// Grid of detection counts over an image, see the [module documentation](self)
+#[derive(Debug, Clone)]
+pub struct Heatmap {
+    width: u32,
+    height: u32,
+    cell_size: u32,
+    mode: HeatmapMode,
+    classes: Option<Vec<String>>,
+    grid: Vec<f32>,
+    frames: usize,
+}
+
+impl Heatmap {
+    /// Heatmap over images of `width` x `height` pixels, with one cell per pixel
+    pub fn new(width: u32, height: u32) -> Self {
+        Heatmap {
+            width,
+            height,
+            cell_size: 1,
+            mode: HeatmapMode::default(),
+            classes: None,
+            grid: vec![0.; (width * height) as usize],
+            frames: 0,
+        }
+    }
+
+    /// Size of the square cells in pixels, larger cells give a smoother heatmap. Clears the grid
+    pub fn with_cell_size(mut self, cell_size: u32) -> Self {
+        self.cell_size = cell_size.max(1);
+        let (cols, rows) = self.grid_size();
+        self.grid = vec![0.; (cols * rows) as usize];
+        self
+    }
+
+    pub fn with_mode(mut self, mode: HeatmapMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Only accumulate detections of these classes
+    pub fn with_classes<S: Into<String>>(mut self, classes: impl IntoIterator<Item = S>) -> Self {
+        self.classes = Some(classes.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Number of columns and rows of the grid
+    pub fn grid_size(&self) -> (u32, u32) {
+        (
+            self.width.div_ceil(self.cell_size),
+            self.height.div_ceil(self.cell_size),
+        )
+    }
+
+    /// Counts of the cells, row by row
+    pub fn grid(&self) -> &[f32] {
+        &self.grid
+    }
+
+    /// Number of frames accumulated so far
+    pub fn frame_count(&self) -> usize {
+        self.frames
+    }
+
+    pub fn max(&self) -> f32 {
+        self.grid.iter().copied().fold(0., f32::max)
+    }
+
+    /// Adds the detections of one image or frame
+    pub fn accumulate(&mut self, results: &[InferenceResult]) {
+        self.frames += 1;
+        let (cols, rows) = self.grid_size();
+        if self.grid.is_empty() {
+            return;
+        }
+
+        for result in results {
+            if let Some(classes) = &self.classes {
+                if !classes.iter().any(|class| class == result.class()) {
+                    continue;
+                }
+            }
+            let b_box = result.b_box();
+            match self.mode {
+                HeatmapMode::Points(anchor) => {
+                    let point = anchor.of(b_box);
+                    if point.x < 0.
+                        || point.y < 0.
+                        || point.x > self.width as f32
+                        || point.y > self.height as f32
+                    {
+                        continue;
+                    }
+                    // Anchors on the right or bottom edge belong to the last cell
+                    let col = (point.x as u32 / self.cell_size).min(cols - 1);
+                    let row = (point.y as u32 / self.cell_size).min(rows - 1);
+                    self.grid[(row * cols + col) as usize] += 1.;
+                }
+                HeatmapMode::Coverage => {
+                    // entirely above or left of the image, clamping would count it in row or column 0
+                    if b_box.right() < 0 || b_box.bottom() < 0 {
+                        continue;
+                    }
+                    let to_col = |x: i32| (x.max(0) as u32 / self.cell_size).min(cols);
+                    let to_row = |y: i32| (y.max(0) as u32 / self.cell_size).min(rows);
+                    // right and bottom of a Rect are inclusive
+                    for row in to_row(b_box.top())..(to_row(b_box.bottom()) + 1).min(rows) {
+                        for col in to_col(b_box.left())..(to_col(b_box.right()) + 1).min(cols) {
+                            self.grid[(row * cols + col) as usize] += 1.;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Adds the detections of every frame of a video, e.g. as returned by
+    /// [`Yolo::infer_video`](crate::Yolo::infer_video)
+    pub fn accumulate_frames(&mut self, frame_results: &[Vec<InferenceResult>]) {
+        for results in frame_results {
+            self.accumulate(results);
+        }
+    }
+
+    /// Clears the grid
+    pub fn reset(&mut self) {
+        self.grid.iter_mut().for_each(|count| *count = 0.);
+        self.frames = 0;
+    }
+
+    /// Renders the counts, relative to the highest one, with [`colormap`] at the image resolution
+    pub fn render(&self) -> RgbImage {
+        let (cols, _) = self.grid_size();
+        let max = self.max();
+        RgbImage::from_fn(self.width, self.height, |x, y| {
+            let count = self.grid[((y / self.cell_size) * cols + x / self.cell_size) as usize];
+            colormap(if max > 0. { count / max } else { 0. })
+        })
+    }
+
+    /// Blends the rendered heatmap over `frame` with opacity `alpha` in [0, 1],
+    /// cells without any detection keep the pixels of `frame`
+    pub fn render_blended(&self, frame: &RgbImage, alpha: f32) -> RgbImage {
+        let (cols, _) = self.grid_size();
+        let (max, alpha) = (self.max(), alpha.clamp(0., 1.));
+        let mut blended = frame.clone();
+
+        for (x, y, pixel) in blended.enumerate_pixels_mut() {
+            if x >= self.width || y >= self.height || max <= 0. {
+                continue;
+            }
+            let count = self.grid[((y / self.cell_size) * cols + x / self.cell_size) as usize];
+            if count <= 0. {
+                continue;
+            }
+            let heat = colormap(count / max);
+            for (channel, heat_channel) in pixel.0.iter_mut().zip(heat.0) {
+                *channel =
+                    (*channel as f32 * (1. - alpha) + heat_channel as f32 * alpha).round() as u8;
+            }
+        }
+        blended
+    }
+
+    /// Renders the heatmap and saves it as a PNG
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> image::ImageResult<()> {
+        self.render()
+            .save_with_format(path, image::ImageFormat::Png)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{colormap, Heatmap, HeatmapMode};
+    use crate::InferenceResult;
+    use image::{Rgb, RgbImage};
+    use imageproc::rect::Rect;
+
+    fn detection(x: i32, y: i32, class: &str) -> InferenceResult {
+        InferenceResult::new(Rect::at(x, y).of_size(10, 10), class, 0, 0.9)
+    }
+
+    #[test]
+    fn test_heatmap_accumulation() {
+        let frames = vec![
+            vec![detection(0, 0, "person"), detection(20, 20, "car")],
+            vec![detection(2, 2, "person")],
+        ];
+
+        // bottom centers at (5, 10) and (7, 12), in the cell (0, 1)
+        let mut points = Heatmap::new(40, 40)
+            .with_cell_size(10)
+            .with_classes(["person"]);
+        points.accumulate_frames(&frames);
+        assert_eq!(points.grid_size(), (4, 4));
+        assert_eq!(points.frame_count(), 2);
+        assert_eq!(points.grid()[4], 2.);
+        assert_eq!(points.grid().iter().sum::<f32>(), 2.);
+
+        let mut coverage = Heatmap::new(40, 40)
+            .with_cell_size(10)
+            .with_mode(HeatmapMode::Coverage);
+        coverage.accumulate_frames(&frames);
+        // the second person covers 4 cells, the others exactly one
+        assert_eq!(coverage.grid()[0], 2.);
+        assert_eq!(coverage.grid()[10], 1.);
+        assert_eq!(coverage.grid().iter().sum::<f32>(), 6.);
+
+        // boxes off the image are not counted, like off-image anchors in points mode
+        coverage.reset();
+        coverage.accumulate(&[
+            detection(-20, 5, "car"),
+            detection(5, -20, "car"),
+            detection(50, 5, "car"),
+        ]);
+        assert_eq!(coverage.grid().iter().sum::<f32>(), 0.);
+    }
+
+    #[test]
+    fn test_heatmap_render() {
+        assert_eq!(colormap(0.), Rgb([0, 0, 0]));
+        assert_eq!(colormap(1.), Rgb([255, 0, 0]));
+
+        let mut heatmap = Heatmap::new(20, 20).with_cell_size(10);
+        heatmap.accumulate(&[detection(0, 0, "person")]);
+        let rendered = heatmap.render();
+        assert_eq!(rendered.get_pixel(5, 15), &Rgb([255, 0, 0]));
+        assert_eq!(rendered.get_pixel(15, 5), &Rgb([0, 0, 0]));
+
+        let frame = RgbImage::from_pixel(20, 20, Rgb([0, 0, 200]));
+        let blended = heatmap.render_blended(&frame, 0.5);
+        assert_eq!(blended.get_pixel(5, 15), &Rgb([128, 0, 100]));
+        assert_eq!(blended.get_pixel(15, 5), &Rgb([0, 0, 200]));
+    }
+}
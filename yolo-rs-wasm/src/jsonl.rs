@@ -0,0 +1,187 @@
+//! JSON Lines output, one JSON object per image or video frame
+//!
+//! Each [`Record`] is written and flushed as soon as its results are produced,
+//! so the output can be piped into other tools while a video is still being processed:
+//!
+//! ```json
+//! {"source":"street.mp4","frame":12,"timestamp":1700000000.123,"time":0.48,"detections":[{"class":"person","class_id":0,"confidence":0.87,"bbox":[412,180,64,170],"track_id":3}]}
+//! ```
+//!
+//! ```rust,ignore
+//! // with the frame rate of the video, records also carry the time of the frame in the video
+//! let mut writer = JsonlWriter::stdout().with_fps(25.0);
+//! yolo.infer_video_with(video_path, output_path, &conf_thresh, &iou_thresh, draw, None, |frame, results| {
+//!     Ok(writer.write("street.mp4", frame, results)?)
+//! })?;
+//! ```
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Stdout, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::InferenceResult;
+
+/// A detection as written in a [`Record`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Detection {
+    pub class: String,
+    pub class_id: usize,
+    pub confidence: f32,
+    /// `[left, top, width, height]` in pixels
+    pub bbox: [i64; 4],
+    pub track_id: Option<u64>,
+}
+
+impl From<&InferenceResult> for Detection {
+    fn from(result: &InferenceResult) -> Self {
+        let b_box = result.b_box();
+        Detection {
+            class: result.class().to_string(),
+            class_id: result.class_id(),
+            confidence: result.confidence(),
+            bbox: [
+                b_box.left() as i64,
+                b_box.top() as i64,
+                b_box.width() as i64,
+                b_box.height() as i64,
+            ],
+            track_id: result.track_id(),
+        }
+    }
+}
+
+/// Detections of an image or video frame
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    /// Path of the image or video
+    pub source: String,
+    /// Index of the frame in the video, 0 for images
+    pub frame: usize,
+    /// Seconds since the Unix epoch at which the results were produced, not when the frame was recorded
+    pub timestamp: f64,
+    /// Seconds from the start of the video to the frame, `None` for images or when the frame rate is unknown
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<f64>,
+    pub detections: Vec<Detection>,
+}
+
+impl Record {
+    /// Record of `results`, timestamped now
+    pub fn new(source: impl Into<String>, frame: usize, results: &[InferenceResult]) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0., |elapsed| elapsed.as_secs_f64());
+        Record {
+            source: source.into(),
+            frame,
+            timestamp,
+            time: None,
+            detections: results.iter().map(Detection::from).collect(),
+        }
+    }
+
+    /// Sets the time of the frame in the video, in seconds
+    pub fn with_time(mut self, seconds: f64) -> Self {
+        self.time = Some(seconds);
+        self
+    }
+}
+
+/// Writes a [`Record`] per line, flushing after each one
+pub struct JsonlWriter<W: Write> {
+    writer: W,
+    fps: Option<f64>,
+}
+
+impl JsonlWriter<Stdout> {
+    pub fn stdout() -> Self {
+        JsonlWriter::new(io::stdout())
+    }
+}
+
+impl JsonlWriter<BufWriter<File>> {
+    /// Creates or truncates the file at `path`
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(JsonlWriter::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write> JsonlWriter<W> {
+    pub fn new(writer: W) -> Self {
+        JsonlWriter { writer, fps: None }
+    }
+
+    /// Frame rate of the video, [`JsonlWriter::write`] sets the time of frame `n` to `n / fps`.
+    /// The video plugin does not report it, so it has to come from the caller
+    pub fn with_fps(mut self, fps: f64) -> Self {
+        self.fps = Some(fps);
+        self
+    }
+
+    pub fn write_record(&mut self, record: &Record) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()
+    }
+
+    /// Writes the record of the results of an image or frame, see [`Record::new`],
+    /// with the time of the frame if the frame rate is known
+    pub fn write(
+        &mut self,
+        source: &str,
+        frame: usize,
+        results: &[InferenceResult],
+    ) -> io::Result<()> {
+        let record = Record::new(source, frame, results);
+        let record = match self.fps {
+            Some(fps) => record.with_time(frame as f64 / fps.max(f64::EPSILON)),
+            None => record,
+        };
+        self.write_record(&record)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{JsonlWriter, Record};
+    use crate::InferenceResult;
+    use imageproc::rect::Rect;
+
+    #[test]
+    fn test_jsonl_writer() {
+        let results =
+            vec![
+                InferenceResult::new(Rect::at(10, 20).of_size(30, 40), "person", 0, 0.5)
+                    .with_track_id(Some(3)),
+            ];
+        let mut writer = JsonlWriter::new(Vec::new());
+        writer.write("street.mp4", 0, &results).unwrap();
+        writer.write("street.mp4", 1, &[]).unwrap();
+
+        let output = String::from_utf8(writer.into_inner()).unwrap();
+        let lines = output.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains(r#""bbox":[10,20,30,40],"track_id":3"#));
+
+        let record: Record = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!((record.source.as_str(), record.frame), ("street.mp4", 1));
+        assert!(record.detections.is_empty());
+        assert!(record.timestamp > 0.);
+        assert_eq!(record.time, None);
+
+        let mut writer = JsonlWriter::new(Vec::new()).with_fps(25.);
+        writer.write("street.mp4", 50, &[]).unwrap();
+        let output = String::from_utf8(writer.into_inner()).unwrap();
+        let record: Record = serde_json::from_str(&output).unwrap();
+        assert_eq!(record.time, Some(2.));
+    }
+}
@@ -6,7 +6,7 @@
 //! It supports both Image and Video inference.
 //!
 //! ### Usage
-//! ```rust
+//! ```rust,ignore
 //! let yolo: Yolo = YoloBuilder::new()
 //!     .classes_file(class_names_path)
 //!     .unwrap()
@@ -29,47 +29,104 @@
 //!
 //! ```
 //!
+//! Models can also be described by a [manifest](manifest) file next to the model,
+//! which declares the model path(s), classes, input size and default thresholds:
+//!
+//! ```rust,ignore
+//! let yolo: Yolo = YoloBuilder::from_manifest("./models/yolov8n.toml")
+//!     .unwrap()
+//!     .build()
+//!     .unwrap();
+//!
+//! let vec_result = yolo
+//!     .infer_image(&yolo.default_conf_thresh(), &yolo.default_iou_thresh(), &rgb_image)
+//!     .unwrap();
+//! ```
+//!
 //! ### Note
 //!
 //! This crate is in active development
 //! There are some features that are still being worked on.
 //! Please refer to the limitations section of the README file on the github repo.
 
-use image::{ImageBuffer, RgbImage};
+#[cfg(feature = "wasi-nn")]
+use backend::WasiNnBackend;
+use backend::{BackendError, DefaultBackend, InferenceBackend, Tensor};
+#[cfg(feature = "video-plugin")]
+use image::ImageBuffer;
+use image::RgbImage;
 use imageproc::rect::Rect;
+#[cfg(feature = "video-plugin")]
 use log::{debug, error, info, LevelFilter};
+use manifest::{ManifestError, ModelManifest};
 use prepare::ResizeScale;
 use process::{
     apply_confidence_and_scale, non_maximum_supression, process_output_buffer_to_tensor,
 };
 
+use roi::Roi;
 use rusttype::Font;
+use serde::Deserialize;
+use stats::{InferenceStats, StageTimings};
+#[cfg(feature = "video-plugin")]
+use std::io::ErrorKind;
 use std::{
+    borrow::Cow,
+    cell::RefCell,
     fs::{self},
-    io::{self, ErrorKind},
-    path::Path,
+    io::{self},
+    path::{Path, PathBuf},
+    time::Instant,
 };
+#[cfg(feature = "wasi-nn")]
 use wasi_nn::{ExecutionTarget, Graph, GraphEncoding};
 
+#[cfg(feature = "video-plugin")]
 use crate::video_proc::yolo_rs_video_plugin;
 
+pub mod analytics;
+pub mod backend;
+pub mod batch;
+pub mod dataset;
+pub mod eval;
+pub mod export;
+pub mod geometry;
+pub mod heatmap;
+pub mod jsonl;
+pub mod manifest;
 mod prepare;
 mod process;
+pub mod roi;
+pub mod server;
+pub mod stats;
+pub mod tracking;
 pub mod utils;
+#[cfg(feature = "video-plugin")]
 mod video_proc;
 
-/// Yolo Struct containing Graph, Font for Drawing and class names.
-pub struct Yolo {
+/// Yolo Struct containing the inference backend, class names and pre processing settings.
+/// Inference runs through WasmEdge's WASI-NN plugin by default,
+/// or in process with the `pure-rust` feature, see [`backend`] for alternatives
+pub struct Yolo<B = DefaultBackend> {
     // inference_type: YoloType
-    graph: Graph,
+    backend: B,
     classes: Vec<String>,
+    input_size: InputSize,
+    normalization: Normalization,
+    conf_thresh: ConfThresh,
+    iou_thresh: IOUThresh,
+    roi: Option<Roi>,
+    stats: Option<RefCell<InferenceStats>>,
 }
 
 // TODO: implement Processing for Pose And Segment models
 /// Enum to distinguish which Type of post proccessing to be applied to nerual net output- Unused Currently
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
 pub enum YoloType {
     Pose,
     Segment,
+    #[serde(alias = "detect")]
     Detection,
 }
 
@@ -82,12 +139,19 @@ pub enum YoloRuntimeError {
     #[error("file error")]
     FileError(#[from] io::Error),
 
+    #[cfg(feature = "wasi-nn")]
     #[error("graph error")]
     GraphError(#[from] wasi_nn::Error),
 
+    #[error("inference backend error")]
+    BackendError(#[from] BackendError),
+
     #[error("result processing error")]
     PostProcessingError(#[from] PostProcessingError),
 
+    #[error("region of interest error")]
+    RoiError(#[from] roi::RoiError),
+
     #[error("video plugin: load video")]
     VideoLoad,
 
@@ -103,19 +167,88 @@ pub enum YoloRuntimeError {
 pub enum PostProcessingError {
     #[error("Cannot broadcast to Array dimension")]
     BroadcastArrayDims,
+
+    #[error("Model output of shape {shape:?} does not match the {num_classes} classes")]
+    OutputShape {
+        shape: Vec<usize>,
+        num_classes: usize,
+    },
 }
 
 pub enum DrawBoundingBoxes {
+    /// Draw with the bundled font and the default style, requires the `bundled-font` feature
+    #[cfg(feature = "bundled-font")]
+    Default,
     TrueWithFont(Font<'static>),
+    /// Draw with a custom [`utils::DrawStyle`]
+    WithStyle(Font<'static>, utils::DrawStyle),
+    /// Anonymize the detections of `classes` instead of drawing them, or every detection if `classes` is empty
+    Anonymize {
+        classes: Vec<String>,
+        anonymization: utils::Anonymization,
+    },
     False,
 }
 
-const INPUT_WIDTH: usize = 640;
-const INPUT_HEIGHT: usize = 640;
-const OUTPUT_CLASSES: usize = 80;
-const OUTPUT_OBJECTS: usize = 8400;
+const INPUT_WIDTH: u32 = 640;
+const INPUT_HEIGHT: u32 = 640;
+/// Strides of the three detection heads of YOLOv8
+const OUTPUT_STRIDES: [u32; 3] = [8, 16, 32];
+
+/// Shape of the output of a YOLOv8 detection model, `[1, 4 + num_classes, num_anchors]`
+#[cfg(feature = "wasi-nn")]
+fn detection_output_shape(input_size: &InputSize, num_classes: usize) -> Vec<usize> {
+    vec![1, 4 + num_classes, input_size.num_anchors()]
+}
+
+/// Width and Height in pixels of the image tensor the model expects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl InputSize {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width, height }
+    }
+
+    /// Number of candidate detections the model outputs for this input size,
+    /// 8400 for a 640 x 640 input
+    pub fn num_anchors(&self) -> usize {
+        OUTPUT_STRIDES
+            .iter()
+            .map(|stride| ((self.width / stride) * (self.height / stride)) as usize)
+            .sum()
+    }
+}
+
+impl Default for InputSize {
+    fn default() -> Self {
+        Self::new(INPUT_WIDTH, INPUT_HEIGHT)
+    }
+}
+
+/// Per channel normalization applied to pixel values after scaling them to [0, 1]
+/// `value = (pixel / 255 - mean) / std`
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct Normalization {
+    pub mean: [f32; 3],
+    pub std: [f32; 3],
+}
+
+impl Default for Normalization {
+    fn default() -> Self {
+        Self {
+            mean: [0.0; 3],
+            std: [1.0; 3],
+        }
+    }
+}
 
 /// Struct to hold Confidence Threshold f32
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct ConfThresh(pub f32);
 
 impl ConfThresh {
@@ -124,7 +257,14 @@ impl ConfThresh {
     }
 }
 
+impl Default for ConfThresh {
+    fn default() -> Self {
+        Self(0.5)
+    }
+}
+
 /// struct to hold floating point value for Intersection over Union
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct IOUThresh(pub f32);
 
 impl IOUThresh {
@@ -133,10 +273,79 @@ impl IOUThresh {
     }
 }
 
-impl Yolo {
-    /// Creates a new instance of YOLO, including graph and classes
+impl Default for IOUThresh {
+    fn default() -> Self {
+        Self(0.5)
+    }
+}
+
+#[cfg(feature = "wasi-nn")]
+impl Yolo<WasiNnBackend> {
+    /// Creates a new instance of YOLO, including graph and classes,
+    /// for a model with the default 640 x 640 input size
     pub fn new(graph: Graph, classes: Vec<String>) -> Self {
-        Yolo { graph, classes }
+        let output_shape = detection_output_shape(&InputSize::default(), classes.len());
+        Yolo::with_backend(WasiNnBackend::new(graph, output_shape), classes)
+    }
+}
+
+impl<B: InferenceBackend> Yolo<B> {
+    /// Creates a new instance of YOLO running inference with `backend`
+    pub fn with_backend(backend: B, classes: Vec<String>) -> Self {
+        Yolo {
+            backend,
+            classes,
+            input_size: InputSize::default(),
+            normalization: Normalization::default(),
+            conf_thresh: ConfThresh::default(),
+            iou_thresh: IOUThresh::default(),
+            roi: None,
+            stats: None,
+        }
+    }
+
+    /// Class names, in the order of the model output
+    pub fn classes(&self) -> &[String] {
+        &self.classes
+    }
+
+    pub fn input_size(&self) -> InputSize {
+        self.input_size
+    }
+
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Confidence threshold set on the builder or declared in the manifest, 0.5 otherwise
+    pub fn default_conf_thresh(&self) -> ConfThresh {
+        self.conf_thresh
+    }
+
+    /// IoU threshold set on the builder or declared in the manifest, 0.5 otherwise
+    pub fn default_iou_thresh(&self) -> IOUThresh {
+        self.iou_thresh
+    }
+
+    /// Region of interest applied to every inference, see [`roi`]
+    pub fn roi(&self) -> Option<&Roi> {
+        self.roi.as_ref()
+    }
+
+    /// Sets or clears the region of interest, e.g. when a fixed camera is moved
+    pub fn set_roi(&mut self, roi: Option<Roi>) {
+        self.roi = roi;
+    }
+
+    /// Timing statistics of every inference run so far,
+    /// `None` unless enabled with [`YoloBuilder::collect_stats`]
+    pub fn stats(&self) -> Option<InferenceStats> {
+        self.stats.as_ref().map(|stats| stats.borrow().clone())
+    }
+
+    /// Returns the statistics collected so far and starts collecting anew
+    pub fn take_stats(&self) -> Option<InferenceStats> {
+        self.stats.as_ref().map(|stats| stats.take())
     }
 
     // Convienence function to run load file and poarse as image
@@ -151,12 +360,12 @@ impl Yolo {
     // }
 
     pub fn infer_file<P: AsRef<Path>>(
-        self,
+        &self,
         image_path: P,
         conf_thresh: &ConfThresh,
         iou_thresh: &IOUThresh,
     ) -> Result<Vec<InferenceResult>, YoloRuntimeError> {
-        let image_buffer = Yolo::load_image_from_file(image_path)?;
+        let image_buffer = Self::load_image_from_file(image_path)?;
         self.infer_image(conf_thresh, iou_thresh, &image_buffer)
     }
 
@@ -166,31 +375,70 @@ impl Yolo {
     /// - Splits video into individual frames
     /// - Retrieves Frames one by one from Video Plugin
     /// - Runs Image inference on frame, using infer_image method
+    /// - Assigns track ids to the detections, if a [`Tracker`](tracking::Tracker) is supplied
     /// - Writes post-inference frame back to Plugin Memory frame buffer
     /// - Reassmbles Frames into video, and outputs video
-    // #[cfg(feature = "video-plugin")]
+    ///
+    /// Returns the results of every frame, see [`Yolo::infer_video_with`] to handle them as they are produced.
+    /// Requires the `video-plugin` feature
+    #[cfg(feature = "video-plugin")]
     pub fn infer_video<P: AsRef<Path>>(
-        self,
+        &self,
         video_path: P,
         output_path: P,
         conf_thresh: &ConfThresh,
         iou_thresh: &IOUThresh,
         draw_bounding_boxes: DrawBoundingBoxes,
+        tracker: Option<&mut tracking::Tracker>,
     ) -> Result<Vec<Vec<InferenceResult>>, YoloRuntimeError> {
+        let mut vec_results_by_frame = Vec::new();
+        self.infer_video_with(
+            video_path,
+            output_path,
+            conf_thresh,
+            iou_thresh,
+            draw_bounding_boxes,
+            tracker,
+            |_, vec_results| {
+                vec_results_by_frame.push(vec_results.clone());
+                Ok(())
+            },
+        )?;
+        Ok(vec_results_by_frame)
+    }
+
+    /// Same as [`Yolo::infer_video`], calling `on_frame` with the index and results of each frame
+    /// instead of collecting them, e.g. to stream them with a [`JsonlWriter`](jsonl::JsonlWriter).
+    /// `on_frame` runs after tracking and before drawing, so it can also filter the results drawn on the frame.
+    /// An error returned by `on_frame` stops the processing. Returns the number of frames
+    #[allow(clippy::too_many_arguments)]
+    #[cfg(feature = "video-plugin")]
+    pub fn infer_video_with<P, F>(
+        &self,
+        video_path: P,
+        output_path: P,
+        conf_thresh: &ConfThresh,
+        iou_thresh: &IOUThresh,
+        draw_bounding_boxes: DrawBoundingBoxes,
+        mut tracker: Option<&mut tracking::Tracker>,
+        mut on_frame: F,
+    ) -> Result<usize, YoloRuntimeError>
+    where
+        P: AsRef<Path>,
+        F: FnMut(usize, &mut Vec<InferenceResult>) -> Result<(), YoloRuntimeError>,
+    {
         // TODO Maybe check for existence of plugin before attempting to call functions
         debug!("Start Proc Video");
 
-        let mut vec_results_by_frame = Vec::new();
-
         // Checks if input path exists
         if !video_path.as_ref().exists() {
             let error = std::io::Error::from(ErrorKind::NotFound);
             return Err(YoloRuntimeError::from(error))?;
         }
 
-        let file_not_found: Result<Vec<Vec<InferenceResult>>, YoloRuntimeError> = Err(
-            YoloRuntimeError::from(std::io::Error::from(ErrorKind::NotFound)),
-        );
+        let file_not_found: Result<usize, YoloRuntimeError> = Err(YoloRuntimeError::from(
+            std::io::Error::from(ErrorKind::NotFound),
+        ));
 
         let (mut filename, mut output_filename) =
             match (video_path.as_ref().to_str(), output_path.as_ref().to_str()) {
@@ -259,20 +507,41 @@ impl Yolo {
                         None => return Err(YoloRuntimeError::VideoPluginImageWriteError),
                     };
 
-                let vec_results: Vec<InferenceResult> =
+                let mut vec_results: Vec<InferenceResult> =
                     self.infer_image(conf_thresh, iou_thresh, &image_buf)?;
 
-                info!("Processing Frame {idx}, #Detections {}", vec_results.len());
-
-                if let DrawBoundingBoxes::TrueWithFont(font) = &draw_bounding_boxes {
-                    // I am discarding the result as this is a convienence post processing function
-                    // Also available for the user if they wish to use it
-                    let _ = utils::draw_bounding_boxes_on_mut_image(image_buf, &vec_results, font);
+                if let Some(tracker) = tracker.as_deref_mut() {
+                    vec_results = tracker.update(vec_results);
                 }
 
-                vec_results_by_frame.push(vec_results);
-
-                unsafe { yolo_rs_video_plugin::write_frame(idx, buf_ptr_raw, buf_len) };
+                info!("Processing Frame {idx}, #Detections {}", vec_results.len());
+                on_frame(idx as usize, &mut vec_results)?;
+
+                let image_buf = match &draw_bounding_boxes {
+                    #[cfg(feature = "bundled-font")]
+                    DrawBoundingBoxes::Default => {
+                        utils::draw_bounding_boxes(image_buf, &vec_results)
+                    }
+                    DrawBoundingBoxes::TrueWithFont(font) => {
+                        utils::draw_bounding_boxes_on_mut_image(image_buf, &vec_results, font)
+                    }
+                    DrawBoundingBoxes::WithStyle(font, style) => {
+                        utils::draw_bounding_boxes_with_style(image_buf, &vec_results, font, style)
+                    }
+                    DrawBoundingBoxes::Anonymize {
+                        classes,
+                        anonymization,
+                    } => {
+                        utils::anonymize_mut_image(image_buf, &vec_results, classes, *anonymization)
+                    }
+                    DrawBoundingBoxes::False => image_buf,
+                };
+
+                // The buffer was moved into the image, write back from its current location
+                let mut frame_buf = image_buf.into_raw();
+                let frame_ptr_raw = frame_buf.as_mut_ptr() as usize as i32;
+                let frame_len = frame_buf.len() as i32;
+                unsafe { yolo_rs_video_plugin::write_frame(idx, frame_ptr_raw, frame_len) };
             }
         }
 
@@ -293,7 +562,11 @@ impl Yolo {
 
         info!("Finished Encoding Video : {}", output_filename);
 
-        Ok(vec_results_by_frame)
+        if let Some(stats) = self.stats() {
+            info!("Inference statistics:\n{stats}");
+        }
+
+        Ok(frame_count as usize)
     }
 
     /// Function to Infer image buffer
@@ -304,9 +577,12 @@ impl Yolo {
     /// - Sets the input and computes the result from the image
     /// - Processes the output buffer Vec<32> into the required tensor shape of (8400 x 84)
     /// - 8400 detections , 80 possible classes , 4 values for x,y,w,h for the bounding box location
+    /// - (for a 640 x 640 input and 80 classes)
     /// - Apply confidence threshold and scaling to results
     /// - Apply Intersection over union and non-maximual supression to results
     /// - Returns Vec of InferenceResult    
+    ///
+    /// The time spent in each stage is recorded if enabled with [`YoloBuilder::collect_stats`]
     // CONTINE FROM HERE
     pub fn infer_image(
         &self,
@@ -314,9 +590,36 @@ impl Yolo {
         iou_thresh: &IOUThresh,
         image_buffer: &RgbImage,
     ) -> Result<Vec<InferenceResult>, YoloRuntimeError> {
+        let (vec_results, timings) =
+            self.infer_image_timed(conf_thresh, iou_thresh, image_buffer)?;
+
+        if let Some(stats) = &self.stats {
+            stats.borrow_mut().record(timings);
+        }
+        Ok(vec_results)
+    }
+
+    /// Same as [`Yolo::infer_image`], also returning the time spent in each stage of the inference.
+    /// The timings are not recorded in [`Yolo::stats`]
+    pub fn infer_image_timed(
+        &self,
+        conf_thresh: &ConfThresh,
+        iou_thresh: &IOUThresh,
+        image_buffer: &RgbImage,
+    ) -> Result<(Vec<InferenceResult>, StageTimings), YoloRuntimeError> {
+        let mut timings = StageTimings::default();
+
+        let start = Instant::now();
+        let image_buffer = match &self.roi {
+            Some(roi) => {
+                roi.check_image_size(image_buffer.width(), image_buffer.height())?;
+                roi.apply_blackout(image_buffer)?
+            }
+            None => Cow::Borrowed(image_buffer),
+        };
         // TODO Improve performance of this
         let (bytes, resize_scale): ([Vec<Vec<f32>>; 3], ResizeScale) =
-            prepare::pre_process_image(image_buffer)?;
+            prepare::pre_process_image(&image_buffer, &self.input_size, &self.normalization)?;
 
         let tensor_data = bytes
             .into_iter()
@@ -325,35 +628,60 @@ impl Yolo {
             .into_iter()
             .flatten()
             .collect::<Vec<f32>>();
-
-        let mut context = self.graph.init_execution_context()?;
-
-        context.set_input(
-            0,
-            wasi_nn::TensorType::F32,
-            // Input
-            &[1, 3, self::INPUT_WIDTH, INPUT_HEIGHT],
-            &tensor_data,
+        let input = Tensor::new(
+            vec![
+                1,
+                3,
+                self.input_size.height as usize,
+                self.input_size.width as usize,
+            ],
+            tensor_data,
         )?;
-
-        let mut output_buffer = vec![0f32; OUTPUT_OBJECTS * OUTPUT_CLASSES];
+        timings.preprocess = start.elapsed();
 
         // Execute the inference.
-        context.compute()?;
-        context.get_output(0, &mut output_buffer)?;
+        let start = Instant::now();
+        let output = self
+            .backend
+            .infer(&input)?
+            .into_iter()
+            .next()
+            .ok_or(BackendError::MissingOutput)?;
+        timings.compute = start.elapsed();
+
+        // Output is (1 x 84 x 8400), 4 values for the bounding box,
+        // followed by one probability per class, for each candidate detection
+        let num_anchors = match *output.shape() {
+            [1, rows, anchors] | [rows, anchors] if rows == 4 + self.classes.len() => anchors,
+            _ => {
+                return Err(PostProcessingError::OutputShape {
+                    shape: output.shape().to_vec(),
+                    num_classes: self.classes.len(),
+                })?
+            }
+        };
 
         // Process inference results into Vector of Results
-        let output_tensor = process_output_buffer_to_tensor(&output_buffer);
+        let start = Instant::now();
+        let output_tensor = process_output_buffer_to_tensor(output.data(), num_anchors);
+        timings.output_parsing = start.elapsed();
 
-        let vec_results =
+        let start = Instant::now();
+        let mut vec_results =
             apply_confidence_and_scale(output_tensor, conf_thresh, &self.classes, resize_scale);
+        if let Some(roi) = &self.roi {
+            vec_results = roi.filter_results(vec_results);
+        }
+        timings.confidence_filter = start.elapsed();
 
         if vec_results.is_empty() {
-            return Ok(vec_results);
+            return Ok((vec_results, timings));
         }
+        let start = Instant::now();
         let vec_results = non_maximum_supression(iou_thresh, vec_results)?;
+        timings.nms = start.elapsed();
 
-        Ok(vec_results)
+        Ok((vec_results, timings))
     }
 }
 
@@ -363,22 +691,51 @@ pub enum BuildError {
     #[error("Classes must be added to the YoloBuilder before building")]
     MissingClasses,
 
+    #[cfg(feature = "wasi-nn")]
     #[error("error creating Graph")]
     GraphError(#[from] wasi_nn::Error),
 
+    #[error("error loading model into the inference backend")]
+    BackendError(#[from] BackendError),
+
+    #[error("Model cache `{0}` is only available with the WASI-NN backend")]
+    ModelCacheUnavailable(String),
+
     #[error("error reading file containing classes")]
     FileError(#[from] std::io::Error),
 
     #[error("File / bytes provided to rusttype::Font could not be parsed as font")]
     InvalidFontData,
+
+    #[error("error reading model manifest")]
+    ManifestError(#[from] ManifestError),
+
+    #[error("error reading dataset data.yaml")]
+    DatasetError(#[from] dataset::DatasetError),
+
+    #[error("Model files or a model cache name must be added to the YoloBuilder before building")]
+    MissingModel,
+
+    #[error("Only Detection models are supported, got {0:?}")]
+    UnsupportedTask(YoloType),
 }
 
 /// Builder Pattern for Yolo Execution Context
 pub struct YoloBuilder {
-    // inference_type: YoloType
+    inference_type: YoloType,
+    #[cfg(feature = "wasi-nn")]
     graph_encoding: GraphEncoding,
+    #[cfg(feature = "wasi-nn")]
     execution_target: ExecutionTarget,
     classes: Option<Vec<String>>,
+    model_files: Vec<PathBuf>,
+    model_cache_name: Option<String>,
+    input_size: InputSize,
+    normalization: Normalization,
+    conf_thresh: ConfThresh,
+    iou_thresh: IOUThresh,
+    roi: Option<Roi>,
+    collect_stats: bool,
 }
 
 impl Default for YoloBuilder {
@@ -391,18 +748,82 @@ impl YoloBuilder {
     #[inline(always)]
     pub fn new() -> Self {
         YoloBuilder {
+            inference_type: YoloType::Detection,
+            #[cfg(feature = "wasi-nn")]
             graph_encoding: GraphEncoding::Pytorch,
+            #[cfg(feature = "wasi-nn")]
             execution_target: ExecutionTarget::CPU,
             classes: None,
+            model_files: Vec::new(),
+            model_cache_name: None,
+            input_size: InputSize::default(),
+            normalization: Normalization::default(),
+            conf_thresh: ConfThresh::default(),
+            iou_thresh: IOUThresh::default(),
+            roi: None,
+            collect_stats: false,
         }
     }
 
+    /// Creates a builder populated from a model manifest file (`.toml`, `.yaml` or `.yml`),
+    /// see [`manifest`] for the format.
+    /// The model is then loaded with [`YoloBuilder::build`]
+    pub fn from_manifest<P>(manifest_path: P) -> Result<Self, BuildError>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let manifest = ModelManifest::from_file(manifest_path)?;
+        Self::new().manifest(manifest)
+    }
+
+    /// Applies every setting declared in the manifest to the builder,
+    /// settings absent from the manifest are left untouched
+    pub fn manifest(mut self, manifest: ModelManifest) -> Result<Self, BuildError> {
+        if let Some(classes) = manifest.load_classes()? {
+            self.classes = Some(classes);
+        }
+        if let Some(input_size) = manifest.input_size() {
+            self.input_size = input_size;
+        }
+        if !manifest.model.is_empty() {
+            self.model_files = manifest.model;
+        }
+        if let Some(cache_name) = manifest.cache_name {
+            self.model_cache_name = Some(cache_name);
+        }
+        #[cfg(feature = "wasi-nn")]
+        if let Some(encoding) = manifest.encoding {
+            self.graph_encoding = encoding.into();
+        }
+        #[cfg(feature = "wasi-nn")]
+        if let Some(target) = manifest.target {
+            self.execution_target = target.into();
+        }
+        if let Some(task) = manifest.task {
+            self.inference_type = task;
+        }
+        if let Some(normalization) = manifest.normalization {
+            self.normalization = normalization;
+        }
+        if let Some(thresholds) = manifest.thresholds {
+            if let Some(conf) = thresholds.confidence {
+                self.conf_thresh = ConfThresh(conf);
+            }
+            if let Some(iou) = thresholds.iou {
+                self.iou_thresh = IOUThresh(iou);
+            }
+        }
+        Ok(self)
+    }
+
+    #[cfg(feature = "wasi-nn")]
     #[inline(always)]
     pub fn execution_target(mut self, execution_target: ExecutionTarget) -> Self {
         self.execution_target = execution_target;
         self
     }
 
+    #[cfg(feature = "wasi-nn")]
     #[inline(always)]
     pub fn graph_encoding(mut self, graph_encoding: GraphEncoding) -> Self {
         self.graph_encoding = graph_encoding;
@@ -415,6 +836,74 @@ impl YoloBuilder {
         self
     }
 
+    #[inline(always)]
+    pub fn inference_type(mut self, inference_type: YoloType) -> Self {
+        self.inference_type = inference_type;
+        self
+    }
+
+    /// Model file(s) used by [`YoloBuilder::build`]
+    #[inline(always)]
+    pub fn model_files<P>(mut self, files: impl AsRef<[P]>) -> Self
+    where
+        P: AsRef<std::path::Path>,
+    {
+        self.model_files = files
+            .as_ref()
+            .iter()
+            .map(|file| file.as_ref().to_path_buf())
+            .collect();
+        self
+    }
+
+    /// Name of a model preloaded by the host, used by [`YoloBuilder::build`]
+    /// instead of the model files, see [`YoloBuilder::build_from_cache`]
+    #[inline(always)]
+    pub fn model_cache_name(mut self, name: impl Into<String>) -> Self {
+        self.model_cache_name = Some(name.into());
+        self
+    }
+
+    #[inline(always)]
+    pub fn input_size(mut self, input_size: InputSize) -> Self {
+        self.input_size = input_size;
+        self
+    }
+
+    #[inline(always)]
+    pub fn normalization(mut self, normalization: Normalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    /// Default confidence threshold, see [`Yolo::default_conf_thresh`]
+    #[inline(always)]
+    pub fn conf_thresh(mut self, conf_thresh: ConfThresh) -> Self {
+        self.conf_thresh = conf_thresh;
+        self
+    }
+
+    /// Default IoU threshold, see [`Yolo::default_iou_thresh`]
+    #[inline(always)]
+    pub fn iou_thresh(mut self, iou_thresh: IOUThresh) -> Self {
+        self.iou_thresh = iou_thresh;
+        self
+    }
+
+    /// Discard detections outside a region of interest, see [`roi`]
+    #[inline(always)]
+    pub fn roi(mut self, roi: Roi) -> Self {
+        self.roi = Some(roi);
+        self
+    }
+
+    /// Record the time spent in each stage of every inference, see [`Yolo::stats`]
+    #[inline(always)]
+    pub fn collect_stats(mut self, collect_stats: bool) -> Self {
+        self.collect_stats = collect_stats;
+        self
+    }
+
     /// Function that takes a path to a classes text file,
     /// each class is separated by a newline
     #[inline(always)]
@@ -432,36 +921,153 @@ impl YoloBuilder {
         Ok(self)
     }
 
+    /// Takes the classes from the `names` of an ultralytics `data.yaml`,
+    /// e.g. for a model trained on that dataset
+    pub fn data_yaml<P>(mut self, data_yaml_path: P) -> Result<Self, BuildError>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let dataset = dataset::Dataset::from_yaml(data_yaml_path)?;
+        self.classes = Some(dataset.classes().to_vec());
+        Ok(self)
+    }
+
+    /// Builds from the model cache name or model files set with
+    /// [`YoloBuilder::model_cache_name`], [`YoloBuilder::model_files`] or a manifest.
+    /// The cache name takes precedence over the model files
+    #[cfg(any(feature = "wasi-nn", feature = "pure-rust"))]
+    #[inline(always)]
+    pub fn build(mut self) -> Result<Yolo, BuildError> {
+        if let Some(name) = self.model_cache_name.take() {
+            // The model cache belongs to the WASI-NN plugin
+            #[cfg(feature = "pure-rust")]
+            return Err(BuildError::ModelCacheUnavailable(name));
+            #[cfg(not(feature = "pure-rust"))]
+            return self.build_from_cache(&name);
+        }
+        if self.model_files.is_empty() {
+            return Err(BuildError::MissingModel);
+        }
+        let files = std::mem::take(&mut self.model_files);
+        self.build_from_files(files)
+    }
+
+    /// Builds from a model the host has already loaded under `name`,
+    /// e.g. with `wasmedge --nn-preload yolo:PyTorch:CPU:yolov8n.torchscript`.
+    /// The model is read and parsed once by the host, and shared by every wasm instance.
+    /// Always uses the WASI-NN plugin, even with the `pure-rust` feature
+    #[cfg(feature = "wasi-nn")]
+    #[inline(always)]
+    pub fn build_from_cache(self, name: &str) -> Result<Yolo<WasiNnBackend>, BuildError> {
+        let output_shape = self.wasi_nn_output_shape()?;
+        let graph = wasi_nn::GraphBuilder::new(self.graph_encoding, self.execution_target)
+            .build_from_cache(name)?;
+        self.build_with_backend(WasiNnBackend::new(graph, output_shape))
+    }
+
+    #[cfg(all(feature = "wasi-nn", not(feature = "pure-rust")))]
     #[inline(always)]
     pub fn build_from_bytes<B>(self, bytes_array: impl AsRef<[B]>) -> Result<Yolo, BuildError>
     where
         B: AsRef<[u8]>,
     {
-        match self.classes {
-            Some(classes) => {
-                let graph = wasi_nn::GraphBuilder::new(self.graph_encoding, self.execution_target)
-                    .build_from_bytes(bytes_array)?;
+        let output_shape = self.wasi_nn_output_shape()?;
+        let graph = wasi_nn::GraphBuilder::new(self.graph_encoding, self.execution_target)
+            .build_from_bytes(bytes_array)?;
+        self.build_with_backend(WasiNnBackend::new(graph, output_shape))
+    }
 
-                Ok(Yolo::new(graph, classes))
-            }
-            None => Err(BuildError::MissingClasses),
-        }
+    #[cfg(all(feature = "wasi-nn", not(feature = "pure-rust")))]
+    #[inline(always)]
+    pub fn build_from_files<P>(self, files: impl AsRef<[P]>) -> Result<Yolo, BuildError>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let output_shape = self.wasi_nn_output_shape()?;
+        let graph = wasi_nn::GraphBuilder::new(self.graph_encoding, self.execution_target)
+            .build_from_files(files)?;
+        self.build_with_backend(WasiNnBackend::new(graph, output_shape))
+    }
+
+    /// Builds from an ONNX model in memory, run in process by [`backend::TractBackend`].
+    /// Only the first buffer is used, graph encoding and execution target are ignored
+    #[cfg(feature = "pure-rust")]
+    #[inline(always)]
+    pub fn build_from_bytes<B>(self, bytes_array: impl AsRef<[B]>) -> Result<Yolo, BuildError>
+    where
+        B: AsRef<[u8]>,
+    {
+        self.check_before_load()?;
+        let bytes = bytes_array
+            .as_ref()
+            .first()
+            .ok_or(BuildError::MissingModel)?;
+        let backend = backend::TractBackend::from_bytes(bytes.as_ref(), &self.input_size)?;
+        self.build_with_backend(backend)
     }
 
+    /// Builds from an ONNX model file, run in process by [`backend::TractBackend`].
+    /// Only the first file is used, graph encoding and execution target are ignored
+    #[cfg(feature = "pure-rust")]
     #[inline(always)]
     pub fn build_from_files<P>(self, files: impl AsRef<[P]>) -> Result<Yolo, BuildError>
     where
         P: AsRef<std::path::Path>,
     {
+        self.check_before_load()?;
+        let file = files.as_ref().first().ok_or(BuildError::MissingModel)?;
+        let backend = backend::TractBackend::from_file(file, &self.input_size)?;
+        self.build_with_backend(backend)
+    }
+
+    /// Builds a Yolo instance running inference with `backend`,
+    /// e.g. a [`backend::ReplayBackend`] in tests
+    pub fn build_with_backend<B>(self, backend: B) -> Result<Yolo<B>, BuildError>
+    where
+        B: InferenceBackend,
+    {
+        self.check_task()?;
         match self.classes {
-            Some(classes) => {
-                let graph = wasi_nn::GraphBuilder::new(self.graph_encoding, self.execution_target)
-                    .build_from_files(files)?;
-                Ok(Yolo::new(graph, classes))
-            }
+            Some(classes) => Ok(Yolo {
+                input_size: self.input_size,
+                normalization: self.normalization,
+                conf_thresh: self.conf_thresh,
+                iou_thresh: self.iou_thresh,
+                roi: self.roi,
+                stats: self.collect_stats.then(RefCell::default),
+                ..Yolo::with_backend(backend, classes)
+            }),
+            None => Err(BuildError::MissingClasses),
+        }
+    }
+
+    /// Checked before loading the graph, which is expensive
+    #[cfg(feature = "wasi-nn")]
+    fn wasi_nn_output_shape(&self) -> Result<Vec<usize>, BuildError> {
+        self.check_task()?;
+        match &self.classes {
+            Some(classes) => Ok(detection_output_shape(&self.input_size, classes.len())),
             None => Err(BuildError::MissingClasses),
         }
     }
+
+    /// Checked before loading and optimizing the model, which is expensive
+    #[cfg(feature = "pure-rust")]
+    fn check_before_load(&self) -> Result<(), BuildError> {
+        self.check_task()?;
+        match self.classes {
+            Some(_) => Ok(()),
+            None => Err(BuildError::MissingClasses),
+        }
+    }
+
+    // TODO: remove once Pose and Segment post processing is implemented
+    fn check_task(&self) -> Result<(), BuildError> {
+        match self.inference_type {
+            YoloType::Detection => Ok(()),
+            task => Err(BuildError::UnsupportedTask(task)),
+        }
+    }
 }
 
 /// Struct to hold Bounding box, Class and Confidence result from inference
@@ -469,5 +1075,49 @@ impl YoloBuilder {
 pub struct InferenceResult {
     b_box: Rect,
     class: String,
+    class_id: usize,
     confidence: f32,
+    track_id: Option<u64>,
+}
+
+impl InferenceResult {
+    /// Detection without a track, [`Tracker::update`](tracking::Tracker::update) assigns one
+    pub fn new(b_box: Rect, class: impl Into<String>, class_id: usize, confidence: f32) -> Self {
+        InferenceResult {
+            b_box,
+            class: class.into(),
+            class_id,
+            confidence,
+            track_id: None,
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn with_track_id(self, track_id: Option<u64>) -> Self {
+        InferenceResult { track_id, ..self }
+    }
+
+    /// Bounding box in pixel coordinates of the input image
+    pub fn b_box(&self) -> Rect {
+        self.b_box
+    }
+
+    pub fn class(&self) -> &str {
+        &self.class
+    }
+
+    /// Index of the class in [`Yolo::classes`]
+    pub fn class_id(&self) -> usize {
+        self.class_id
+    }
+
+    pub fn confidence(&self) -> f32 {
+        self.confidence
+    }
+
+    /// Id of the track this detection belongs to, set by [`Tracker::update`](tracking::Tracker::update)
+    /// once the track is confirmed
+    pub fn track_id(&self) -> Option<u64> {
+        self.track_id
+    }
 }
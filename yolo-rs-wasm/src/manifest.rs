@@ -0,0 +1,304 @@
+//! Model manifest files
+//!
+//! A manifest is a small TOML or YAML file that lives next to a model and describes
+//! everything needed to run it, so that the model path, classes and thresholds
+//! do not have to be passed around separately.
+//!
+//! ```toml
+//! model = ["yolov8n.torchscript"]
+//! # cache_name = "yolov8n"
+//! encoding = "pytorch"
+//! target = "cpu"
+//! classes_file = "class_names"
+//! input_size = [640, 640]
+//! task = "detection"
+//!
+//! [normalization]
+//! mean = [0.0, 0.0, 0.0]
+//! std = [1.0, 1.0, 1.0]
+//!
+//! [thresholds]
+//! confidence = 0.5
+//! iou = 0.5
+//! ```
+//!
+//! Relative paths in the manifest are resolved against the directory containing the manifest.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use serde::{
+    de::{value, IntoDeserializer},
+    Deserialize,
+};
+#[cfg(feature = "wasi-nn")]
+use wasi_nn::{ExecutionTarget, GraphEncoding};
+
+use crate::{InputSize, Normalization, YoloType};
+
+/// Error emitted while reading a model manifest
+#[derive(thiserror::Error, Debug)]
+pub enum ManifestError {
+    #[error("error reading manifest file")]
+    FileError(#[from] std::io::Error),
+
+    #[error("error parsing TOML manifest")]
+    Toml(#[from] toml::de::Error),
+
+    #[error("error parsing YAML manifest")]
+    Yaml(#[from] serde_yaml::Error),
+
+    #[error("manifest file extension must be one of .toml, .yaml or .yml")]
+    UnknownFormat,
+
+    #[error("manifest must declare either `classes` or `classes_file`, not both")]
+    AmbiguousClasses,
+}
+
+/// Graph encoding as written in a manifest, only used by the WASI-NN backend
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ManifestEncoding {
+    Openvino,
+    Onnx,
+    Tensorflow,
+    Pytorch,
+    #[serde(alias = "tflite")]
+    TensorflowLite,
+    Autodetect,
+}
+
+/// Parses the names used in manifests, e.g. `pytorch` or `tflite`
+impl FromStr for ManifestEncoding {
+    type Err = value::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::deserialize(s.into_deserializer())
+    }
+}
+
+#[cfg(feature = "wasi-nn")]
+impl From<ManifestEncoding> for GraphEncoding {
+    fn from(encoding: ManifestEncoding) -> Self {
+        match encoding {
+            ManifestEncoding::Openvino => GraphEncoding::Openvino,
+            ManifestEncoding::Onnx => GraphEncoding::Onnx,
+            ManifestEncoding::Tensorflow => GraphEncoding::Tensorflow,
+            ManifestEncoding::Pytorch => GraphEncoding::Pytorch,
+            ManifestEncoding::TensorflowLite => GraphEncoding::TensorflowLite,
+            ManifestEncoding::Autodetect => GraphEncoding::Autodetec,
+        }
+    }
+}
+
+/// Execution target as written in a manifest, only used by the WASI-NN backend
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ManifestTarget {
+    Cpu,
+    Gpu,
+    Tpu,
+    Auto,
+}
+
+/// Parses the names used in manifests, e.g. `cpu` or `gpu`
+impl FromStr for ManifestTarget {
+    type Err = value::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::deserialize(s.into_deserializer())
+    }
+}
+
+#[cfg(feature = "wasi-nn")]
+impl From<ManifestTarget> for ExecutionTarget {
+    fn from(target: ManifestTarget) -> Self {
+        match target {
+            ManifestTarget::Cpu => ExecutionTarget::CPU,
+            ManifestTarget::Gpu => ExecutionTarget::GPU,
+            ManifestTarget::Tpu => ExecutionTarget::TPU,
+            ManifestTarget::Auto => ExecutionTarget::AUTO,
+        }
+    }
+}
+
+/// Default confidence and IoU thresholds declared in a manifest
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ManifestThresholds {
+    pub confidence: Option<f32>,
+    pub iou: Option<f32>,
+}
+
+/// Contents of a model manifest, see the [module documentation](self) for the format
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ModelManifest {
+    /// Model file(s), passed to the graph builder in order
+    #[serde(default)]
+    pub model: Vec<PathBuf>,
+    /// Name of a model preloaded by the host (`wasmedge --nn-preload`),
+    /// used instead of `model` when present
+    pub cache_name: Option<String>,
+    pub encoding: Option<ManifestEncoding>,
+    pub target: Option<ManifestTarget>,
+    /// Class names, in the order of the model output
+    pub classes: Option<Vec<String>>,
+    /// Path to a file containing one class name per line
+    pub classes_file: Option<PathBuf>,
+    /// Model input size as `[width, height]`
+    pub input_size: Option<[u32; 2]>,
+    pub task: Option<YoloType>,
+    pub normalization: Option<Normalization>,
+    pub thresholds: Option<ManifestThresholds>,
+}
+
+impl ModelManifest {
+    /// Reads a manifest from a `.toml`, `.yaml` or `.yml` file,
+    /// and resolves the paths it contains relative to the manifest's directory
+    pub fn from_file<P: AsRef<Path>>(manifest_path: P) -> Result<Self, ManifestError> {
+        let path = manifest_path.as_ref();
+        let contents = fs::read_to_string(path)?;
+
+        let mut manifest = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::from_toml_str(&contents)?,
+            Some("yaml") | Some("yml") => Self::from_yaml_str(&contents)?,
+            _ => return Err(ManifestError::UnknownFormat),
+        };
+
+        if let Some(base_dir) = path.parent() {
+            manifest.resolve_paths(base_dir);
+        }
+        Ok(manifest)
+    }
+
+    /// Parses a manifest in TOML format, paths are left as written
+    pub fn from_toml_str(contents: &str) -> Result<Self, ManifestError> {
+        let manifest: ModelManifest = toml::from_str(contents)?;
+        manifest.validate()
+    }
+
+    /// Parses a manifest in YAML format, paths are left as written
+    pub fn from_yaml_str(contents: &str) -> Result<Self, ManifestError> {
+        let manifest: ModelManifest = serde_yaml::from_str(contents)?;
+        manifest.validate()
+    }
+
+    /// Reads the class names, either inline or from the classes file
+    pub fn load_classes(&self) -> Result<Option<Vec<String>>, ManifestError> {
+        match (&self.classes, &self.classes_file) {
+            (Some(classes), None) => Ok(Some(classes.clone())),
+            (None, Some(classes_file)) => Ok(Some(
+                fs::read_to_string(classes_file)?
+                    .lines()
+                    .map(String::from)
+                    .collect(),
+            )),
+            (None, None) => Ok(None),
+            (Some(_), Some(_)) => Err(ManifestError::AmbiguousClasses),
+        }
+    }
+
+    pub fn input_size(&self) -> Option<InputSize> {
+        self.input_size
+            .map(|[width, height]| InputSize::new(width, height))
+    }
+
+    fn validate(self) -> Result<Self, ManifestError> {
+        if self.classes.is_some() && self.classes_file.is_some() {
+            return Err(ManifestError::AmbiguousClasses);
+        }
+        Ok(self)
+    }
+
+    fn resolve_paths(&mut self, base_dir: &Path) {
+        for model in self.model.iter_mut() {
+            *model = base_dir.join(&model);
+        }
+        if let Some(classes_file) = self.classes_file.as_mut() {
+            *classes_file = base_dir.join(&classes_file);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ManifestEncoding, ManifestError, ManifestTarget, ModelManifest};
+    use crate::{InputSize, YoloType};
+    use std::path::Path;
+
+    #[test]
+    fn test_parse_toml_manifest() {
+        let manifest = ModelManifest::from_toml_str(
+            r#"
+            model = ["yolov8n.onnx"]
+            encoding = "onnx"
+            target = "gpu"
+            classes = ["person", "bicycle"]
+            input_size = [320, 256]
+            task = "detection"
+
+            [thresholds]
+            confidence = 0.25
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(manifest.model, vec![Path::new("yolov8n.onnx")]);
+        assert_eq!(manifest.encoding, Some(ManifestEncoding::Onnx));
+        assert_eq!(manifest.target, Some(ManifestTarget::Gpu));
+        assert_eq!(manifest.input_size(), Some(InputSize::new(320, 256)));
+        assert_eq!(manifest.task, Some(YoloType::Detection));
+        assert_eq!(manifest.thresholds.unwrap().confidence, Some(0.25));
+        assert_eq!(manifest.thresholds.unwrap().iou, None);
+        assert_eq!(
+            manifest.load_classes().unwrap(),
+            Some(vec!["person".to_string(), "bicycle".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_parse_yaml_manifest() {
+        let manifest = ModelManifest::from_yaml_str(
+            "
+model: [yolov8n.torchscript]
+encoding: tflite
+classes_file: class_names
+normalization:
+  mean: [0.485, 0.456, 0.406]
+  std: [0.229, 0.224, 0.225]
+",
+        )
+        .unwrap();
+
+        assert_eq!(manifest.encoding, Some(ManifestEncoding::TensorflowLite));
+        assert_eq!(manifest.normalization.unwrap().mean, [0.485, 0.456, 0.406]);
+        assert_eq!(manifest.classes_file.unwrap(), Path::new("class_names"));
+    }
+
+    #[test]
+    fn test_parse_manifest_names() {
+        assert_eq!(
+            "tflite".parse::<ManifestEncoding>().unwrap(),
+            ManifestEncoding::TensorflowLite
+        );
+        assert_eq!(
+            "gpu".parse::<ManifestTarget>().unwrap(),
+            ManifestTarget::Gpu
+        );
+        assert!("cuda".parse::<ManifestTarget>().is_err());
+    }
+
+    #[test]
+    fn test_manifest_rejects_ambiguous_classes() {
+        let manifest = ModelManifest::from_toml_str(
+            r#"
+            classes = ["person"]
+            classes_file = "class_names"
+            "#,
+        );
+        assert!(matches!(manifest, Err(ManifestError::AmbiguousClasses)));
+    }
+}
@@ -1,8 +1,8 @@
 use image::{GenericImage, ImageError, RgbImage};
 
+use crate::{InputSize, Normalization};
+
 // Function to normalize and resize image for YOLO
-const SIZE: usize = 640;
-const SIZE_U32: u32 = 640;
 type Channel = Vec<Vec<f32>>;
 
 #[derive(Debug)]
@@ -11,23 +11,20 @@ pub struct ResizeScale(pub f32);
 /// This Function maps an RGB image into a single Vector of Floating point numbers
 pub(crate) fn pre_process_image(
     image: &RgbImage,
+    input_size: &InputSize,
+    normalization: &Normalization,
 ) -> Result<([Channel; 3], ResizeScale), ImageError> {
     let input_width = image.width();
     let input_height = image.height();
 
-    let (height, width);
-    let length = input_height.max(input_width) as f32;
-    let resize_scale = ResizeScale(length / SIZE as f32);
+    // Scale by the side that overflows the model input the most,
+    // so that the whole image fits into the input tensor
+    let scale = (input_width as f32 / input_size.width as f32)
+        .max(input_height as f32 / input_size.height as f32);
+    let resize_scale = ResizeScale(scale);
 
-    if input_width > input_height {
-        // height is the shorter length
-        height = SIZE_U32 * input_height / input_width;
-        width = SIZE_U32;
-    } else {
-        // width is the shorter length
-        width = SIZE_U32 * input_width / input_height;
-        height = SIZE_U32;
-    }
+    let width = ((input_width as f32 / scale).round() as u32).clamp(1, input_size.width);
+    let height = ((input_height as f32 / scale).round() as u32).clamp(1, input_size.height);
 
     let resized: image::ImageBuffer<image::Rgb<u8>, Vec<u8>> = image::imageops::resize(
         image,
@@ -36,25 +33,27 @@ pub(crate) fn pre_process_image(
         ::image::imageops::FilterType::Triangle,
     );
 
-    // We need the image to fit the 640 x 640 size,
+    // We need the image to fit the input size of the model,
     // and we want to keep the aspect ratio of the original image
     // So we fill the remaining pixels with black,
-    let mut resized_640x640 = RgbImage::new(SIZE_U32, SIZE_U32);
-    resized_640x640.copy_from(&resized, 0, 0)?;
+    let mut resized_input = RgbImage::new(input_size.width, input_size.height);
+    resized_input.copy_from(&resized, 0, 0)?;
 
     // Split intoChannels
-    let mut red: Channel = vec![vec![0.0; SIZE]; SIZE];
-    let mut blue: Channel = vec![vec![0.0; SIZE]; SIZE];
-    let mut green: Channel = vec![vec![0.0; SIZE]; SIZE];
+    let (rows, cols) = (input_size.height as usize, input_size.width as usize);
+    let mut red: Channel = vec![vec![0.0; cols]; rows];
+    let mut blue: Channel = vec![vec![0.0; cols]; rows];
+    let mut green: Channel = vec![vec![0.0; cols]; rows];
 
-    for (_, pixel) in resized_640x640.enumerate_rows() {
+    let Normalization { mean, std } = normalization;
+    for (_, pixel) in resized_input.enumerate_rows() {
         for (x, y, rgb) in pixel {
             let x = x as usize;
             let y = y as usize;
 
-            red[y][x] = rgb.0[0] as f32 / 255.0;
-            green[y][x] = rgb.0[1] as f32 / 255.0;
-            blue[y][x] = rgb.0[2] as f32 / 255.0;
+            red[y][x] = (rgb.0[0] as f32 / 255.0 - mean[0]) / std[0];
+            green[y][x] = (rgb.0[1] as f32 / 255.0 - mean[1]) / std[1];
+            blue[y][x] = (rgb.0[2] as f32 / 255.0 - mean[2]) / std[2];
         }
     }
 
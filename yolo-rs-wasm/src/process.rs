@@ -9,12 +9,12 @@ use ndarray::{s, Array2, ArrayView, Axis, Dim, Zip};
 /// Function to process output tensor from YOLOv8 Detection Model
 // TODO: more efficient parsing: remove transpose convert from buffer directly to
 // 2D vector
-pub fn process_output_buffer_to_tensor(buffer: &[f32]) -> Vec<Vec<f32>> {
+pub fn process_output_buffer_to_tensor(buffer: &[f32], num_anchors: usize) -> Vec<Vec<f32>> {
     // Output buffer is in format
-    // 8400 x 84 as a single Vec of f32
+    // 8400 x 84 as a single Vec of f32 (for a 640 x 640 input and 80 classes)
     // i.e. [x1,x2,x3,..,x8400, y1,y2,y3,...,y84000,]
     let mut columns = Vec::new();
-    for col_slice in buffer.chunks_exact(8400) {
+    for col_slice in buffer.chunks_exact(num_anchors) {
         let col_vec = col_slice.to_vec();
         columns.push(col_vec);
     }
@@ -36,7 +36,7 @@ pub(crate) fn apply_confidence_and_scale(
     scale: ResizeScale,
 ) -> Vec<InferenceResult> {
     let mut results = Vec::new();
-    for (_, row) in rows.iter().enumerate() {
+    for row in rows.iter() {
         // Get maximum likeliehood for each detection
         // Iterator of only class probabilities
         // Skip [x,y,w,h]
@@ -59,12 +59,12 @@ pub(crate) fn apply_confidence_and_scale(
             continue;
         }
 
-        let class = match prob_iter
+        let (class_id, class) = match prob_iter
             .into_iter()
             .position(|element| *element == max)
-            .and_then(|idx| classes.get(idx))
+            .and_then(|idx| Some((idx, classes.get(idx)?)))
         {
-            Some(x) => x.to_string(),
+            Some((idx, x)) => (idx, x.to_string()),
             None => {
                 continue;
             }
@@ -78,11 +78,12 @@ pub(crate) fn apply_confidence_and_scale(
         let w = (row[2] * scale.0).round() as u32;
         let h = (row[3] * scale.0).round() as u32;
 
-        results.push(InferenceResult {
-            b_box: Rect::at(x as i32, y as i32).of_size(w, h),
-            confidence: max,
+        results.push(InferenceResult::new(
+            Rect::at(x as i32, y as i32).of_size(w, h),
             class,
-        });
+            class_id,
+            max,
+        ));
     }
     results
 }
@@ -169,9 +170,12 @@ pub(crate) fn non_maximum_supression(
         }
     }
 
+    // Keep the results sorted by descending confidence
     let mut keepers = Vec::new();
-    for elem in keep {
-        keepers.push(results[elem as usize].clone());
+    for (idx, result) in results.iter().enumerate() {
+        if keep.contains(&(idx as i32)) {
+            keepers.push(result.clone());
+        }
     }
 
     // TODO See if i can make use of references and not allocate new arrays
@@ -189,10 +193,33 @@ pub fn _iou(box1: Rect, box2: Rect) -> f32 {
 
     match box1.intersect(box2) {
         Some(intersection) => area(intersection) as f32 / (area_boxes - area(intersection)) as f32,
-        None => 1.,
+        None => 0.,
     }
 }
 
+/// Converts a rectangle to [x1,y1,x2,y2], with exclusive right and bottom edges
+pub(crate) fn rect_to_xyxy(b_box: Rect) -> [f32; 4] {
+    [
+        b_box.left() as f32,
+        b_box.top() as f32,
+        (b_box.left() + b_box.width() as i32) as f32,
+        (b_box.top() + b_box.height() as i32) as f32,
+    ]
+}
+
+/// Intersection over union of two boxes in [x1,y1,x2,y2] format, 0 when they do not overlap
+pub(crate) fn iou_xyxy(box1: [f32; 4], box2: [f32; 4]) -> f32 {
+    let area = |b: [f32; 4]| (b[2] - b[0]).max(0.) * (b[3] - b[1]).max(0.);
+    let inter_w = (box1[2].min(box2[2]) - box1[0].max(box2[0])).max(0.);
+    let inter_h = (box1[3].min(box2[3]) - box1[1].max(box2[1])).max(0.);
+    let intersection = inter_w * inter_h;
+    let union = area(box1) + area(box2) - intersection;
+    if union <= 0. {
+        return 0.;
+    }
+    intersection / union
+}
+
 /// Converts a bounding box to an ArrayBase
 /// Map [x1,y1,x2,y2] -> to ArrayBase<OwnedRepr<A>, D>
 pub fn bboxes_to_ndarray(arr_b_boxes: Vec<[f64; 4]>) -> Array2<f64> {
@@ -210,7 +237,7 @@ pub fn bboxes_to_ndarray(arr_b_boxes: Vec<[f64; 4]>) -> Array2<f64> {
 }
 
 /// Vectorized version of the Intersection Over Union Algorithm
-///
+/// Returns the (len(boxes_a) x len(boxes_b)) matrix of the IoU of every pair of boxes
 pub fn vectorized_iou(
     boxes_a: Array2<f64>,
     boxes_b: Array2<f64>,
@@ -219,7 +246,8 @@ pub fn vectorized_iou(
 
     let box_area =
         |bbox: ArrayView<f64, Dim<[usize; 1]>>| (bbox[2] - bbox[0]) * (bbox[3] - bbox[1]);
-    let (num_boxes, _elems_per_box) = boxes_a.dim();
+    let (num_boxes_a, _elems_per_box) = boxes_a.dim();
+    let (num_boxes_b, _elems_per_box) = boxes_b.dim();
 
     let area_a = boxes_a.map_axis(Axis(1), box_area);
     let area_b = boxes_b.map_axis(Axis(1), box_area);
@@ -228,7 +256,7 @@ pub fn vectorized_iou(
     // let boxes_b_new_axis = boxes_b.clone();
     let a_top_left = boxes_a_new_axis.slice(s![.., .., ..2]);
 
-    let a_top_left_bc = a_top_left.broadcast((num_boxes, num_boxes, 2)).ok_or(
+    let a_top_left_bc = a_top_left.broadcast((num_boxes_a, num_boxes_b, 2)).ok_or(
         YoloRuntimeError::PostProcessingError(PostProcessingError::BroadcastArrayDims),
     )?;
     let b_top_left = boxes_b.slice(s![.., ..2]);
@@ -241,15 +269,17 @@ pub fn vectorized_iou(
     let a_bot_right = boxes_a_new_axis.slice(s![.., .., 2..]);
     let b_bot_right = boxes_b.slice(s![.., 2..]);
     // TODO Remove Unwrap in case of Impossible Broadcast
-    let a_bot_right_bc = a_bot_right.broadcast((num_boxes, num_boxes, 2)).unwrap();
+    let a_bot_right_bc = a_bot_right
+        .broadcast((num_boxes_a, num_boxes_b, 2))
+        .unwrap();
 
     // Elementwise minumum
     let bottom_right = Zip::from(a_bot_right_bc)
         .and_broadcast(&b_bot_right)
         .map_collect(|x, &y| x.min(y));
 
-    // Difference between right and bottom left
-    let bot_right_top_left = bottom_right - top_left;
+    // Difference between right and bottom left, boxes that do not overlap have no intersection
+    let bot_right_top_left = (bottom_right - top_left).mapv(|x| x.max(0.));
     let area_inter = bot_right_top_left.map_axis(Axis(2), |x| x.product());
     let iou = area_inter.clone() / (area_a.insert_axis(Axis(1)) + area_b - area_inter);
     Ok(iou)
@@ -257,7 +287,10 @@ pub fn vectorized_iou(
 
 #[cfg(test)]
 mod tests {
-    use crate::process::{_iou, bboxes_to_ndarray, vectorized_iou};
+    use crate::process::{
+        _iou, bboxes_to_ndarray, iou_xyxy, non_maximum_supression, vectorized_iou,
+    };
+    use crate::{IOUThresh, InferenceResult};
     use imageproc::rect::Rect;
     use ndarray::array;
 
@@ -279,6 +312,19 @@ mod tests {
         let iou_out = _iou(box1, box2);
 
         assert_eq!(iou_out, 0.2857143);
+
+        // no overlap
+        let box2 = Rect::at(10, 10).of_size(3, 3);
+        assert_eq!(_iou(box1, box2), 0.);
+    }
+
+    #[test]
+    fn test_iou_xyxy() {
+        assert_eq!(iou_xyxy([1., 1., 3., 3.], [2., 2., 3., 3.]), 0.25);
+        // no overlap
+        assert_eq!(iou_xyxy([0., 0., 1., 1.], [5., 5., 6., 6.]), 0.);
+        // degenerate boxes
+        assert_eq!(iou_xyxy([0., 0., 0., 0.], [0., 0., 0., 0.]), 0.);
     }
 
     // Testing with 2 bounding boxes
@@ -310,9 +356,45 @@ mod tests {
 
         assert_eq!(expected_iou, actual_iou);
     }
+
+    // Testing with a different number of boxes on each side, some without any overlap
+    #[test]
+    fn test_vectorized_iou_disjoint() {
+        let boxes_a = bboxes_to_ndarray(vec![[0., 0., 2., 2.], [10., 10., 12., 12.]]);
+        let boxes_b = bboxes_to_ndarray(vec![
+            [1., 0., 3., 2.],
+            [20., 20., 22., 22.],
+            [0., 0., 2., 2.],
+        ]);
+        let expected_iou = array!([1. / 3., 0., 1.], [0., 0., 0.]);
+        let actual_iou = vectorized_iou(boxes_a, boxes_b).unwrap();
+
+        assert_eq!(expected_iou, actual_iou);
+    }
     #[test]
     fn test_nms() {
-        // write tests for non-maximum supression
-        todo!();
+        let result = |x: i32, class: &str, confidence: f32| {
+            InferenceResult::new(Rect::at(x, 0).of_size(10, 10), class, 0, confidence)
+        };
+
+        let results = vec![
+            result(1, "person", 0.6),
+            result(0, "person", 0.9),
+            // no overlap with the other people
+            result(50, "person", 0.7),
+            // same box as the best person, but a different class
+            result(0, "car", 0.8),
+        ];
+
+        let kept = non_maximum_supression(&IOUThresh(0.5), results).unwrap();
+        let kept = kept
+            .iter()
+            .map(|r| (r.b_box.left(), r.class.as_str(), r.confidence))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            kept,
+            vec![(0, "person", 0.9), (0, "car", 0.8), (50, "person", 0.7)]
+        );
     }
 }
@@ -0,0 +1,283 @@
+//! Region of interest of a fixed camera
+//!
+//! Detections outside the [`Roi`] are discarded by [`Yolo::infer_image`](crate::Yolo::infer_image),
+//! either when their center is outside, or when too little of their box overlaps the region.
+//! Optionally the pixels outside the region are blacked out before pre processing,
+//! so that the model does not see them at all.
+//!
+//! ```rust,ignore
+//! // Ignore the sky and the neighbouring parking lot
+//! let roi = Roi::from_polygons([Polygon::new([(0., 300.), (1280., 300.), (1280., 720.), (0., 720.)])])
+//!     .with_filter(RoiFilter::Overlap(0.5))
+//!     .with_blackout(true);
+//! let yolo = YoloBuilder::new().roi(roi).classes_file(classes_path)?.build_from_files([model_path])?;
+//! ```
+
+use std::{borrow::Cow, cell::RefCell};
+
+use image::{GrayImage, Luma, RgbImage};
+use imageproc::rect::Rect;
+
+use crate::{
+    geometry::{Point, Polygon},
+    InferenceResult,
+};
+
+/// Maximum number of points sampled along each side of a bounding box to estimate its overlap
+const OVERLAP_SAMPLES: u32 = 32;
+
+#[derive(thiserror::Error, Debug)]
+pub enum RoiError {
+    #[error("region of interest mask is {mask:?}, the image is {image:?}")]
+    MaskSize { mask: (u32, u32), image: (u32, u32) },
+}
+
+/// Shape of the region
+#[derive(Debug, Clone, PartialEq)]
+pub enum RoiRegion {
+    /// Union of polygons in image coordinates
+    Polygons(Vec<Polygon>),
+    /// Binary mask the size of the image, non zero pixels are inside the region
+    Mask(GrayImage),
+}
+
+/// When a detection is considered inside the region
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum RoiFilter {
+    /// The center of the bounding box is inside the region
+    #[default]
+    Center,
+    /// At least this fraction of the bounding box area, in [0, 1], is inside the region
+    Overlap(f32),
+}
+
+/// Region of interest, see the [module documentation](self)
+#[derive(Debug, Clone)]
+pub struct Roi {
+    region: RoiRegion,
+    filter: RoiFilter,
+    blackout: bool,
+    /// Polygons rasterized for the size of the last blacked out image
+    polygons_mask: RefCell<Option<GrayImage>>,
+}
+
+impl PartialEq for Roi {
+    fn eq(&self, other: &Self) -> bool {
+        self.region == other.region
+            && self.filter == other.filter
+            && self.blackout == other.blackout
+    }
+}
+
+impl Roi {
+    pub fn new(region: RoiRegion) -> Self {
+        Roi {
+            region,
+            filter: RoiFilter::default(),
+            blackout: false,
+            polygons_mask: RefCell::new(None),
+        }
+    }
+
+    pub fn from_polygons(polygons: impl IntoIterator<Item = Polygon>) -> Self {
+        Self::new(RoiRegion::Polygons(polygons.into_iter().collect()))
+    }
+
+    /// Region from a binary mask the size of the image, non zero pixels are inside the region.
+    /// Inference fails with [`RoiError::MaskSize`] on images of another size
+    pub fn from_mask(mask: GrayImage) -> Self {
+        Self::new(RoiRegion::Mask(mask))
+    }
+
+    pub fn with_filter(mut self, filter: RoiFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Black out the pixels outside the region before pre processing
+    pub fn with_blackout(mut self, blackout: bool) -> Self {
+        self.blackout = blackout;
+        self
+    }
+
+    pub fn region(&self) -> &RoiRegion {
+        &self.region
+    }
+
+    pub fn filter(&self) -> RoiFilter {
+        self.filter
+    }
+
+    pub fn blackout(&self) -> bool {
+        self.blackout
+    }
+
+    pub fn contains(&self, point: Point) -> bool {
+        match &self.region {
+            RoiRegion::Polygons(polygons) => polygons.iter().any(|polygon| polygon.contains(point)),
+            RoiRegion::Mask(mask) => {
+                if point.x < 0. || point.y < 0. {
+                    return false;
+                }
+                mask.get_pixel_checked(point.x as u32, point.y as u32)
+                    .is_some_and(|pixel| pixel.0[0] != 0)
+            }
+        }
+    }
+
+    /// Fraction of the area of `b_box` inside the region, estimated on a grid of sample points
+    pub fn overlap(&self, b_box: Rect) -> f32 {
+        let samples_x = b_box.width().min(OVERLAP_SAMPLES);
+        let samples_y = b_box.height().min(OVERLAP_SAMPLES);
+        let step_x = b_box.width() as f32 / samples_x as f32;
+        let step_y = b_box.height() as f32 / samples_y as f32;
+
+        let mut inside = 0;
+        for j in 0..samples_y {
+            for i in 0..samples_x {
+                let point = Point::new(
+                    b_box.left() as f32 + (i as f32 + 0.5) * step_x,
+                    b_box.top() as f32 + (j as f32 + 0.5) * step_y,
+                );
+                if self.contains(point) {
+                    inside += 1;
+                }
+            }
+        }
+        inside as f32 / (samples_x * samples_y) as f32
+    }
+
+    /// Whether a detection is inside the region, according to the [`RoiFilter`]
+    pub fn keeps(&self, result: &InferenceResult) -> bool {
+        let b_box = result.b_box();
+        match self.filter {
+            RoiFilter::Center => self.contains(Point::new(
+                b_box.left() as f32 + b_box.width() as f32 / 2.,
+                b_box.top() as f32 + b_box.height() as f32 / 2.,
+            )),
+            RoiFilter::Overlap(min_overlap) => self.overlap(b_box) >= min_overlap,
+        }
+    }
+
+    /// Discards the detections outside the region
+    pub fn filter_results(&self, mut results: Vec<InferenceResult>) -> Vec<InferenceResult> {
+        results.retain(|result| self.keeps(result));
+        results
+    }
+
+    /// Checks that a mask region has the size of the image, polygons fit any image
+    pub fn check_image_size(&self, width: u32, height: u32) -> Result<(), RoiError> {
+        match &self.region {
+            RoiRegion::Mask(mask) if mask.dimensions() != (width, height) => {
+                Err(RoiError::MaskSize {
+                    mask: mask.dimensions(),
+                    image: (width, height),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Copy of the image with the pixels outside the region set to black,
+    /// or the image itself if blackout is disabled.
+    /// Polygons are rasterized once per image size, so that frames of a video are only masked
+    pub fn apply_blackout<'a>(&self, image: &'a RgbImage) -> Result<Cow<'a, RgbImage>, RoiError> {
+        if !self.blackout {
+            return Ok(Cow::Borrowed(image));
+        }
+        self.check_image_size(image.width(), image.height())?;
+
+        let mut polygons_mask = self.polygons_mask.borrow_mut();
+        let mask = match &self.region {
+            RoiRegion::Mask(mask) => mask,
+            RoiRegion::Polygons(_) => {
+                if polygons_mask
+                    .as_ref()
+                    .is_none_or(|mask| mask.dimensions() != image.dimensions())
+                {
+                    *polygons_mask =
+                        Some(GrayImage::from_fn(image.width(), image.height(), |x, y| {
+                            let inside = self.contains(Point::new(x as f32 + 0.5, y as f32 + 0.5));
+                            Luma([if inside { 255 } else { 0 }])
+                        }));
+                }
+                polygons_mask.as_ref().expect("mask rasterized above")
+            }
+        };
+
+        let mut masked = image.clone();
+        for (pixel, mask) in masked.pixels_mut().zip(mask.pixels()) {
+            if mask.0[0] == 0 {
+                pixel.0 = [0, 0, 0];
+            }
+        }
+        Ok(Cow::Owned(masked))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Roi, RoiError, RoiFilter};
+    use crate::{geometry::Polygon, InferenceResult};
+    use image::{GrayImage, Luma, Rgb, RgbImage};
+    use imageproc::rect::Rect;
+
+    fn detection(x: i32, y: i32) -> InferenceResult {
+        InferenceResult::new(Rect::at(x, y).of_size(20, 20), "car", 0, 0.9)
+    }
+
+    #[test]
+    fn test_roi_filters() {
+        // bottom half of a 100 x 100 image
+        let polygon = Polygon::new([(0., 50.), (100., 50.), (100., 100.), (0., 100.)]);
+        let mut mask = GrayImage::new(100, 100);
+        for y in 50..100 {
+            for x in 0..100 {
+                mask.put_pixel(x, y, Luma([255]));
+            }
+        }
+
+        // box straddling the edge with 3/4 of its area inside
+        let straddling = detection(10, 45);
+        for roi in [Roi::from_polygons([polygon]), Roi::from_mask(mask)] {
+            assert!(roi.keeps(&straddling));
+            assert!((roi.overlap(straddling.b_box()) - 0.75).abs() < 1e-6);
+            assert!(!roi
+                .clone()
+                .with_filter(RoiFilter::Overlap(0.8))
+                .keeps(&straddling));
+
+            let kept = roi.filter_results(vec![detection(10, 10), detection(10, 70)]);
+            assert_eq!(kept.len(), 1);
+            assert_eq!(kept[0].b_box().top(), 70);
+        }
+    }
+
+    #[test]
+    fn test_roi_blackout() {
+        let image = RgbImage::from_pixel(4, 4, Rgb([255, 255, 255]));
+        let roi = Roi::from_polygons([Polygon::new([(0., 0.), (2., 0.), (2., 4.), (0., 4.)])]);
+        assert_eq!(*roi.apply_blackout(&image).unwrap(), image);
+
+        let roi = roi.with_blackout(true);
+        let masked = roi.apply_blackout(&image).unwrap().into_owned();
+        assert_eq!(masked.get_pixel(1, 1), &Rgb([255, 255, 255]));
+        assert_eq!(masked.get_pixel(3, 1), &Rgb([0, 0, 0]));
+
+        // the cached mask follows the image size
+        let wide = RgbImage::from_pixel(8, 2, Rgb([255, 255, 255]));
+        let masked = roi.apply_blackout(&wide).unwrap().into_owned();
+        assert_eq!(masked.get_pixel(1, 1), &Rgb([255, 255, 255]));
+        assert_eq!(masked.get_pixel(7, 1), &Rgb([0, 0, 0]));
+
+        // a mask of another size is an error, not a blacked out image
+        let roi = Roi::from_mask(GrayImage::new(2, 2)).with_blackout(true);
+        assert!(matches!(
+            roi.apply_blackout(&image),
+            Err(RoiError::MaskSize {
+                mask: (2, 2),
+                image: (4, 4)
+            })
+        ));
+    }
+}
@@ -0,0 +1,407 @@
+//! Minimal HTTP/1.1 detection server, independent of the socket implementation
+//!
+//! [`DetectionServer`] keeps a single [`Yolo`] loaded and answers one request per connection on any
+//! `Read + Write` stream, e.g. a TCP stream accepted through WasmEdge's WASI sockets,
+//! see `examples/http-server.rs`:
+//!
+//! - `POST /detect` with an image as the body returns `{"width", "height", "detections"}` as JSON,
+//!   the detections as in [`jsonl::Detection`](crate::jsonl::Detection).
+//!   `?format=png` returns the image with the detections drawn instead, `?conf=0.4&iou=0.5` override the
+//!   default thresholds of the model
+//! - `GET /health` returns the classes of the model
+//!
+//! ```sh
+//! curl --data-binary @busy_street.png http://127.0.0.1:8080/detect
+//! curl --data-binary @busy_street.png -o annotated.png "http://127.0.0.1:8080/detect?format=png&conf=0.4"
+//! ```
+//!
+//! Connections are served one at a time, so a client that stops sending or reading holds every other one.
+//! Set read and write timeouts on the stream before [`DetectionServer::serve`], as the example does.
+//! WASI preview 1 has no socket options: there `TcpStream::set_read_timeout` fails with `Unsupported`
+//! and a stalled client can only be dropped by the client itself or the host.
+
+use std::io::{self, BufRead, BufReader, Cursor, Read, Write};
+
+use image::ImageFormat;
+use rusttype::Font;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::{
+    backend::{DefaultBackend, InferenceBackend},
+    jsonl::Detection,
+    utils::{self, DrawStyle},
+    ConfThresh, IOUThresh, Yolo,
+};
+
+/// Default limit of the size of request bodies, see [`DetectionServer::with_max_body_size`]
+pub const MAX_BODY_SIZE: usize = 32 * 1024 * 1024;
+
+/// Limit of the size of the request line and of each header
+const MAX_LINE_SIZE: usize = 8 * 1024;
+const MAX_HEADERS: usize = 100;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ServerError {
+    #[error("connection error")]
+    Io(#[from] io::Error),
+
+    #[error("malformed request: {0}")]
+    BadRequest(&'static str),
+
+    #[error("request body is larger than {0} bytes")]
+    TooLarge(usize),
+}
+
+/// An HTTP request, with its body read in full
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub method: String,
+    /// Path without the query string
+    pub path: String,
+    /// Query parameters, which are not percent-decoded
+    pub query: Vec<(String, String)>,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// Reads a request, `None` if the connection is closed before a request line is received.
+    /// Only bodies with a `Content-Length` are supported
+    pub fn read_from<R: BufRead>(
+        reader: &mut R,
+        max_body_size: usize,
+    ) -> Result<Option<Request>, ServerError> {
+        let Some(request_line) = read_line(reader)? else {
+            return Ok(None);
+        };
+        let mut parts = request_line.split_whitespace();
+        let (Some(method), Some(target), Some(_version)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(ServerError::BadRequest("invalid request line"));
+        };
+
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let query = query
+            .split('&')
+            .filter(|param| !param.is_empty())
+            .map(|param| {
+                let (name, value) = param.split_once('=').unwrap_or((param, ""));
+                (name.to_string(), value.to_string())
+            })
+            .collect();
+
+        let mut headers = Vec::new();
+        loop {
+            let line = read_line(reader)?.ok_or(ServerError::BadRequest("incomplete headers"))?;
+            if line.is_empty() {
+                break;
+            }
+            if headers.len() == MAX_HEADERS {
+                return Err(ServerError::BadRequest("too many headers"));
+            }
+            let (name, value) = line
+                .split_once(':')
+                .ok_or(ServerError::BadRequest("invalid header"))?;
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+
+        let mut request = Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            query,
+            headers,
+            body: Vec::new(),
+        };
+        if request.header("Transfer-Encoding").is_some() {
+            return Err(ServerError::BadRequest("chunked bodies are not supported"));
+        }
+        let content_length = match request.header("Content-Length") {
+            Some(length) => length
+                .parse::<usize>()
+                .map_err(|_| ServerError::BadRequest("invalid Content-Length"))?,
+            None => 0,
+        };
+        if content_length > max_body_size {
+            return Err(ServerError::TooLarge(max_body_size));
+        }
+        request.body = vec![0; content_length];
+        reader.read_exact(&mut request.body)?;
+        Ok(Some(request))
+    }
+
+    /// Value of a header, matched case-insensitively
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Value of a query parameter
+    pub fn query(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(param, _)| param == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// A line without its line ending, `None` at the end of the stream
+fn read_line<R: BufRead>(reader: &mut R) -> Result<Option<String>, ServerError> {
+    let mut line = Vec::new();
+    reader
+        .take(MAX_LINE_SIZE as u64 + 1)
+        .read_until(b'\n', &mut line)?;
+    if line.is_empty() {
+        return Ok(None);
+    }
+    if line.len() > MAX_LINE_SIZE {
+        return Err(ServerError::BadRequest("line too long"));
+    }
+    let line = String::from_utf8(line).map_err(|_| ServerError::BadRequest("invalid UTF-8"))?;
+    Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()))
+}
+
+/// An HTTP response, sent with `Connection: close`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn json<T: Serialize>(status: u16, value: &T) -> Self {
+        Response {
+            status,
+            content_type: "application/json",
+            body: serde_json::to_vec(value).unwrap_or_default(),
+        }
+    }
+
+    /// `{"error": message}`
+    pub fn error(status: u16, message: &str) -> Self {
+        Self::json(status, &json!({ "error": message }))
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        write!(
+            writer,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.status,
+            reason_phrase(self.status),
+            self.content_type,
+            self.body.len()
+        )?;
+        writer.write_all(&self.body)?;
+        writer.flush()
+    }
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        500 => "Internal Server Error",
+        _ => "",
+    }
+}
+
+/// Body of `POST /detect` responses
+#[derive(Serialize)]
+struct DetectResponse {
+    width: u32,
+    height: u32,
+    detections: Vec<Detection>,
+}
+
+/// Serves detections of a loaded model, see the [module documentation](self)
+pub struct DetectionServer<B = DefaultBackend> {
+    yolo: Yolo<B>,
+    font: Option<Font<'static>>,
+    style: DrawStyle,
+    max_body_size: usize,
+}
+
+impl<B: InferenceBackend> DetectionServer<B> {
+    pub fn new(yolo: Yolo<B>) -> Self {
+        DetectionServer {
+            yolo,
+            #[cfg(feature = "bundled-font")]
+            font: Some(utils::default_font()),
+            #[cfg(not(feature = "bundled-font"))]
+            font: None,
+            style: DrawStyle::default(),
+            max_body_size: MAX_BODY_SIZE,
+        }
+    }
+
+    /// Font of the labels of `?format=png` responses, the bundled font with the `bundled-font` feature
+    pub fn with_font(mut self, font: Font<'static>) -> Self {
+        self.font = Some(font);
+        self
+    }
+
+    pub fn with_style(mut self, style: DrawStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Requests with larger bodies are answered with `413 Payload Too Large`, [`MAX_BODY_SIZE`] by default
+    pub fn with_max_body_size(mut self, max_body_size: usize) -> Self {
+        self.max_body_size = max_body_size;
+        self
+    }
+
+    pub fn yolo(&self) -> &Yolo<B> {
+        &self.yolo
+    }
+
+    /// Reads a request from `stream` and writes the response. Malformed requests are answered
+    /// with an error status, only connection errors are returned, including timeouts of the stream
+    pub fn serve<S: Read + Write>(&self, stream: S) -> Result<(), ServerError> {
+        let mut reader = BufReader::new(stream);
+        let response = match Request::read_from(&mut reader, self.max_body_size) {
+            Ok(Some(request)) => self.handle(&request),
+            Ok(None) => return Ok(()),
+            Err(ServerError::Io(error)) => return Err(ServerError::Io(error)),
+            Err(error @ ServerError::TooLarge(_)) => Response::error(413, &error.to_string()),
+            Err(error) => Response::error(400, &error.to_string()),
+        };
+        response.write_to(reader.get_mut())?;
+        Ok(())
+    }
+
+    pub fn handle(&self, request: &Request) -> Response {
+        match (request.method.as_str(), request.path.as_str()) {
+            ("POST", "/detect") => self.detect(request),
+            ("GET", "/health") => Response::json(
+                200,
+                &json!({ "status": "ok", "classes": self.yolo.classes() }),
+            ),
+            (_, "/detect" | "/health") => Response::error(405, "method not allowed"),
+            _ => Response::error(404, "not found"),
+        }
+    }
+
+    fn detect(&self, request: &Request) -> Response {
+        let conf_thresh = match request.query("conf").map(str::parse::<f32>) {
+            Some(Ok(conf)) => ConfThresh(conf),
+            Some(Err(_)) => return Response::error(400, "invalid conf"),
+            None => self.yolo.default_conf_thresh(),
+        };
+        let iou_thresh = match request.query("iou").map(str::parse::<f32>) {
+            Some(Ok(iou)) => IOUThresh(iou),
+            Some(Err(_)) => return Response::error(400, "invalid iou"),
+            None => self.yolo.default_iou_thresh(),
+        };
+
+        let image = match image::load_from_memory(&request.body) {
+            Ok(image) => image.to_rgb8(),
+            Err(error) => return Response::error(400, &format!("invalid image: {}", error)),
+        };
+        let results = match self.yolo.infer_image(&conf_thresh, &iou_thresh, &image) {
+            Ok(results) => results,
+            Err(error) => return Response::error(500, &format!("inference failed: {}", error)),
+        };
+
+        match request.query("format").unwrap_or("json") {
+            "json" => Response::json(
+                200,
+                &DetectResponse {
+                    width: image.width(),
+                    height: image.height(),
+                    detections: results.iter().map(Detection::from).collect(),
+                },
+            ),
+            "png" => {
+                let Some(font) = &self.font else {
+                    return Response::error(500, "no font to draw the labels");
+                };
+                let annotated =
+                    utils::draw_bounding_boxes_with_style(image, &results, font, &self.style);
+                let mut png = Cursor::new(Vec::new());
+                match annotated.write_to(&mut png, ImageFormat::Png) {
+                    Ok(()) => Response {
+                        status: 200,
+                        content_type: "image/png",
+                        body: png.into_inner(),
+                    },
+                    Err(error) => Response::error(500, &format!("error encoding PNG: {}", error)),
+                }
+            }
+            _ => Response::error(400, "format must be json or png"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DetectionServer;
+    use crate::backend::{one_person_yolo, ReplayBackend};
+    use image::{ImageFormat, RgbImage};
+    use std::io::{self, Cursor, Read, Write};
+
+    /// Connection reading a request from memory and collecting the response
+    struct Connection {
+        request: Cursor<Vec<u8>>,
+        response: Vec<u8>,
+    }
+
+    impl Read for Connection {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.request.read(buf)
+        }
+    }
+
+    impl Write for Connection {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.response.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn request(server: &DetectionServer<ReplayBackend>, head: &str, body: &[u8]) -> String {
+        let mut request =
+            format!("{}\r\nContent-Length: {}\r\n\r\n", head, body.len()).into_bytes();
+        request.extend_from_slice(body);
+        let mut connection = Connection {
+            request: Cursor::new(request),
+            response: Vec::new(),
+        };
+        server.serve(&mut connection).unwrap();
+        String::from_utf8_lossy(&connection.response).to_string()
+    }
+
+    #[test]
+    fn test_detection_server() {
+        let yolo = one_person_yolo();
+        let server = DetectionServer::new(yolo);
+
+        let mut png = Cursor::new(Vec::new());
+        RgbImage::new(64, 64)
+            .write_to(&mut png, ImageFormat::Png)
+            .unwrap();
+        let response = request(&server, "POST /detect HTTP/1.1", png.get_ref());
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("Content-Type: application/json\r\n"));
+        assert!(response.ends_with(
+            r#"{"width":64,"height":64,"detections":[{"class":"person","class_id":0,"confidence":0.9,"bbox":[24,24,16,16],"track_id":null}]}"#
+        ));
+
+        let response = request(&server, "POST /detect?conf=x HTTP/1.1", png.get_ref());
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        let response = request(&server, "POST /detect HTTP/1.1", b"not an image");
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        let response = request(&server, "GET /detect HTTP/1.1", b"");
+        assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+    }
+}
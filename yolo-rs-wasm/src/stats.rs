@@ -0,0 +1,240 @@
+//! Timing of the stages of an inference, and statistics aggregated over many inferences
+
+use std::{collections::VecDeque, fmt, time::Duration};
+
+/// Time spent in each stage of a single [`Yolo::infer_image`](crate::Yolo::infer_image) call
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StageTimings {
+    /// Resizing, normalizing and flattening the image into the input tensor
+    pub preprocess: Duration,
+    /// Setting the input, running the graph and reading back the output
+    pub compute: Duration,
+    /// Reshaping the output buffer into one row per candidate detection
+    pub output_parsing: Duration,
+    /// Discarding candidates below the confidence threshold and scaling boxes back to the image,
+    /// then discarding those outside the region of interest
+    pub confidence_filter: Duration,
+    /// Non maximum supression
+    pub nms: Duration,
+}
+
+impl StageTimings {
+    pub fn total(&self) -> Duration {
+        self.preprocess + self.compute + self.output_parsing + self.confidence_filter + self.nms
+    }
+
+    fn stages(&self) -> [Duration; 5] {
+        [
+            self.preprocess,
+            self.compute,
+            self.output_parsing,
+            self.confidence_filter,
+            self.nms,
+        ]
+    }
+
+    fn from_stages(stages: [Duration; 5]) -> Self {
+        let [preprocess, compute, output_parsing, confidence_filter, nms] = stages;
+        StageTimings {
+            preprocess,
+            compute,
+            output_parsing,
+            confidence_filter,
+            nms,
+        }
+    }
+}
+
+/// Number of most recent inferences kept for [`InferenceStats::percentile`]
+pub const PERCENTILE_WINDOW: usize = 1024;
+
+/// Aggregate of the [`StageTimings`] of many inferences, e.g. every frame of a video.
+/// Memory is bounded: the count, sums, minimum and maximum cover every inference,
+/// percentiles the last [`PERCENTILE_WINDOW`] ones
+#[derive(Debug, Clone, Default)]
+pub struct InferenceStats {
+    count: usize,
+    sums: [Duration; 5],
+    min: Option<StageTimings>,
+    max: Option<StageTimings>,
+    recent: VecDeque<StageTimings>,
+}
+
+impl InferenceStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, timings: StageTimings) {
+        self.count += 1;
+        for (sum, stage) in self.sums.iter_mut().zip(timings.stages()) {
+            *sum += stage;
+        }
+        let combine = |aggregate: Option<StageTimings>,
+                       pick: fn(Duration, Duration) -> Duration| {
+            let stages = match aggregate {
+                Some(aggregate) => {
+                    let mut stages = aggregate.stages();
+                    for (stage, new) in stages.iter_mut().zip(timings.stages()) {
+                        *stage = pick(*stage, new);
+                    }
+                    stages
+                }
+                None => timings.stages(),
+            };
+            Some(StageTimings::from_stages(stages))
+        };
+        self.min = combine(self.min, Duration::min);
+        self.max = combine(self.max, Duration::max);
+
+        if self.recent.len() == PERCENTILE_WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(timings);
+    }
+
+    /// Timings of the last [`PERCENTILE_WINDOW`] inferences, in the order they were recorded
+    pub fn recent(&self) -> impl Iterator<Item = &StageTimings> {
+        self.recent.iter()
+    }
+
+    /// Number of recorded inferences
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Mean time of each stage
+    pub fn mean(&self) -> StageTimings {
+        if self.count == 0 {
+            return StageTimings::default();
+        }
+        StageTimings::from_stages(self.sums.map(|sum| sum / self.count as u32))
+    }
+
+    /// Shortest time of each stage, computed independently per stage
+    pub fn min(&self) -> StageTimings {
+        self.min.unwrap_or_default()
+    }
+
+    /// Longest time of each stage, computed independently per stage
+    pub fn max(&self) -> StageTimings {
+        self.max.unwrap_or_default()
+    }
+
+    /// Nearest rank percentile of each stage over the last [`PERCENTILE_WINDOW`] inferences,
+    /// computed independently per stage. `percentile` is clamped to [0, 100]
+    pub fn percentile(&self, percentile: f32) -> StageTimings {
+        if self.recent.is_empty() {
+            return StageTimings::default();
+        }
+        let rank = ((percentile.clamp(0.0, 100.0) / 100.0) * self.recent.len() as f32).ceil();
+        let idx = (rank as usize).clamp(1, self.recent.len()) - 1;
+
+        let mut stages = [Duration::ZERO; 5];
+        for (stage_idx, stage) in stages.iter_mut().enumerate() {
+            let mut values = self
+                .recent
+                .iter()
+                .map(|timings| timings.stages()[stage_idx])
+                .collect::<Vec<Duration>>();
+            values.sort_unstable();
+            *stage = values[idx];
+        }
+        StageTimings::from_stages(stages)
+    }
+
+    pub fn p95(&self) -> StageTimings {
+        self.percentile(95.0)
+    }
+
+    /// Inferences per second, based on the total time spent inside the inference stages
+    pub fn fps(&self) -> f32 {
+        let total: Duration = self.sums.iter().sum();
+        if total.is_zero() {
+            return 0.0;
+        }
+        self.count as f32 / total.as_secs_f32()
+    }
+}
+
+impl fmt::Display for InferenceStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (mean, p95) = (self.mean(), self.p95());
+        writeln!(f, "{} inferences, {:.2} FPS", self.count(), self.fps())?;
+        writeln!(f, "{:<20}{:>12}{:>12}", "stage", "mean", "p95")?;
+        let rows = [
+            ("preprocess", mean.preprocess, p95.preprocess),
+            ("compute", mean.compute, p95.compute),
+            ("output parsing", mean.output_parsing, p95.output_parsing),
+            (
+                "confidence filter",
+                mean.confidence_filter,
+                p95.confidence_filter,
+            ),
+            ("nms", mean.nms, p95.nms),
+            ("total", mean.total(), p95.total()),
+        ];
+        for (name, mean, p95) in rows {
+            writeln!(f, "{:<20}{:>12.2?}{:>12.2?}", name, mean, p95)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InferenceStats, StageTimings, PERCENTILE_WINDOW};
+    use std::time::Duration;
+
+    fn timings_ms(compute: u64) -> StageTimings {
+        StageTimings {
+            preprocess: Duration::from_millis(10),
+            compute: Duration::from_millis(compute),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_inference_stats() {
+        let mut stats = InferenceStats::new();
+        assert_eq!(stats.fps(), 0.0);
+        assert_eq!(stats.mean(), StageTimings::default());
+
+        for compute in 1..=20 {
+            stats.record(timings_ms(compute * 10));
+        }
+
+        assert_eq!(stats.count(), 20);
+        assert_eq!(stats.mean().preprocess, Duration::from_millis(10));
+        assert_eq!(stats.mean().compute, Duration::from_millis(105));
+        assert_eq!(stats.p95().compute, Duration::from_millis(190));
+        assert_eq!(stats.percentile(100.0).compute, Duration::from_millis(200));
+        assert_eq!(stats.percentile(0.0).compute, Duration::from_millis(10));
+
+        // 20 inferences in 0.2s of preprocessing + 2.1s of compute
+        assert!((stats.fps() - 20.0 / 2.3).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_inference_stats_window() {
+        let mut stats = InferenceStats::new();
+        for compute in 1..=2 * PERCENTILE_WINDOW as u64 {
+            stats.record(timings_ms(compute));
+        }
+
+        // the aggregates cover every inference, percentiles only the recent ones
+        assert_eq!(stats.count(), 2 * PERCENTILE_WINDOW);
+        assert_eq!(stats.recent().count(), PERCENTILE_WINDOW);
+        assert_eq!(stats.min().compute, Duration::from_millis(1));
+        assert_eq!(stats.max().compute, Duration::from_millis(2048));
+        assert_eq!(
+            stats.percentile(0.0).compute,
+            Duration::from_millis(PERCENTILE_WINDOW as u64 + 1)
+        );
+        assert_eq!(stats.mean().compute, Duration::from_micros(1024500));
+    }
+}
@@ -0,0 +1,490 @@
+//! Multi-object tracking across video frames
+//!
+//! [`Tracker`] links the detections of consecutive frames and assigns a stable track id to each object,
+//! following SORT / ByteTrack:
+//! - every track predicts its next bounding box with a constant velocity Kalman filter
+//! - high confidence detections are matched to the predicted boxes by IoU
+//! - low confidence detections are then matched to the remaining confirmed tracks,
+//!   which keeps tracks alive through occlusions and motion blur
+//! - unmatched high confidence detections start new tracks, which are confirmed (and get an id)
+//!   after `min_hits` consecutive matches
+//! - tracks without a match for more than `max_age` frames are removed
+//! - low confidence detections that did not match a track are dropped from the results
+//!
+//! Low confidence detections only reach the tracker if the confidence threshold
+//! passed to inference is lowered below [`TrackerConfig::high_conf_thresh`], e.g. to `0.1`.
+//!
+//! ```rust,ignore
+//! let mut tracker = Tracker::new(TrackerConfig::default());
+//! for frame in frames {
+//!     let results = tracker.update(yolo.infer_image(&conf_thresh, &iou_thresh, &frame)?);
+//!     for result in &results {
+//!         println!("{:?} {}", result.track_id(), result.class());
+//!     }
+//! }
+//! ```
+
+use imageproc::rect::Rect;
+use nalgebra::{SMatrix, SVector};
+
+use crate::{
+    process::{iou_xyxy, rect_to_xyxy},
+    InferenceResult,
+};
+
+type StateVector = SVector<f32, 7>;
+type StateMatrix = SMatrix<f32, 7, 7>;
+type Measurement = SVector<f32, 4>;
+type MeasurementMatrix = SMatrix<f32, 4, 7>;
+
+/// Constant velocity Kalman filter over `[cx, cy, area, aspect ratio]` of a box,
+/// with the velocity of the first three as hidden state
+#[derive(Debug, Clone)]
+struct KalmanBoxFilter {
+    state: StateVector,
+    covariance: StateMatrix,
+}
+
+impl KalmanBoxFilter {
+    fn new(b_box: [f32; 4]) -> Self {
+        let mut state = StateVector::zeros();
+        state
+            .fixed_rows_mut::<4>(0)
+            .copy_from(&xyxy_to_measurement(b_box));
+
+        // Velocities are unknown at first, give them a high uncertainty
+        let covariance =
+            StateMatrix::from_diagonal(&StateVector::from([10., 10., 10., 10., 1e4, 1e4, 1e4]));
+        KalmanBoxFilter { state, covariance }
+    }
+
+    fn transition() -> StateMatrix {
+        let mut transition = StateMatrix::identity();
+        transition[(0, 4)] = 1.;
+        transition[(1, 5)] = 1.;
+        transition[(2, 6)] = 1.;
+        transition
+    }
+
+    /// Advances the state by one frame, returns the predicted box
+    fn predict(&mut self) -> [f32; 4] {
+        // The area cannot shrink below zero
+        if self.state[2] + self.state[6] <= 0. {
+            self.state[6] = 0.;
+        }
+        let process_noise =
+            StateMatrix::from_diagonal(&StateVector::from([1., 1., 1., 1., 0.01, 0.01, 1e-4]));
+
+        let transition = Self::transition();
+        self.state = transition * self.state;
+        self.covariance = transition * self.covariance * transition.transpose() + process_noise;
+        self.b_box()
+    }
+
+    /// Corrects the state with an observed box
+    fn update(&mut self, b_box: [f32; 4]) {
+        let observation = MeasurementMatrix::identity();
+        let measurement_noise =
+            SMatrix::<f32, 4, 4>::from_diagonal(&Measurement::from([1., 1., 10., 10.]));
+
+        let innovation = xyxy_to_measurement(b_box) - observation * self.state;
+        let innovation_covariance =
+            observation * self.covariance * observation.transpose() + measurement_noise;
+        let Some(inverse) = innovation_covariance.try_inverse() else {
+            return;
+        };
+        let gain = self.covariance * observation.transpose() * inverse;
+
+        self.state += gain * innovation;
+        self.covariance = (StateMatrix::identity() - gain * observation) * self.covariance;
+    }
+
+    fn b_box(&self) -> [f32; 4] {
+        let (cx, cy, area, ratio) = (self.state[0], self.state[1], self.state[2], self.state[3]);
+        let width = (area * ratio).max(0.).sqrt();
+        let height = if width > 0. { area / width } else { 0. };
+        [
+            cx - width / 2.,
+            cy - height / 2.,
+            cx + width / 2.,
+            cy + height / 2.,
+        ]
+    }
+}
+
+fn xyxy_to_measurement(b_box: [f32; 4]) -> Measurement {
+    let width = b_box[2] - b_box[0];
+    let height = b_box[3] - b_box[1];
+    Measurement::from([
+        b_box[0] + width / 2.,
+        b_box[1] + height / 2.,
+        width * height,
+        width / height.max(f32::EPSILON),
+    ])
+}
+
+/// Settings of a [`Tracker`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrackerConfig {
+    /// Detections at or above this confidence are matched first and may start new tracks
+    pub high_conf_thresh: f32,
+    /// Detections below this confidence are ignored
+    pub low_conf_thresh: f32,
+    /// Minimum IoU between a detection and the predicted box of a track to match them
+    pub match_iou_thresh: f32,
+    /// Number of frames a track is kept without any matching detection
+    pub max_age: u32,
+    /// Number of consecutive matches before a track is confirmed and gets an id
+    pub min_hits: u32,
+    /// Only match detections and tracks of the same class
+    pub class_aware: bool,
+}
+
+impl Default for TrackerConfig {
+    fn default() -> Self {
+        TrackerConfig {
+            high_conf_thresh: 0.5,
+            low_conf_thresh: 0.1,
+            match_iou_thresh: 0.3,
+            max_age: 30,
+            min_hits: 3,
+            class_aware: true,
+        }
+    }
+}
+
+/// An object followed across frames
+#[derive(Debug, Clone)]
+pub struct Track {
+    id: Option<u64>,
+    class: String,
+    confidence: f32,
+    filter: KalmanBoxFilter,
+    hit_streak: u32,
+    frames_since_update: u32,
+}
+
+impl Track {
+    /// Id of the track, `None` until the track is confirmed
+    pub fn id(&self) -> Option<u64> {
+        self.id
+    }
+
+    pub fn class(&self) -> &str {
+        &self.class
+    }
+
+    /// Confidence of the last matched detection
+    pub fn confidence(&self) -> f32 {
+        self.confidence
+    }
+
+    /// Current estimate of the bounding box
+    pub fn b_box(&self) -> Rect {
+        let [x1, y1, x2, y2] = self.filter.b_box();
+        Rect::at(x1.round() as i32, y1.round() as i32).of_size(
+            (x2 - x1).round().max(1.) as u32,
+            (y2 - y1).round().max(1.) as u32,
+        )
+    }
+
+    pub fn is_confirmed(&self) -> bool {
+        self.id.is_some()
+    }
+
+    /// Number of frames since the track was last matched to a detection
+    pub fn frames_since_update(&self) -> u32 {
+        self.frames_since_update
+    }
+}
+
+/// SORT / ByteTrack style multi-object tracker, see the [module documentation](self)
+#[derive(Debug, Clone)]
+pub struct Tracker {
+    config: TrackerConfig,
+    tracks: Vec<Track>,
+    next_id: u64,
+    frame_count: u64,
+}
+
+impl Default for Tracker {
+    fn default() -> Self {
+        Self::new(TrackerConfig::default())
+    }
+}
+
+impl Tracker {
+    pub fn new(config: TrackerConfig) -> Self {
+        Tracker {
+            config,
+            tracks: Vec::new(),
+            next_id: 1,
+            frame_count: 0,
+        }
+    }
+
+    pub fn config(&self) -> &TrackerConfig {
+        &self.config
+    }
+
+    /// Tracks alive after the last update, including unconfirmed ones
+    pub fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+
+    /// Forgets every track, ids start over from 1
+    pub fn reset(&mut self) {
+        *self = Self::new(self.config);
+    }
+
+    /// Associates the detections of the next frame with the current tracks.
+    /// Returns the detections matched to a track or above [`TrackerConfig::high_conf_thresh`],
+    /// in their original order, with [`InferenceResult::track_id`] set for those matched to a confirmed track.
+    /// Other low confidence detections are likely false positives and are dropped, as in ByteTrack
+    pub fn update(&mut self, mut frame_results: Vec<InferenceResult>) -> Vec<InferenceResult> {
+        self.frame_count += 1;
+
+        let predicted = self
+            .tracks
+            .iter_mut()
+            .map(|track| track.filter.predict())
+            .collect::<Vec<[f32; 4]>>();
+        let detections = frame_results
+            .iter()
+            .map(|result| rect_to_xyxy(result.b_box))
+            .collect::<Vec<[f32; 4]>>();
+
+        let (high, low): (Vec<usize>, Vec<usize>) = (0..frame_results.len())
+            .filter(|&idx| frame_results[idx].confidence >= self.config.low_conf_thresh)
+            .partition(|&idx| frame_results[idx].confidence >= self.config.high_conf_thresh);
+
+        // First association, high confidence detections with every track
+        let all_tracks = (0..self.tracks.len()).collect::<Vec<usize>>();
+        let mut matches =
+            self.associate(&high, &all_tracks, &frame_results, &detections, &predicted);
+
+        // Second association, low confidence detections with the remaining confirmed tracks
+        let remaining_tracks = all_tracks
+            .into_iter()
+            .filter(|&trk| {
+                self.tracks[trk].is_confirmed() && !matches.iter().any(|&(_, t)| t == trk)
+            })
+            .collect::<Vec<usize>>();
+        matches.extend(self.associate(
+            &low,
+            &remaining_tracks,
+            &frame_results,
+            &detections,
+            &predicted,
+        ));
+
+        for track in self.tracks.iter_mut() {
+            track.frames_since_update += 1;
+        }
+        for &(det, trk) in &matches {
+            let track = &mut self.tracks[trk];
+            track.filter.update(detections[det]);
+            track.confidence = frame_results[det].confidence;
+            track.hit_streak += 1;
+            track.frames_since_update = 0;
+        }
+        for track in self.tracks.iter_mut() {
+            if track.frames_since_update > 0 {
+                track.hit_streak = 0;
+            }
+        }
+
+        // Unmatched high confidence detections start new tracks
+        let mut matched_tracks = matches.clone();
+        for &det in &high {
+            if matches.iter().any(|&(d, _)| d == det) {
+                continue;
+            }
+            self.tracks.push(Track {
+                id: None,
+                class: frame_results[det].class.clone(),
+                confidence: frame_results[det].confidence,
+                filter: KalmanBoxFilter::new(detections[det]),
+                hit_streak: 1,
+                frames_since_update: 0,
+            });
+            matched_tracks.push((det, self.tracks.len() - 1));
+        }
+
+        // Confirm tracks, the very first frames confirm immediately so that ids show up from the start
+        for track in self.tracks.iter_mut() {
+            let confirmed = track.hit_streak >= self.config.min_hits
+                || self.frame_count <= self.config.min_hits as u64;
+            if track.id.is_none() && track.frames_since_update == 0 && confirmed {
+                track.id = Some(self.next_id);
+                self.next_id += 1;
+            }
+        }
+
+        let mut keep = vec![false; frame_results.len()];
+        for (det, trk) in matched_tracks {
+            frame_results[det].track_id = self.tracks[trk].id;
+            keep[det] = true;
+        }
+
+        // Unconfirmed tracks are dropped as soon as they miss a frame
+        let max_age = self.config.max_age;
+        self.tracks.retain(|track| {
+            track.frames_since_update <= max_age
+                && (track.is_confirmed() || track.frames_since_update == 0)
+        });
+
+        frame_results
+            .into_iter()
+            .zip(keep)
+            .filter_map(|(result, keep)| keep.then_some(result))
+            .collect()
+    }
+
+    /// Greedy matching of detections and tracks by descending IoU
+    fn associate(
+        &self,
+        dets: &[usize],
+        trks: &[usize],
+        frame_results: &[InferenceResult],
+        detections: &[[f32; 4]],
+        predicted: &[[f32; 4]],
+    ) -> Vec<(usize, usize)> {
+        let mut candidates = Vec::new();
+        for &det in dets {
+            for &trk in trks {
+                if self.config.class_aware && frame_results[det].class != self.tracks[trk].class {
+                    continue;
+                }
+                let iou = iou_xyxy(detections[det], predicted[trk]);
+                if iou >= self.config.match_iou_thresh {
+                    candidates.push((iou, det, trk));
+                }
+            }
+        }
+        candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let mut matches: Vec<(usize, usize)> = Vec::new();
+        for (_, det, trk) in candidates {
+            if matches.iter().any(|&(d, t)| d == det || t == trk) {
+                continue;
+            }
+            matches.push((det, trk));
+        }
+        matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Tracker, TrackerConfig};
+    use crate::InferenceResult;
+    use imageproc::rect::Rect;
+
+    fn detection(x: i32, y: i32, class: &str, confidence: f32) -> InferenceResult {
+        InferenceResult::new(Rect::at(x, y).of_size(20, 40), class, 0, confidence)
+    }
+
+    fn track_ids(results: &[InferenceResult]) -> Vec<Option<u64>> {
+        results.iter().map(|r| r.track_id).collect()
+    }
+
+    #[test]
+    fn test_tracks_keep_their_id() {
+        let mut tracker = Tracker::default();
+        for frame in 0..20 {
+            let results = tracker.update(vec![
+                // moving right, then seen with low confidence
+                detection(
+                    10 + 5 * frame,
+                    10,
+                    "person",
+                    if frame < 15 { 0.9 } else { 0.3 },
+                ),
+                // static
+                detection(200, 100, "car", 0.8),
+            ]);
+            assert_eq!(track_ids(&results), vec![Some(1), Some(2)]);
+        }
+    }
+
+    #[test]
+    fn test_new_tracks_need_min_hits() {
+        let mut tracker = Tracker::default();
+        for _ in 0..5 {
+            tracker.update(vec![detection(10, 10, "person", 0.9)]);
+        }
+
+        // A new object after the first frames is confirmed on its 3rd consecutive detection
+        let new_object = || {
+            vec![
+                detection(10, 10, "person", 0.9),
+                detection(300, 10, "dog", 0.9),
+            ]
+        };
+        assert_eq!(
+            track_ids(&tracker.update(new_object())),
+            vec![Some(1), None]
+        );
+        assert_eq!(
+            track_ids(&tracker.update(new_object())),
+            vec![Some(1), None]
+        );
+        assert_eq!(
+            track_ids(&tracker.update(new_object())),
+            vec![Some(1), Some(2)]
+        );
+    }
+
+    #[test]
+    fn test_unmatched_low_confidence_detections_are_dropped() {
+        let mut tracker = Tracker::default();
+        let results = tracker.update(vec![
+            detection(10, 10, "person", 0.9),
+            // between the low and high thresholds, without a track to match
+            detection(200, 100, "car", 0.3),
+            // below the low threshold
+            detection(300, 100, "dog", 0.05),
+        ]);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].class, "person");
+
+        // a low confidence detection of a tracked object is kept
+        let results = tracker.update(vec![
+            detection(12, 10, "person", 0.3),
+            detection(200, 100, "car", 0.3),
+        ]);
+        assert_eq!(track_ids(&results), vec![Some(1)]);
+    }
+
+    #[test]
+    fn test_lost_tracks_are_removed() {
+        let mut tracker = Tracker::new(TrackerConfig {
+            max_age: 2,
+            min_hits: 1,
+            ..Default::default()
+        });
+        assert_eq!(
+            track_ids(&tracker.update(vec![detection(10, 10, "person", 0.9)])),
+            vec![Some(1)]
+        );
+
+        // Short occlusion, the track survives
+        tracker.update(Vec::new());
+        tracker.update(Vec::new());
+        assert_eq!(
+            track_ids(&tracker.update(vec![detection(10, 10, "person", 0.9)])),
+            vec![Some(1)]
+        );
+
+        // Long occlusion, the object comes back as a new track
+        for _ in 0..3 {
+            tracker.update(Vec::new());
+        }
+        assert!(tracker.tracks().is_empty());
+        assert_eq!(
+            track_ids(&tracker.update(vec![detection(10, 10, "person", 0.9)])),
+            vec![Some(2)]
+        );
+    }
+}
@@ -1,51 +1,389 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use crate::InferenceResult;
-use image::{Rgb, RgbImage};
+use image::{imageops, GenericImageView, Rgb, RgbImage};
 use imageproc::{
-    drawing::{draw_hollow_rect_mut, draw_text_mut},
+    drawing::{draw_filled_rect_mut, draw_hollow_rect_mut, draw_text_mut, text_size},
+    filter::gaussian_blur_f32,
     rect::Rect,
 };
 use rusttype::{Font, Scale};
 
-/// Convieience Function to draw bounding boxes to image
+/// 20 distinct colors, class `i` is drawn with color `i % 20`
+pub const DEFAULT_PALETTE: [Rgb<u8>; 20] = [
+    Rgb([255, 56, 56]),
+    Rgb([255, 157, 151]),
+    Rgb([255, 112, 31]),
+    Rgb([255, 178, 29]),
+    Rgb([207, 210, 49]),
+    Rgb([72, 249, 10]),
+    Rgb([146, 204, 23]),
+    Rgb([61, 219, 134]),
+    Rgb([26, 147, 52]),
+    Rgb([0, 212, 187]),
+    Rgb([44, 153, 168]),
+    Rgb([0, 194, 255]),
+    Rgb([52, 69, 147]),
+    Rgb([100, 115, 255]),
+    Rgb([0, 24, 236]),
+    Rgb([132, 56, 255]),
+    Rgb([82, 0, 133]),
+    Rgb([203, 56, 255]),
+    Rgb([255, 149, 200]),
+    Rgb([255, 55, 199]),
+];
+
+/// Size of the label text
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FontSize {
+    /// Height in pixels
+    Fixed(f32),
+    /// Fraction of the image height, so that labels look the same at any resolution
+    Relative(f32),
+}
+
+/// How [`draw_bounding_boxes_with_style`] draws boxes and labels
+#[derive(Debug, Clone, PartialEq)]
+pub struct DrawStyle {
+    /// Colors by class id, see [`DrawStyle::color`]
+    pub palette: Vec<Rgb<u8>>,
+    /// Thickness of the box outline in pixels
+    pub thickness: u32,
+    pub font_size: FontSize,
+    /// Smallest font size in pixels when the size is [`FontSize::Relative`]
+    pub min_font_size: f32,
+    /// Draw the label text on a background of the box color
+    pub label_background: bool,
+    /// Number of decimals of the confidence
+    pub confidence_precision: usize,
+    pub show_labels: bool,
+    /// Add the confidence to the label
+    pub show_confidence: bool,
+}
+
+impl Default for DrawStyle {
+    fn default() -> Self {
+        DrawStyle {
+            palette: DEFAULT_PALETTE.to_vec(),
+            thickness: 2,
+            font_size: FontSize::Relative(0.025),
+            min_font_size: 12.0,
+            label_background: true,
+            confidence_precision: 2,
+            show_labels: true,
+            show_confidence: true,
+        }
+    }
+}
+
+impl DrawStyle {
+    /// Color of a class, deterministic by class id
+    pub fn color(&self, class_id: usize) -> Rgb<u8> {
+        match self.palette.len() {
+            0 => DEFAULT_PALETTE[class_id % DEFAULT_PALETTE.len()],
+            len => self.palette[class_id % len],
+        }
+    }
+
+    /// Font size in pixels for an image of height `image_height`
+    pub fn font_size(&self, image_height: u32) -> f32 {
+        match self.font_size {
+            FontSize::Fixed(size) => size,
+            FontSize::Relative(fraction) => {
+                (image_height as f32 * fraction).max(self.min_font_size)
+            }
+        }
+    }
+
+    /// Label text of a detection, `None` if labels are hidden
+    pub fn label(&self, result: &InferenceResult) -> Option<String> {
+        if !self.show_labels {
+            return None;
+        }
+        let mut label = match result.track_id {
+            Some(track_id) => format!("#{} {}", track_id, result.class),
+            None => result.class.clone(),
+        };
+        if self.show_confidence {
+            label = format!(
+                "{} {:.*}",
+                label, self.confidence_precision, result.confidence
+            );
+        }
+        Some(label)
+    }
+}
+
+/// ClearSans Medium, embedded in the library so that drawing labels needs no file access.
+/// The font is parsed on the first call, later calls return a cheap clone of it.
+///
+/// Requires the `bundled-font` feature
+#[cfg(feature = "bundled-font")]
+pub fn default_font() -> Font<'static> {
+    static FONT: std::sync::OnceLock<Font<'static>> = std::sync::OnceLock::new();
+    FONT.get_or_init(|| {
+        Font::try_from_bytes(include_bytes!("../assets/ClearSans-Medium.ttf"))
+            .expect("the bundled font is a valid TrueType font")
+    })
+    .clone()
+}
+
+/// Draws bounding boxes to image with the bundled font and the default [`DrawStyle`]
+///
+/// Requires the `bundled-font` feature
+#[cfg(feature = "bundled-font")]
+pub fn draw_bounding_boxes(rgb_image: RgbImage, vec_results: &[InferenceResult]) -> RgbImage {
+    draw_bounding_boxes_on_mut_image(rgb_image, vec_results, &default_font())
+}
+
+/// Convieience Function to draw bounding boxes to image, with the default [`DrawStyle`]
 pub fn draw_bounding_boxes_on_mut_image(
+    rgb_image: RgbImage,
+    vec_results: &[InferenceResult],
+    font: &Font<'static>,
+) -> RgbImage {
+    draw_bounding_boxes_with_style(rgb_image, vec_results, font, &DrawStyle::default())
+}
+
+/// Draws bounding boxes and labels to image, as described by `style`.
+/// Labels are placed with [`layout_labels`], after every box so that no box is drawn over a label
+pub fn draw_bounding_boxes_with_style(
     mut rgb_image: RgbImage,
-    vec_results: &Vec<InferenceResult>,
+    vec_results: &[InferenceResult],
     font: &Font<'static>,
+    style: &DrawStyle,
 ) -> RgbImage {
-    let color = Rgb([0u8, 0u8, 255u8]);
+    let scale = Scale::uniform(style.font_size(rgb_image.height()));
+    let padding = (scale.y / 5.0).round() as i32;
 
     for result in vec_results {
-        let conf = result.confidence;
-
+        let color = style.color(result.class_id);
         let rect: Rect = result.b_box;
 
-        draw_hollow_rect_mut(&mut rgb_image, rect, color);
+        // Thicker outlines grow inwards, so that they stay within the image for boxes on its edges
+        for inset in 0..style.thickness.max(1) {
+            if rect.width() <= 2 * inset || rect.height() <= 2 * inset {
+                break;
+            }
+            let inner = Rect::at(rect.left() + inset as i32, rect.top() + inset as i32)
+                .of_size(rect.width() - 2 * inset, rect.height() - 2 * inset);
+            draw_hollow_rect_mut(&mut rgb_image, inner, color);
+        }
+    }
 
-        let class_conf = format!("{} {}", result.class, conf);
+    let labels = vec_results
+        .iter()
+        .filter_map(|result| Some((result, style.label(result)?)))
+        .collect::<Vec<(&InferenceResult, String)>>();
+    let label_sizes = labels
+        .iter()
+        .map(|(_, label)| {
+            let (text_width, text_height) = text_size(scale, font, label);
+            (
+                (text_width + 2 * padding).max(1) as u32,
+                (text_height + 2 * padding).max(1) as u32,
+            )
+        })
+        .collect::<Vec<(u32, u32)>>();
+    let boxes = labels
+        .iter()
+        .map(|(result, _)| result.b_box)
+        .collect::<Vec<Rect>>();
+    let placements = layout_labels(rgb_image.width(), rgb_image.height(), &boxes, &label_sizes);
 
-        // TODO: calc font size based on image dims
-        // let height = 12.4;
-        // let scale = Scale {
-        //     x: height * 2.0,
-        //     y: height,
-        // };
+    for ((result, label), placement) in labels.iter().zip(placements) {
+        let color = style.color(result.class_id);
+        let text_color = if style.label_background {
+            draw_filled_rect_mut(&mut rgb_image, placement, color);
+            Rgb([255u8, 255u8, 255u8])
+        } else {
+            color
+        };
 
-        let scale = Scale::uniform(25.0);
         draw_text_mut(
             &mut rgb_image,
-            color,
-            rect.left() + 5,
-            rect.top() - 30,
+            text_color,
+            placement.left() + padding,
+            placement.top() + padding,
             scale,
             font,
-            &class_conf,
+            label,
         );
     }
     rgb_image
 }
 
+/// Places a label of size `label_sizes[i]` (width, height) for each box in `boxes`, within an image
+/// of `image_width` x `image_height`. Returns the rectangle of each label.
+///
+/// Each label goes to the first of these positions that fits in the image vertically and
+/// does not overlap the labels placed before it:
+/// above the box, inside its top edge, below the box, inside its bottom edge.
+/// When every position overlaps, the one overlapping the least is used.
+/// Labels are moved left to stay within the image, so labels placed first get the best positions,
+/// e.g. the most confident detections
+pub fn layout_labels(
+    image_width: u32,
+    image_height: u32,
+    boxes: &[Rect],
+    label_sizes: &[(u32, u32)],
+) -> Vec<Rect> {
+    let mut placed: Vec<Rect> = Vec::with_capacity(boxes.len());
+
+    for (b_box, &(width, height)) in boxes.iter().zip(label_sizes) {
+        let (width, height) = (width.max(1), height.max(1));
+        let left = b_box.left().min(image_width as i32 - width as i32).max(0);
+        let bottom = b_box.top() + b_box.height() as i32;
+
+        let candidate_tops = [
+            b_box.top() - height as i32,
+            b_box.top(),
+            bottom,
+            bottom - height as i32,
+        ];
+        let mut candidates = candidate_tops
+            .into_iter()
+            .filter(|&top| top >= 0 && top + height as i32 <= image_height as i32)
+            .map(|top| Rect::at(left, top).of_size(width, height))
+            .collect::<Vec<Rect>>();
+        if candidates.is_empty() {
+            // Taller than the image, or than the space around the box
+            let top = candidate_tops[0]
+                .min(image_height as i32 - height as i32)
+                .max(0);
+            candidates.push(Rect::at(left, top).of_size(width, height));
+        }
+
+        let overlap = |candidate: &Rect| -> u32 {
+            placed
+                .iter()
+                .filter_map(|label| label.intersect(*candidate))
+                .map(|intersection| intersection.width() * intersection.height())
+                .sum()
+        };
+        let best = candidates
+            .iter()
+            .copied()
+            .min_by_key(|candidate| overlap(candidate))
+            .unwrap_or(candidates[0]);
+        placed.push(best);
+    }
+    placed
+}
+
+/// How [`anonymize_mut_image`] hides the content of a bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Anonymization {
+    /// Gaussian blur with standard deviation `sigma` in pixels
+    Blur { sigma: f32 },
+    /// Replace blocks of `block_size` x `block_size` pixels by their average color
+    Pixelate { block_size: u32 },
+    /// Paint the box with a solid color
+    Fill(Rgb<u8>),
+}
+
+impl Default for Anonymization {
+    fn default() -> Self {
+        Anonymization::Pixelate { block_size: 16 }
+    }
+}
+
+/// Anonymizes the bounding boxes of the detections of `classes` in the image,
+/// or of every detection if `classes` is empty. E.g. to publish footage with people unrecognizable
+pub fn anonymize_mut_image(
+    mut rgb_image: RgbImage,
+    vec_results: &[InferenceResult],
+    classes: &[String],
+    anonymization: Anonymization,
+) -> RgbImage {
+    let image_rect = Rect::at(0, 0).of_size(rgb_image.width().max(1), rgb_image.height().max(1));
+
+    for result in vec_results {
+        if !classes.is_empty() && !classes.contains(&result.class) {
+            continue;
+        }
+        let Some(rect) = result.b_box.intersect(image_rect) else {
+            continue;
+        };
+        let (x, y) = (rect.left() as u32, rect.top() as u32);
+
+        match anonymization {
+            Anonymization::Blur { sigma } => {
+                let region = imageops::crop_imm(&rgb_image, x, y, rect.width(), rect.height());
+                let blurred = gaussian_blur_f32(&region.to_image(), sigma.max(0.1));
+                imageops::replace(&mut rgb_image, &blurred, x as i64, y as i64);
+            }
+            Anonymization::Pixelate { block_size } => {
+                let block_size = block_size.max(1);
+                for block_y in (y..y + rect.height()).step_by(block_size as usize) {
+                    for block_x in (x..x + rect.width()).step_by(block_size as usize) {
+                        let width = block_size.min(x + rect.width() - block_x);
+                        let height = block_size.min(y + rect.height() - block_y);
+                        let block = imageops::crop_imm(&rgb_image, block_x, block_y, width, height);
+
+                        let mut sums = [0u64; 3];
+                        for (_, _, pixel) in block.pixels() {
+                            for (sum, channel) in sums.iter_mut().zip(pixel.0) {
+                                *sum += channel as u64;
+                            }
+                        }
+                        let count = (width * height) as u64;
+                        let average = Rgb(sums.map(|sum| ((sum + count / 2) / count) as u8));
+
+                        let block_rect =
+                            Rect::at(block_x as i32, block_y as i32).of_size(width, height);
+                        draw_filled_rect_mut(&mut rgb_image, block_rect, average);
+                    }
+                }
+            }
+            Anonymization::Fill(color) => draw_filled_rect_mut(&mut rgb_image, rect, color),
+        }
+    }
+    rgb_image
+}
+
+/// Paths of the image files in `dir` and its sub directories, sorted.
+/// Files are recognized as images by their extension
+pub fn image_files<P: AsRef<Path>>(dir: P) -> std::io::Result<Vec<PathBuf>> {
+    let mut images = Vec::new();
+    let mut dirs = vec![dir.as_ref().to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if image::ImageFormat::from_path(&path).is_ok() {
+                images.push(path);
+            }
+        }
+    }
+    images.sort();
+    Ok(images)
+}
+
+/// An error followed by its sources, e.g. `error reading image: Format error decoding Png: ...`
+pub fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(&format!(": {}", cause));
+        source = cause.source();
+    }
+    message
+}
+
+/// Whether a path is inside `dir`, also when one of them is relative or goes through a symlink,
+/// e.g. to leave the outputs of a previous run out of the inputs
+pub(crate) fn in_dir(dir: &Path) -> impl Fn(&Path) -> bool + '_ {
+    let canonical_dir = dir.canonicalize().ok();
+    move |path| {
+        path.starts_with(dir)
+            || canonical_dir
+                .as_ref()
+                .is_some_and(|dir| path.canonicalize().is_ok_and(|path| path.starts_with(dir)))
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum FontLoadError {
     #[error("error parsing bytes as font")]
@@ -66,3 +404,153 @@ where
     let font: Font = Font::try_from_vec(bytes).ok_or(FontLoadError::InvalidFontData)?;
     Ok(font)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        anonymize_mut_image, layout_labels, Anonymization, DrawStyle, FontSize, DEFAULT_PALETTE,
+    };
+    use crate::InferenceResult;
+    use imageproc::rect::Rect;
+
+    #[test]
+    fn test_draw_style() {
+        let result = InferenceResult::new(Rect::at(0, 0).of_size(10, 10), "person", 21, 0.87654);
+
+        let style = DrawStyle::default();
+        assert_eq!(style.color(21), DEFAULT_PALETTE[1]);
+        assert_eq!(style.label(&result).unwrap(), "person 0.88");
+        assert_eq!(style.font_size(1080), 27.0);
+        assert_eq!(style.font_size(100), 12.0);
+
+        let style = DrawStyle {
+            confidence_precision: 3,
+            font_size: FontSize::Fixed(20.0),
+            ..Default::default()
+        };
+        assert_eq!(style.label(&result).unwrap(), "person 0.877");
+        assert_eq!(style.font_size(1080), 20.0);
+
+        let hide_confidence = DrawStyle {
+            show_confidence: false,
+            ..Default::default()
+        };
+        assert_eq!(hide_confidence.label(&result).unwrap(), "person");
+
+        let hide_labels = DrawStyle {
+            show_labels: false,
+            ..Default::default()
+        };
+        assert!(hide_labels.label(&result).is_none());
+    }
+
+    #[test]
+    fn test_layout_labels() {
+        let size = (40, 10);
+
+        // room above the box
+        let boxes = [Rect::at(10, 50).of_size(50, 50)];
+        assert_eq!(
+            layout_labels(100, 100, &boxes, &[size]),
+            vec![Rect::at(10, 40).of_size(40, 10)]
+        );
+
+        // box touching the top edge, label inside the box
+        let boxes = [Rect::at(10, 0).of_size(50, 50)];
+        assert_eq!(
+            layout_labels(100, 100, &boxes, &[size]),
+            vec![Rect::at(10, 0).of_size(40, 10)]
+        );
+
+        // box touching the right edge, label moved left
+        let boxes = [Rect::at(80, 50).of_size(20, 20)];
+        assert_eq!(
+            layout_labels(100, 100, &boxes, &[size]),
+            vec![Rect::at(60, 40).of_size(40, 10)]
+        );
+
+        // two boxes at the same height, the second label goes inside its box
+        let boxes = [
+            Rect::at(10, 50).of_size(50, 30),
+            Rect::at(20, 50).of_size(50, 30),
+        ];
+        assert_eq!(
+            layout_labels(100, 100, &boxes, &[size, size]),
+            vec![
+                Rect::at(10, 40).of_size(40, 10),
+                Rect::at(20, 50).of_size(40, 10)
+            ]
+        );
+
+        // label larger than the image
+        let boxes = [Rect::at(0, 0).of_size(10, 10)];
+        assert_eq!(
+            layout_labels(20, 5, &boxes, &[size]),
+            vec![Rect::at(0, 0).of_size(40, 10)]
+        );
+    }
+
+    #[cfg(feature = "bundled-font")]
+    #[test]
+    fn test_draw_with_bundled_font() {
+        use super::draw_bounding_boxes;
+        use image::{Rgb, RgbImage};
+
+        let result = InferenceResult::new(Rect::at(0, 0).of_size(60, 60), "person", 0, 0.9);
+        let image = draw_bounding_boxes(RgbImage::new(100, 100), &[result]);
+
+        // The box touches the top edge, its label background is drawn inside the box
+        assert_eq!(image.get_pixel(1, 0), &DEFAULT_PALETTE[0]);
+        assert_eq!(image.get_pixel(1, 12), &DEFAULT_PALETTE[0]);
+        assert_eq!(image.get_pixel(30, 40), &Rgb([0, 0, 0]));
+    }
+
+    #[test]
+    fn test_anonymize() {
+        use image::{Rgb, RgbImage};
+
+        // vertical stripes, black and white
+        let image = RgbImage::from_fn(40, 40, |x, _| match x % 2 {
+            0 => Rgb([0, 0, 0]),
+            _ => Rgb([255, 255, 255]),
+        });
+        let result = |x: i32, class: &str| {
+            InferenceResult::new(Rect::at(x, 0).of_size(10, 10), class, 0, 0.9)
+        };
+        let results = [result(0, "person"), result(20, "car"), result(35, "person")];
+        let people = ["person".to_string()];
+
+        let filled = anonymize_mut_image(
+            image.clone(),
+            &results,
+            &people,
+            Anonymization::Fill(Rgb([255, 0, 0])),
+        );
+        assert_eq!(filled.get_pixel(0, 0), &Rgb([255, 0, 0]));
+        // the box on the right edge is clipped to the image
+        assert_eq!(filled.get_pixel(39, 9), &Rgb([255, 0, 0]));
+        // other classes and the rest of the image are untouched
+        assert_eq!(filled.get_pixel(21, 0), image.get_pixel(21, 0));
+        assert_eq!(filled.get_pixel(1, 10), image.get_pixel(1, 10));
+
+        let pixelated = anonymize_mut_image(
+            image.clone(),
+            &results,
+            &people,
+            Anonymization::Pixelate { block_size: 4 },
+        );
+        assert_eq!(pixelated.get_pixel(0, 0), &Rgb([128, 128, 128]));
+        assert_eq!(pixelated.get_pixel(21, 0), image.get_pixel(21, 0));
+
+        // every class
+        let blurred = anonymize_mut_image(
+            image.clone(),
+            &results,
+            &[],
+            Anonymization::Blur { sigma: 2.0 },
+        );
+        let Rgb([value, _, _]) = *blurred.get_pixel(25, 5);
+        assert!((100..=155).contains(&value));
+        assert_eq!(blurred.get_pixel(15, 5), image.get_pixel(15, 5));
+    }
+}